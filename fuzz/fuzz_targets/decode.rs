@@ -0,0 +1,38 @@
+//! Feed arbitrary bytes to the decoder, checking that it never panics and that whatever it
+//! decodes can be encoded again.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oasis_cbor_value::{
+    reader::{self, DuplicateKeyHandling, ReaderOptions},
+    writer,
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Strictly decoded data is canonical, so it re-encodes to the very same bytes.
+    if let Ok(value) = reader::read(data) {
+        let mut encoded_cbor = Vec::new();
+        writer::write(value, &mut encoded_cbor).expect("decoded value should re-encode");
+        assert_eq!(encoded_cbor, data);
+        assert!(reader::is_canonical(data));
+    }
+
+    // Non-strictly decoded data may be non-canonical in other ways, so it is only re-encoded,
+    // with each handling of duplicate keys.
+    for on_duplicate_key in [
+        DuplicateKeyHandling::Error,
+        DuplicateKeyHandling::KeepFirst,
+        DuplicateKeyHandling::KeepLast,
+    ] {
+        let options = ReaderOptions {
+            on_duplicate_key,
+            ..ReaderOptions::default()
+        };
+        if let Ok(value) = reader::read_non_strict_with_options(data, Some(i8::MAX), &options) {
+            let mut encoded_cbor = Vec::new();
+            let _ = writer::write(value, &mut encoded_cbor);
+        }
+    }
+
+    let _ = reader::canonicalize_bytes(data);
+});
@@ -39,6 +39,19 @@ pub trait Decode {
     }
 }
 
+/// Decode an optional value for hand-written code paths, e.g. the result of looking up a field in a
+/// map, with an absent value (`None`), null and undefined all decoding as `None`, and any other
+/// value decoding as `Some`, same as [`Decode`] does for `Option<T>` fields.
+///
+/// Note that this does not distinguish an absent field from a field present with a null value. If
+/// that matters, match on the looked up value before decoding it.
+pub fn decode_option<T: Decode>(value: Option<Value>) -> Result<Option<T>, DecodeError> {
+    match value {
+        None => Ok(None),
+        Some(value) => Option::<T>::try_from_cbor_value(value),
+    }
+}
+
 #[impl_for_tuples(1, 10)]
 impl Decode for Tuple {
     fn try_default() -> Result<Self, DecodeError> {
@@ -233,6 +246,7 @@ impl<const N: usize> Decode for [u8; N] {
     }
 }
 
+/// Both null and undefined are decoded as `None`, while `None` is encoded as null.
 impl<T: Decode> Decode for Option<T> {
     fn try_default() -> Result<Self, DecodeError> {
         Ok(Default::default())
@@ -240,7 +254,7 @@ impl<T: Decode> Decode for Option<T> {
 
     fn try_from_cbor_value(value: Value) -> Result<Self, DecodeError> {
         match value {
-            Value::Simple(SimpleValue::NullValue) => Ok(None),
+            Value::Simple(SimpleValue::NullValue | SimpleValue::Undefined) => Ok(None),
             _ => Ok(Some(T::try_from_cbor_value(value)?)),
         }
     }
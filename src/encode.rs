@@ -27,6 +27,17 @@ pub trait Encode {
     }
 }
 
+/// Encode an optional value for hand-written code paths, with `None` as null and `Some(v)` as the
+/// encoding of `v`, same as [`Encode`] does for `Option<T>`.
+///
+/// Note that an absent map field and a field present with a null value are different encodings,
+/// although both decode as `None` (see [`decode_option`](crate::decode::decode_option)). Derived
+/// map encodings omit `#[cbor(optional)]` fields that are `None` instead of encoding them as null;
+/// do likewise by only adding the field when the value is `Some`.
+pub fn encode_option<T: Encode>(value: Option<T>) -> Value {
+    value.into_cbor_value()
+}
+
 /// Trait for types that always encode as CBOR maps.
 pub trait EncodeAsMap: Encode {
     /// Encode the type into a CBOR Map.
@@ -215,6 +226,9 @@ impl<const N: usize> Encode for [u8; N] {
     }
 }
 
+/// `None` is encoded as null by convention; set
+/// [`WriterOptions::write_undefined_for_none`](crate::writer::WriterOptions::write_undefined_for_none)
+/// to emit undefined instead. Decoding accepts both.
 impl<T: Encode> Encode for Option<T> {
     fn is_empty(&self) -> bool {
         self.is_none()
@@ -38,8 +38,13 @@ pub enum DecodeError {
 }
 
 impl From<reader::DecoderError> for DecodeError {
-    fn from(_e: reader::DecoderError) -> Self {
-        DecodeError::ParsingFailed
+    fn from(e: reader::DecoderError) -> Self {
+        match e {
+            reader::DecoderError::SchemaMismatch => DecodeError::UnexpectedType,
+            reader::DecoderError::MissingMapKey => DecodeError::MissingField,
+            reader::DecoderError::UnknownMapKey => DecodeError::UnknownField,
+            _ => DecodeError::ParsingFailed,
+        }
     }
 }
 
@@ -61,6 +66,19 @@ where
     T::try_from_cbor_value_default(value)
 }
 
+/// Convert CBOR-encoded data into the given type, after checking that it matches the given schema.
+///
+/// Data not matching the schema fails with [`DecodeError::UnexpectedType`],
+/// [`DecodeError::MissingField`] or [`DecodeError::UnknownField`] before any decoding takes place.
+pub fn from_slice_checked<T>(data: &[u8], schema: &schema::Schema) -> Result<T, DecodeError>
+where
+    T: Decode,
+{
+    let value = reader::read_nested(data, Some(MAX_NESTING_LEVEL))?;
+    schema.check(&value)?;
+    T::try_from_cbor_value_default(value)
+}
+
 /// Convert high-level CBOR representation into the given type.
 ///
 /// This is the same as calling `T::try_from_cbor_value(value)`.
@@ -31,11 +31,13 @@ impl<'de> de::Deserializer<'de> for Deserializer {
             Value::Array(_) => self.deserialize_seq(visitor),
             Value::Map(_) => self.deserialize_map(visitor),
             Value::Tag(_, _) => Err(Error::UnsupportedType("cbor tag")),
+            Value::Float(_) => Err(Error::UnsupportedType("float")),
             Value::Simple(v) => match v {
                 SimpleValue::FalseValue => visitor.visit_bool(false),
                 SimpleValue::TrueValue => visitor.visit_bool(true),
                 SimpleValue::NullValue => visitor.visit_unit(),
                 SimpleValue::Undefined => visitor.visit_unit(),
+                SimpleValue::Unassigned(_) => Err(Error::UnsupportedType("simple value")),
             },
         };
         value
@@ -86,7 +88,7 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         V: Visitor<'de>,
     {
         match self.0 {
-            Value::Simple(SimpleValue::NullValue) => visitor.visit_none(),
+            Value::Simple(SimpleValue::NullValue | SimpleValue::Undefined) => visitor.visit_none(),
             x => visitor.visit_some(Self(x)),
         }
     }
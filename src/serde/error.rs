@@ -59,6 +59,8 @@ pub(crate) fn unexpected(v: &Value) -> de::Unexpected {
             SimpleValue::TrueValue => de::Unexpected::Bool(true),
             SimpleValue::NullValue => de::Unexpected::Other("null"),
             SimpleValue::Undefined => de::Unexpected::Other("undefined"),
+            SimpleValue::Unassigned(_) => de::Unexpected::Other("simple value"),
         },
+        Value::Float(f) => de::Unexpected::Float(*f),
     }
 }
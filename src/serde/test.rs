@@ -211,6 +211,10 @@ fn test_option() {
 
     let s = Some("foo".to_string());
     assert_compat_roundtrip(s, str!("foo"));
+
+    // Undefined is decoded as None too.
+    let n: Option<u16> = crate::serde::from_slice(&[0xF7]).unwrap();
+    assert_eq!(n, None);
 }
 
 #[test]
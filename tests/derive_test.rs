@@ -585,6 +585,42 @@ fn test_field_reorder() {
     );
 }
 
+#[test]
+fn test_from_slice_checked() {
+    use cbor::schema::Schema;
+
+    let fields = [
+        (cbor::cbor_text!("bytes"), Schema::Bytes),
+        (cbor::cbor_text!("foo"), Schema::Uint),
+    ];
+    let schema = Schema::Map(&fields);
+
+    let b = B {
+        foo: 42,
+        bytes: vec![0x01],
+    };
+    let res: B = cbor::from_slice_checked(&cbor::to_vec(b.clone()), &schema).unwrap();
+    assert_eq!(res, b);
+
+    let mistyped =
+        cbor::to_vec(cbor::cbor_map! {"foo" => "42", "bytes" => cbor::cbor_bytes!(vec![])});
+    let res: Result<B, _> = cbor::from_slice_checked(&mistyped, &schema);
+    assert!(matches!(res, Err(cbor::DecodeError::UnexpectedType)));
+
+    let missing = cbor::to_vec(cbor::cbor_map! {"foo" => 42});
+    let res: Result<B, _> = cbor::from_slice_checked(&missing, &schema);
+    assert!(matches!(res, Err(cbor::DecodeError::MissingField)));
+
+    let extra = cbor::to_vec(
+        cbor::cbor_map! {"foo" => 42, "bytes" => cbor::cbor_bytes!(vec![]), "extra" => true},
+    );
+    let res: Result<B, _> = cbor::from_slice_checked(&extra, &schema);
+    assert!(matches!(res, Err(cbor::DecodeError::UnknownField)));
+
+    let res: Result<B, _> = cbor::from_slice_checked(&[0xA1], &schema);
+    assert!(matches!(res, Err(cbor::DecodeError::ParsingFailed)));
+}
+
 #[test]
 fn test_extra_fields() {
     // Extra field at the end.
@@ -719,6 +755,79 @@ fn test_with_default() {
     assert_eq!(enc, vec![0xA0]);
 }
 
+#[test]
+fn test_option_null_undefined() {
+    // None is encoded as null.
+    assert_eq!(cbor::to_vec(None::<u64>), vec![0xF6]);
+    assert_eq!(cbor::to_vec(vec![Some(1u64), None]), vec![0x82, 0x01, 0xF6]);
+
+    // Both null and undefined are decoded as None.
+    let dec: Option<u64> = cbor::from_slice(&[0xF6]).unwrap();
+    assert_eq!(dec, None);
+    let dec: Option<u64> = cbor::from_slice(&[0xF7]).unwrap();
+    assert_eq!(dec, None);
+    let dec: Vec<Option<String>> = cbor::from_slice(&[0x82, 0xF7, 0x60]).unwrap();
+    assert_eq!(dec, vec![None, Some("".to_owned())]);
+
+    // Undefined can be emitted instead, and still round-trips.
+    let options = cbor::writer::WriterOptions {
+        write_undefined_for_none: true,
+        ..Default::default()
+    };
+    let mut enc = Vec::new();
+    cbor::writer::write_with_options(cbor::to_value(None::<u64>), &mut enc, None, &options)
+        .unwrap();
+    assert_eq!(enc, vec![0xF7]);
+    let dec: Option<u64> = cbor::from_slice(&enc).unwrap();
+    assert_eq!(dec, None);
+}
+
+#[test]
+fn test_encode_decode_option() {
+    use cbor::{decode::decode_option, encode::encode_option};
+
+    assert_eq!(encode_option(Some(1u64)), cbor::Value::Unsigned(1));
+    assert_eq!(
+        encode_option(None::<u64>),
+        cbor::Value::Simple(cbor::SimpleValue::NullValue)
+    );
+
+    let lookup = |map: &[(cbor::Value, cbor::Value)], key: &str| {
+        map.iter()
+            .find(|(k, _)| k.as_text() == Some(key))
+            .map(|(_, v)| v.clone())
+    };
+    // Map with a present value, a present null and a present undefined value.
+    let map = vec![
+        (cbor::Value::from("a"), cbor::Value::Unsigned(1)),
+        (
+            cbor::Value::from("b"),
+            cbor::Value::Simple(cbor::SimpleValue::NullValue),
+        ),
+        (
+            cbor::Value::from("c"),
+            cbor::Value::Simple(cbor::SimpleValue::Undefined),
+        ),
+    ];
+    let dec: Option<u64> = decode_option(lookup(&map, "a")).unwrap();
+    assert_eq!(dec, Some(1));
+    // Both present-null and absent values decode as None.
+    let dec: Option<u64> = decode_option(lookup(&map, "b")).unwrap();
+    assert_eq!(dec, None);
+    let dec: Option<u64> = decode_option(lookup(&map, "c")).unwrap();
+    assert_eq!(dec, None);
+    let dec: Option<u64> = decode_option(lookup(&map, "d")).unwrap();
+    assert_eq!(dec, None);
+    // Which can still be told apart by looking up the value first.
+    assert!(lookup(&map, "b").is_some());
+    assert!(lookup(&map, "d").is_none());
+
+    assert!(matches!(
+        decode_option::<u64>(Some(cbor::Value::from("a"))),
+        Err(cbor::DecodeError::UnexpectedType)
+    ));
+}
+
 #[test]
 fn test_with_optional() {
     // Optional unit struct is not encoded.
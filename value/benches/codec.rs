@@ -0,0 +1,267 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks for encoding and decoding representative payloads.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use oasis_cbor_value::{
+    cbor_array, cbor_array_vec, cbor_bytes, cbor_map_collection, cbor_text, reader, writer, Value,
+};
+
+/// System allocator counting allocations, including reallocations, so that benchmarks reusing
+/// buffers can report how many they save.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Average number of allocations made by a call of `f`, after a first call filling any reused
+/// buffers.
+fn allocations_per_call(mut f: impl FnMut()) -> f64 {
+    const CALLS: usize = 100;
+    f();
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CALLS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - start) as f64 / CALLS as f64
+}
+
+/// Map of 1000 integer keys to integer values, in canonical order.
+fn flat_integer_map() -> Value {
+    cbor_map_collection!((0..1000u64)
+        .map(|i| (Value::Unsigned(i), Value::Unsigned(i * 1000)))
+        .collect::<Vec<_>>())
+}
+
+/// Array of 10000 integers of varying sizes.
+fn large_array() -> Value {
+    cbor_array_vec!((0..10000u64).map(|i| i * i).collect::<Vec<_>>())
+}
+
+/// Arrays nested 100 levels deep, within the default nesting limit.
+fn deeply_nested() -> Value {
+    let mut value = cbor_text!("leaf");
+    for i in 0..100i64 {
+        value = cbor_array![i, value];
+    }
+    value
+}
+
+/// Byte string of 1 MiB.
+fn large_byte_string() -> Value {
+    cbor_bytes!(vec![0xA5; 1 << 20])
+}
+
+/// Map of 1000 text keys of varying lengths, in reverse canonical order so that encoding must sort
+/// them.
+fn unsorted_map() -> Value {
+    let mut entries = (0..1000u64)
+        .map(|i| (Value::from(format!("key-{}", i)), Value::Unsigned(i)))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.0.cmp(&a.0));
+    cbor_map_collection!(entries)
+}
+
+fn payloads() -> Vec<(&'static str, Value)> {
+    vec![
+        ("flat_integer_map", flat_integer_map()),
+        ("large_array", large_array()),
+        ("deeply_nested", deeply_nested()),
+        ("large_byte_string", large_byte_string()),
+        ("unsorted_map", unsorted_map()),
+    ]
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, value) in payloads() {
+        let mut encoded_cbor = Vec::new();
+        writer::write_ref(&value, &mut encoded_cbor).unwrap();
+        group.throughput(Throughput::Bytes(encoded_cbor.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                encoded_cbor.clear();
+                writer::write_ref(black_box(&value), &mut encoded_cbor).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Encode into a new vector, either grown as needed or pre-allocated from
+/// [`Value::encoded_len_hint`], computed along or known in advance.
+fn bench_encode_with_capacity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_with_capacity");
+    let value = large_array();
+    let capacity = value.encoded_len_hint();
+    group.throughput(Throughput::Bytes(capacity as u64));
+    group.bench_function("growing", |b| {
+        b.iter(|| {
+            let mut encoded_cbor = Vec::new();
+            writer::write_ref(black_box(&value), &mut encoded_cbor).unwrap();
+            encoded_cbor
+        })
+    });
+    group.bench_function("pre_allocated", |b| {
+        b.iter(|| {
+            let mut encoded_cbor = Vec::with_capacity(black_box(&value).encoded_len_hint());
+            writer::write_ref(black_box(&value), &mut encoded_cbor).unwrap();
+            encoded_cbor
+        })
+    });
+    group.bench_function("pre_allocated_known", |b| {
+        b.iter(|| {
+            let mut encoded_cbor = Vec::with_capacity(black_box(capacity));
+            writer::write_ref(black_box(&value), &mut encoded_cbor).unwrap();
+            encoded_cbor
+        })
+    });
+    group.finish();
+}
+
+/// Encode a map of 1000 integer keys in reverse canonical order, sorted numerically, against the
+/// same map with one text key added, whose keys must all be encoded to be sorted.
+fn bench_integer_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("integer_keys");
+    let entries = (0..1000u64)
+        .rev()
+        .map(|i| (Value::Unsigned(i * 1000), Value::Unsigned(i)))
+        .collect::<Vec<_>>();
+    let mut mixed_entries = entries.clone();
+    mixed_entries.push((cbor_text!("key"), Value::Unsigned(0)));
+    let payloads = vec![
+        ("integer", cbor_map_collection!(entries)),
+        ("mixed", cbor_map_collection!(mixed_entries)),
+    ];
+    for (name, value) in payloads {
+        let mut encoded_cbor = Vec::new();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                encoded_cbor.clear();
+                writer::write_ref(black_box(&value), &mut encoded_cbor).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, value) in payloads() {
+        let mut encoded_cbor = Vec::new();
+        writer::write(value, &mut encoded_cbor).unwrap();
+        group.throughput(Throughput::Bytes(encoded_cbor.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| reader::read(black_box(&encoded_cbor)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Encode with an [`writer::Encoder`] kept across iterations, reusing its scratch buffers, and
+/// report the allocations per call against [`writer::write_ref`].
+fn bench_encode_reused(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_reused");
+    for (name, value) in payloads() {
+        let mut encoder = writer::Encoder::new();
+        let mut encoded_cbor = Vec::new();
+        let fresh_allocations = allocations_per_call(|| {
+            encoded_cbor.clear();
+            writer::write_ref(&value, &mut encoded_cbor).unwrap();
+        });
+        let reused_allocations = allocations_per_call(|| {
+            encoded_cbor.clear();
+            encoder.encode(&value, &mut encoded_cbor).unwrap();
+        });
+        println!(
+            "encode_reused/{}: {} allocations per call, {} without reuse",
+            name, reused_allocations, fresh_allocations
+        );
+        group.throughput(Throughput::Bytes(encoded_cbor.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                encoded_cbor.clear();
+                encoder
+                    .encode(black_box(&value), &mut encoded_cbor)
+                    .unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Decode with a [`reader::Decoder`] kept across iterations, recycling each decoded value, and
+/// report the allocations per call against [`reader::read`].
+fn bench_decode_reused(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_reused");
+    for (name, value) in payloads() {
+        let mut decoder = reader::Decoder::new();
+        let mut encoded_cbor = Vec::new();
+        writer::write(value, &mut encoded_cbor).unwrap();
+        let fresh_allocations = allocations_per_call(|| {
+            reader::read(&encoded_cbor).unwrap();
+        });
+        let reused_allocations = allocations_per_call(|| {
+            let value = decoder.decode(&encoded_cbor).unwrap();
+            decoder.recycle(value);
+        });
+        println!(
+            "decode_reused/{}: {} allocations per call, {} without reuse",
+            name, reused_allocations, fresh_allocations
+        );
+        group.throughput(Throughput::Bytes(encoded_cbor.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let value = decoder.decode(black_box(&encoded_cbor)).unwrap();
+                decoder.recycle(value);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_encode,
+    bench_encode_with_capacity,
+    bench_integer_keys,
+    bench_decode,
+    bench_encode_reused,
+    bench_decode_reused
+);
+criterion_main!(benches);
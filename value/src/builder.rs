@@ -0,0 +1,167 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builders for constructing CBOR arrays and maps incrementally.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use super::{
+    values::Value,
+    writer::{self, EncoderError},
+};
+
+/// Builder for a [`Value::Map`], for when entries are not known up front as with `cbor_map!`.
+#[derive(Clone, Debug, Default)]
+pub struct MapBuilder {
+    entries: Vec<(Value, Value)>,
+}
+
+impl MapBuilder {
+    /// Create an empty map builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty map builder with room for the given number of entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        MapBuilder {
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Add an entry to the map.
+    pub fn insert<K: Into<Value>, V: Into<Value>>(&mut self, key: K, value: V) -> &mut Self {
+        self.entries.push((key.into(), value.into()));
+        self
+    }
+
+    /// Return the number of entries added so far.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return whether no entries were added so far.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the map of the entries added so far, in insertion order, leaving the builder empty.
+    /// Duplicate keys are left for the writer to reject.
+    pub fn build(&mut self) -> Value {
+        Value::Map(mem::take(&mut self.entries))
+    }
+
+    /// Return the map of the entries added so far like [`MapBuilder::build`], failing with
+    /// [`EncoderError::DuplicateMapKey`] (and leaving the builder untouched) if a key was added
+    /// more than once.
+    pub fn try_build(&mut self) -> Result<Value, EncoderError> {
+        let mut keys: Vec<_> = self.entries.iter().map(|(k, _)| k).collect();
+        keys.sort();
+        if let Some(w) = keys.windows(2).find(|w| w[0] == w[1]) {
+            return Err(writer::duplicate_key_error(w[0]));
+        }
+        Ok(self.build())
+    }
+}
+
+/// Builder for a [`Value::Array`], for when elements are not known up front as with `cbor_array!`.
+#[derive(Clone, Debug, Default)]
+pub struct ArrayBuilder {
+    elements: Vec<Value>,
+}
+
+impl ArrayBuilder {
+    /// Create an empty array builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty array builder with room for the given number of elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArrayBuilder {
+            elements: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Append an element to the array.
+    pub fn push<V: Into<Value>>(&mut self, value: V) -> &mut Self {
+        self.elements.push(value.into());
+        self
+    }
+
+    /// Return the number of elements added so far.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Return whether no elements were added so far.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Return the array of the elements added so far, leaving the builder empty.
+    pub fn build(&mut self) -> Value {
+        Value::Array(mem::take(&mut self.elements))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{cbor_array, cbor_map, cbor_null};
+
+    #[test]
+    fn test_map_builder() {
+        let mut builder = MapBuilder::new();
+        assert!(builder.is_empty());
+        for i in 0..3 {
+            builder.insert(i, i * 2);
+        }
+        builder.insert("a", cbor_null!()).insert(-1, vec![0x01u8]);
+        assert_eq!(builder.len(), 5);
+        assert_eq!(
+            builder.try_build(),
+            Ok(cbor_map! {0 => 0, 1 => 2, 2 => 4, "a" => cbor_null!(), -1 => vec![0x01u8]})
+        );
+        assert!(builder.is_empty());
+        assert_eq!(MapBuilder::with_capacity(2).build(), cbor_map! {});
+
+        builder.insert(1, "a").insert(2, "b").insert(1, "c");
+        assert_eq!(
+            builder.try_build(),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x01] })
+        );
+        assert_eq!(builder.len(), 3);
+        assert_eq!(builder.build(), cbor_map! {1 => "a", 2 => "b", 1 => "c"});
+    }
+
+    #[test]
+    fn test_array_builder() {
+        let mut builder = ArrayBuilder::with_capacity(4);
+        for i in 0..3 {
+            builder.push(i);
+        }
+        builder.push("a").push(cbor_array![true]);
+        assert_eq!(builder.len(), 5);
+        assert_eq!(
+            builder.build(),
+            cbor_array![0, 1, 2, "a", cbor_array![true]]
+        );
+        assert!(builder.is_empty());
+        assert_eq!(ArrayBuilder::new().build(), cbor_array![]);
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functionality for rendering CBOR values in diagnostic notation (RFC 8949 §8).
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use super::values::{SimpleValue, Value};
+
+/// Render a [`Value`] in CBOR diagnostic notation, e.g. `{"a": 1, "b": [2, 3]}`.
+///
+/// Byte strings are rendered as `h'...'` hex, tags as `N(inner)` and floats always carry a
+/// decimal point or exponent so that they can be told apart from integers.
+pub fn to_diagnostic(value: &Value) -> String {
+    let mut diagnostic = String::new();
+    // Writing to a string never fails.
+    write_diagnostic(value, &mut diagnostic).unwrap();
+    diagnostic
+}
+
+/// Render a [`Value`] in CBOR diagnostic notation as [`to_diagnostic`] does, but over multiple
+/// lines: each element of a non-empty array or map goes on its own line, indented by `indent`
+/// spaces per level of nesting.  This is only meant for reading large values, e.g. in logs.
+pub fn to_diagnostic_pretty(value: &Value, indent: usize) -> String {
+    let mut diagnostic = String::new();
+    // Writing to a string never fails.
+    write_diagnostic_pretty(value, indent, 0, &mut diagnostic).unwrap();
+    diagnostic
+}
+
+fn write_diagnostic_pretty(
+    value: &Value,
+    indent: usize,
+    level: usize,
+    out: &mut String,
+) -> fmt::Result {
+    let write_line_start = |level: usize, out: &mut String| {
+        out.push('\n');
+        out.extend(core::iter::repeat(' ').take(indent * level));
+    };
+    match value {
+        Value::Array(array) if !array.is_empty() => {
+            out.push('[');
+            for (i, el) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_line_start(level + 1, out);
+                write_diagnostic_pretty(el, indent, level + 1, out)?;
+            }
+            write_line_start(level, out);
+            out.push(']');
+            Ok(())
+        }
+        Value::Map(map) if !map.is_empty() => {
+            out.push('{');
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_line_start(level + 1, out);
+                write_diagnostic_pretty(k, indent, level + 1, out)?;
+                out.push_str(": ");
+                write_diagnostic_pretty(v, indent, level + 1, out)?;
+            }
+            write_line_start(level, out);
+            out.push('}');
+            Ok(())
+        }
+        Value::Tag(tag, inner_value) => {
+            write!(out, "{}(", tag)?;
+            write_diagnostic_pretty(inner_value, indent, level, out)?;
+            out.push(')');
+            Ok(())
+        }
+        _ => write_diagnostic(value, out),
+    }
+}
+
+fn write_diagnostic(value: &Value, out: &mut String) -> fmt::Result {
+    match value {
+        Value::Unsigned(unsigned) => write!(out, "{}", unsigned),
+        Value::Negative(negative) => write!(out, "{}", negative),
+        Value::ByteString(byte_string) => {
+            out.push_str("h'");
+            for byte in byte_string {
+                write!(out, "{:02x}", byte)?;
+            }
+            out.push('\'');
+            Ok(())
+        }
+        Value::TextString(text_string) => write_text(text_string, out),
+        Value::Array(array) => {
+            out.push('[');
+            for (i, el) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(el, out)?;
+            }
+            out.push(']');
+            Ok(())
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(k, out)?;
+                out.push_str(": ");
+                write_diagnostic(v, out)?;
+            }
+            out.push('}');
+            Ok(())
+        }
+        Value::Tag(tag, inner_value) => {
+            write!(out, "{}(", tag)?;
+            write_diagnostic(inner_value, out)?;
+            out.push(')');
+            Ok(())
+        }
+        Value::Simple(simple_value) => match simple_value {
+            SimpleValue::FalseValue => out.write_str("false"),
+            SimpleValue::TrueValue => out.write_str("true"),
+            SimpleValue::NullValue => out.write_str("null"),
+            SimpleValue::Undefined => out.write_str("undefined"),
+            SimpleValue::Unassigned(int) => write!(out, "simple({})", int),
+        },
+        Value::Float(float) if float.is_nan() => out.write_str("NaN"),
+        Value::Float(float) if float.is_infinite() => out.write_str(if *float > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }),
+        // The debug representation always includes a decimal point or an exponent.
+        Value::Float(float) => write!(out, "{:?}", float),
+    }
+}
+
+/// Write a text string as a double-quoted, JSON-style escaped string.
+pub(crate) fn write_text(text_string: &str, out: &mut String) -> fmt::Result {
+    out.push('"');
+    for c in text_string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{
+        cbor_array, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null, cbor_simple,
+        cbor_tagged, cbor_text, cbor_true, cbor_undefined,
+    };
+
+    #[test]
+    fn test_to_diagnostic() {
+        let cases = vec![
+            (cbor_int!(0), "0"),
+            (cbor_int!(1000000), "1000000"),
+            (Value::Unsigned(u64::MAX), "18446744073709551615"),
+            (cbor_int!(-1), "-1"),
+            (
+                Value::Negative(-18446744073709551616),
+                "-18446744073709551616",
+            ),
+            (cbor_bytes!(vec![]), "h''"),
+            (cbor_bytes!(vec![0x01, 0xAB, 0xFF]), "h'01abff'"),
+            (cbor_text!(""), "\"\""),
+            (cbor_text!("IETF"), "\"IETF\""),
+            (cbor_text!("\"\\\n\u{1}ü"), "\"\\\"\\\\\\n\\u0001ü\""),
+            (cbor_array![], "[]"),
+            (cbor_array![1, cbor_array![2, 3]], "[1, [2, 3]]"),
+            (cbor_map! {}, "{}"),
+            (
+                cbor_map! {"a" => 1, "b" => cbor_array![2, 3]},
+                "{\"a\": 1, \"b\": [2, 3]}",
+            ),
+            (cbor_tagged!(1, cbor_int!(1363896240)), "1(1363896240)"),
+            (cbor_false!(), "false"),
+            (cbor_true!(), "true"),
+            (cbor_null!(), "null"),
+            (cbor_undefined!(), "undefined"),
+            (cbor_simple!(16), "simple(16)"),
+            (Value::Float(1.0), "1.0"),
+            (Value::Float(-4.1), "-4.1"),
+            (Value::Float(1.0e300), "1e300"),
+            (Value::Float(f64::INFINITY), "Infinity"),
+            (Value::Float(f64::NEG_INFINITY), "-Infinity"),
+            (Value::Float(f64::NAN), "NaN"),
+        ];
+        for (value, diagnostic) in cases {
+            assert_eq!(to_diagnostic(&value), diagnostic);
+        }
+    }
+
+    #[test]
+    fn test_to_diagnostic_pretty() {
+        let value = cbor_map! {
+            "a" => 1,
+            "b" => cbor_array![2, cbor_array![], cbor_tagged!(1, cbor_map! {3 => "c"})],
+            "c" => cbor_map! {},
+        };
+        assert_eq!(
+            to_diagnostic_pretty(&value, 2),
+            "{
+  \"a\": 1,
+  \"b\": [
+    2,
+    [],
+    1({
+      3: \"c\"
+    })
+  ],
+  \"c\": {}
+}"
+        );
+        assert_eq!(
+            to_diagnostic_pretty(&cbor_array![1, cbor_array![2]], 0),
+            "[\n1,\n[\n2\n]\n]"
+        );
+
+        // Scalars and empty containers are rendered as by `to_diagnostic`.
+        let values = vec![
+            cbor_int!(-1),
+            cbor_text!("a\nb"),
+            cbor_bytes!(vec![0x01]),
+            cbor_array![],
+            cbor_map! {},
+            cbor_tagged!(1, cbor_int!(2)),
+            Value::Float(1.5),
+        ];
+        for value in values {
+            assert_eq!(to_diagnostic_pretty(&value, 4), to_diagnostic(&value));
+        }
+    }
+}
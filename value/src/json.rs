@@ -0,0 +1,914 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functionality for converting between CBOR values and JSON, following RFC 8949 §6.1.
+//!
+//! The mapping is as follows:
+//! - Integers become numbers, or strings for those outside the range JSON numbers represent
+//!   exactly if [`JsonCborOptions::large_integers_as_strings`] is set.
+//! - Byte strings become base64url (without padding) or base64 strings, see
+//!   [`ByteStringEncoding`].
+//! - Text strings, arrays, `true`, `false` and `null` map to their JSON counterparts.
+//! - Maps become objects. Keys other than text strings are stringified: byte string keys become
+//!   their encoded string and other keys their JSON text, e.g. `1` becomes `"1"`.
+//! - Tagged values become `{"tag": N, "value": ...}`.
+//! - Undefined, as well as NaN and infinite floats, become `null`.
+//!
+//! Parsing JSON applies the mapping in reverse where possible:
+//! - Numbers without a fraction or exponent that fit become integers, all others floats.
+//! - Strings become text strings, except that with
+//!   [`JsonCborOptions::large_integers_as_strings`] set, strings holding an integer beyond
+//!   ±(2^53 - 1) become that integer.
+//! - Arrays, objects, `true`, `false` and `null` map to their CBOR counterparts.
+//!
+//! A CBOR to JSON to CBOR round trip therefore preserves integers, floats, text strings, arrays,
+//! maps with text string keys, booleans and null.  Byte strings come back as their base64 text
+//! strings, maps with other keys come back with the stringified keys, tagged values come back as
+//! `{"tag": N, "value": ...}` maps, and undefined, other simple values, NaN and infinities come back
+//! as null.
+//!
+//! With the `serde_json` feature, the same mapping converts between [`Value`] and the in-memory
+//! [`serde_json::Value`], see [`to_serde_json_with_options`] and [`from_serde_json_with_options`].
+
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "serde_json")]
+use core::convert::TryFrom;
+use core::fmt::{self, Write};
+
+use super::{
+    diagnostic::write_text,
+    values::{SimpleValue, Value, MAX_NESTING_DEPTH},
+};
+
+/// Largest integer magnitude that JSON numbers (IEEE 754 doubles) represent exactly.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Possible errors from converting between CBOR and JSON.
+#[derive(Debug, PartialEq)]
+pub enum ConvertError {
+    /// Distinct map keys stringify to the same object member name, or a JSON object has
+    /// duplicate member names.
+    DuplicateKey,
+    /// The JSON text is malformed.
+    InvalidJson,
+    /// The JSON text is nested too deeply.
+    TooMuchNesting,
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::DuplicateKey => f.write_str("duplicate object member name"),
+            ConvertError::InvalidJson => f.write_str("invalid JSON"),
+            ConvertError::TooMuchNesting => f.write_str("maximum nesting depth exceeded"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConvertError {}
+
+/// Options controlling the mapping between CBOR and JSON.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JsonCborOptions {
+    /// Encoding of byte strings as JSON strings.
+    pub byte_string_encoding: ByteStringEncoding,
+    /// Render integers beyond ±(2^53 - 1), which JSON numbers cannot represent exactly, as
+    /// strings of their decimal digits, and parse such strings back into integers.
+    pub large_integers_as_strings: bool,
+}
+
+/// Encoding of byte strings as JSON strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ByteStringEncoding {
+    /// URL-safe base64 without padding, as recommended by RFC 8949 §6.1.
+    #[default]
+    Base64Url,
+    /// Standard base64 with padding.
+    Base64,
+}
+
+/// Render a [`Value`] as JSON using the default [`JsonCborOptions`].
+pub fn to_json(value: &Value) -> Result<String, ConvertError> {
+    to_json_with_options(value, &JsonCborOptions::default())
+}
+
+/// Render a [`Value`] as JSON using the given [`JsonCborOptions`].
+pub fn to_json_with_options(
+    value: &Value,
+    options: &JsonCborOptions,
+) -> Result<String, ConvertError> {
+    let mut json = String::new();
+    write_json(value, options, &mut json)?;
+    Ok(json)
+}
+
+fn write_json(
+    value: &Value,
+    options: &JsonCborOptions,
+    out: &mut String,
+) -> Result<(), ConvertError> {
+    // Writing to a string never fails.
+    match value {
+        Value::Unsigned(unsigned)
+            if options.large_integers_as_strings && *unsigned > MAX_SAFE_INTEGER =>
+        {
+            write!(out, "\"{}\"", unsigned).unwrap()
+        }
+        Value::Unsigned(unsigned) => write!(out, "{}", unsigned).unwrap(),
+        Value::Negative(negative)
+            if options.large_integers_as_strings && *negative < -(MAX_SAFE_INTEGER as i128) =>
+        {
+            write!(out, "\"{}\"", negative).unwrap()
+        }
+        Value::Negative(negative) => write!(out, "{}", negative).unwrap(),
+        Value::ByteString(byte_string) => {
+            out.push('"');
+            write_base64(byte_string, options.byte_string_encoding, out);
+            out.push('"');
+        }
+        Value::TextString(text_string) => write_text(text_string, out).unwrap(),
+        Value::Array(array) => {
+            out.push('[');
+            for (i, el) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(el, options, out)?;
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            let mut names = Vec::with_capacity(map.len());
+            for (k, _) in map {
+                names.push(member_name(k, options)?);
+            }
+            let mut sorted_names: Vec<_> = names.iter().collect();
+            sorted_names.sort();
+            if sorted_names.windows(2).any(|w| w[0] == w[1]) {
+                return Err(ConvertError::DuplicateKey);
+            }
+            out.push('{');
+            for (i, (name, (_, v))) in names.iter().zip(map).enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_text(name, out).unwrap();
+                out.push(':');
+                write_json(v, options, out)?;
+            }
+            out.push('}');
+        }
+        Value::Tag(tag, inner_value) => {
+            write!(out, "{{\"tag\":{},\"value\":", tag).unwrap();
+            write_json(inner_value, options, out)?;
+            out.push('}');
+        }
+        Value::Simple(simple_value) => out.push_str(match simple_value {
+            SimpleValue::FalseValue => "false",
+            SimpleValue::TrueValue => "true",
+            // As suggested by RFC 8949 §6.1 for other simple values.
+            SimpleValue::NullValue | SimpleValue::Undefined | SimpleValue::Unassigned(_) => "null",
+        }),
+        Value::Float(float) if !float.is_finite() => out.push_str("null"),
+        // The debug representation always includes a decimal point or an exponent.
+        Value::Float(float) => write!(out, "{:?}", float).unwrap(),
+    }
+    Ok(())
+}
+
+/// Return the object member name for a map key.
+fn member_name(key: &Value, options: &JsonCborOptions) -> Result<String, ConvertError> {
+    match key {
+        Value::TextString(text_string) => Ok(text_string.clone()),
+        Value::ByteString(byte_string) => {
+            let mut name = String::new();
+            write_base64(byte_string, options.byte_string_encoding, &mut name);
+            Ok(name)
+        }
+        _ => to_json_with_options(key, options),
+    }
+}
+
+/// Append the base64 encoding of the given bytes.
+fn write_base64(bytes: &[u8], encoding: ByteStringEncoding, out: &mut String) {
+    let alphabet: &[u8; 64] = match encoding {
+        ByteStringEncoding::Base64Url => {
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+        }
+        ByteStringEncoding::Base64 => {
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+        }
+    };
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            out.push(alphabet[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+        if encoding == ByteStringEncoding::Base64 {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+}
+
+/// Maximum level of nesting of parsed JSON arrays and objects, as for decoding CBOR.
+const MAX_NESTING: usize = MAX_NESTING_DEPTH as usize;
+
+/// Parse JSON into a [`Value`] using the default [`JsonCborOptions`].
+pub fn from_json(json: &str) -> Result<Value, ConvertError> {
+    from_json_with_options(json, &JsonCborOptions::default())
+}
+
+/// Parse JSON into a [`Value`] using the given [`JsonCborOptions`].  See the module documentation
+/// for the mapping.  Nesting deeper than 127 levels fails with [`ConvertError::TooMuchNesting`].
+pub fn from_json_with_options(
+    json: &str,
+    options: &JsonCborOptions,
+) -> Result<Value, ConvertError> {
+    let mut parser = JsonParser {
+        remaining_json: json,
+        options,
+    };
+    let value = parser.parse_value(MAX_NESTING)?;
+    parser.skip_whitespace();
+    if !parser.remaining_json.is_empty() {
+        return Err(ConvertError::InvalidJson);
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    remaining_json: &'a str,
+    options: &'a JsonCborOptions,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.remaining_json = self
+            .remaining_json
+            .trim_start_matches([' ', '\t', '\n', '\r']);
+    }
+
+    /// Consume the given text if the remaining JSON starts with it.
+    fn eat(&mut self, text: &str) -> bool {
+        match self.remaining_json.strip_prefix(text) {
+            Some(rest) => {
+                self.remaining_json = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), ConvertError> {
+        self.skip_whitespace();
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(ConvertError::InvalidJson)
+        }
+    }
+
+    fn parse_value(&mut self, remaining_depth: usize) -> Result<Value, ConvertError> {
+        self.skip_whitespace();
+        match self.remaining_json.as_bytes().first() {
+            Some(b'[') | Some(b'{') if remaining_depth == 0 => Err(ConvertError::TooMuchNesting),
+            Some(b'[') => {
+                self.eat("[");
+                let mut array = Vec::new();
+                self.skip_whitespace();
+                if !self.eat("]") {
+                    loop {
+                        array.push(self.parse_value(remaining_depth - 1)?);
+                        self.skip_whitespace();
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Value::Array(array))
+            }
+            Some(b'{') => {
+                self.eat("{");
+                let mut map = Vec::new();
+                self.skip_whitespace();
+                if !self.eat("}") {
+                    loop {
+                        self.skip_whitespace();
+                        let key = Value::TextString(self.parse_string()?);
+                        self.expect(":")?;
+                        map.push((key, self.parse_value(remaining_depth - 1)?));
+                        self.skip_whitespace();
+                        if self.eat("}") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                let mut keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+                keys.sort();
+                if keys.windows(2).any(|w| w[0] == w[1]) {
+                    return Err(ConvertError::DuplicateKey);
+                }
+                Ok(Value::Map(map))
+            }
+            Some(b'"') => {
+                let text_string = self.parse_string()?;
+                if self.options.large_integers_as_strings {
+                    if let Some(value) = parse_large_integer(&text_string) {
+                        return Ok(value);
+                    }
+                }
+                Ok(Value::TextString(text_string))
+            }
+            Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+            _ if self.eat("true") => Ok(Value::Simple(SimpleValue::TrueValue)),
+            _ if self.eat("false") => Ok(Value::Simple(SimpleValue::FalseValue)),
+            _ if self.eat("null") => Ok(Value::Simple(SimpleValue::NullValue)),
+            _ => Err(ConvertError::InvalidJson),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ConvertError> {
+        if !self.eat("\"") {
+            return Err(ConvertError::InvalidJson);
+        }
+        let mut text_string = String::new();
+        let mut chars = self.remaining_json.char_indices();
+        loop {
+            match chars.next() {
+                Some((i, '"')) => {
+                    self.remaining_json = &self.remaining_json[i + 1..];
+                    return Ok(text_string);
+                }
+                Some((_, '\\')) => {
+                    let c = match chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => {
+                            let unit = parse_hex4(&mut chars)?;
+                            match unit {
+                                0xD800..=0xDBFF => {
+                                    // A high surrogate must be followed by an escaped low one.
+                                    if chars.next().map(|(_, c)| c) != Some('\\')
+                                        || chars.next().map(|(_, c)| c) != Some('u')
+                                    {
+                                        return Err(ConvertError::InvalidJson);
+                                    }
+                                    let low = parse_hex4(&mut chars)?;
+                                    if !(0xDC00..=0xDFFF).contains(&low) {
+                                        return Err(ConvertError::InvalidJson);
+                                    }
+                                    let code_point =
+                                        0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                                    char::from_u32(code_point).ok_or(ConvertError::InvalidJson)?
+                                }
+                                _ => char::from_u32(unit).ok_or(ConvertError::InvalidJson)?,
+                            }
+                        }
+                        _ => return Err(ConvertError::InvalidJson),
+                    };
+                    text_string.push(c);
+                }
+                Some((_, c)) if (c as u32) < 0x20 => return Err(ConvertError::InvalidJson),
+                Some((_, c)) => text_string.push(c),
+                None => return Err(ConvertError::InvalidJson),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ConvertError> {
+        let json = self.remaining_json.as_bytes();
+        let digits_from =
+            |i: usize| i + json[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+        let int_start = usize::from(json[0] == b'-');
+        let int_end = digits_from(int_start);
+        // No leading zeros, and at least one digit.
+        if int_end == int_start || (json[int_start] == b'0' && int_end > int_start + 1) {
+            return Err(ConvertError::InvalidJson);
+        }
+        let mut end = int_end;
+        if json.get(end) == Some(&b'.') {
+            let fraction_end = digits_from(end + 1);
+            if fraction_end == end + 1 {
+                return Err(ConvertError::InvalidJson);
+            }
+            end = fraction_end;
+        }
+        if matches!(json.get(end), Some(b'e') | Some(b'E')) {
+            let exponent_start =
+                end + 1 + usize::from(matches!(json.get(end + 1), Some(b'+') | Some(b'-')));
+            let exponent_end = digits_from(exponent_start);
+            if exponent_end == exponent_start {
+                return Err(ConvertError::InvalidJson);
+            }
+            end = exponent_end;
+        }
+        let (number, rest) = self.remaining_json.split_at(end);
+        self.remaining_json = rest;
+        if end == int_end {
+            if let Some(value) = parse_integer(number) {
+                return Ok(value);
+            }
+        }
+        number
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| ConvertError::InvalidJson)
+    }
+}
+
+/// Parse four hexadecimal digits of a `\u` escape.
+fn parse_hex4(chars: &mut core::str::CharIndices) -> Result<u32, ConvertError> {
+    let mut unit = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|(_, c)| c.to_digit(16))
+            .ok_or(ConvertError::InvalidJson)?;
+        unit = unit << 4 | digit;
+    }
+    Ok(unit)
+}
+
+/// Parse a decimal integer into an integer value, if it is in the range of CBOR integers.
+fn parse_integer(number: &str) -> Option<Value> {
+    match number.strip_prefix('-') {
+        Some(magnitude) => match magnitude.parse::<u128>().ok()? {
+            0 => Some(Value::Unsigned(0)),
+            magnitude if magnitude <= 1 << 64 => Some(Value::Negative(-(magnitude as i128))),
+            _ => None,
+        },
+        None => number.parse().ok().map(Value::Unsigned),
+    }
+}
+
+/// Parse an integer that [`JsonCborOptions::large_integers_as_strings`] renders as a string.
+fn parse_large_integer(text_string: &str) -> Option<Value> {
+    let digits = text_string.strip_prefix('-').unwrap_or(text_string);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || digits.starts_with('0') {
+        return None;
+    }
+    match parse_integer(text_string)? {
+        Value::Unsigned(unsigned) if unsigned <= MAX_SAFE_INTEGER => None,
+        Value::Negative(negative) if negative >= -(MAX_SAFE_INTEGER as i128) => None,
+        value => Some(value),
+    }
+}
+
+/// Convert a [`Value`] to a [`serde_json::Value`] using the given [`JsonCborOptions`], with the
+/// mapping of [`to_json_with_options`].  As a [`serde_json::Number`] cannot hold integers below
+/// `i64::MIN`, those become strings of their decimal digits.
+#[cfg(feature = "serde_json")]
+pub fn to_serde_json_with_options(
+    value: &Value,
+    options: &JsonCborOptions,
+) -> Result<serde_json::Value, ConvertError> {
+    use alloc::string::ToString;
+
+    use serde_json::Value as Json;
+
+    Ok(match value {
+        Value::Unsigned(unsigned)
+            if options.large_integers_as_strings && *unsigned > MAX_SAFE_INTEGER =>
+        {
+            Json::String(unsigned.to_string())
+        }
+        Value::Unsigned(unsigned) => Json::from(*unsigned),
+        Value::Negative(negative)
+            if options.large_integers_as_strings && *negative < -(MAX_SAFE_INTEGER as i128) =>
+        {
+            Json::String(negative.to_string())
+        }
+        Value::Negative(negative) => match i64::try_from(*negative) {
+            Ok(negative) => Json::from(negative),
+            Err(_) => Json::String(negative.to_string()),
+        },
+        Value::ByteString(byte_string) => {
+            let mut text_string = String::new();
+            write_base64(byte_string, options.byte_string_encoding, &mut text_string);
+            Json::String(text_string)
+        }
+        Value::TextString(text_string) => Json::String(text_string.clone()),
+        Value::Array(array) => Json::Array(
+            array
+                .iter()
+                .map(|el| to_serde_json_with_options(el, options))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(map) => {
+            let mut object = serde_json::Map::new();
+            for (k, v) in map {
+                let v = to_serde_json_with_options(v, options)?;
+                if object.insert(member_name(k, options)?, v).is_some() {
+                    return Err(ConvertError::DuplicateKey);
+                }
+            }
+            Json::Object(object)
+        }
+        Value::Tag(tag, inner_value) => {
+            let mut object = serde_json::Map::new();
+            object.insert("tag".into(), Json::from(*tag));
+            object.insert(
+                "value".into(),
+                to_serde_json_with_options(inner_value, options)?,
+            );
+            Json::Object(object)
+        }
+        Value::Simple(SimpleValue::FalseValue) => Json::Bool(false),
+        Value::Simple(SimpleValue::TrueValue) => Json::Bool(true),
+        Value::Simple(_) => Json::Null,
+        // Non-finite floats have no number representation, and become null.
+        Value::Float(float) => {
+            serde_json::Number::from_f64(*float).map_or(Json::Null, Json::Number)
+        }
+    })
+}
+
+/// Convert a [`serde_json::Value`] to a [`Value`] using the given [`JsonCborOptions`], with the
+/// mapping of [`from_json_with_options`].
+#[cfg(feature = "serde_json")]
+pub fn from_serde_json_with_options(json: serde_json::Value, options: &JsonCborOptions) -> Value {
+    use serde_json::Value as Json;
+
+    match json {
+        Json::Null => Value::Simple(SimpleValue::NullValue),
+        Json::Bool(false) => Value::Simple(SimpleValue::FalseValue),
+        Json::Bool(true) => Value::Simple(SimpleValue::TrueValue),
+        Json::Number(number) => number
+            .as_u64()
+            .map(Value::Unsigned)
+            .or_else(|| {
+                number
+                    .as_i64()
+                    .map(|negative| Value::Negative(negative.into()))
+            })
+            .or_else(|| number.as_f64().map(Value::Float))
+            .unwrap_or(Value::Simple(SimpleValue::NullValue)),
+        Json::String(text_string) => {
+            if options.large_integers_as_strings {
+                if let Some(value) = parse_large_integer(&text_string) {
+                    return value;
+                }
+            }
+            Value::TextString(text_string)
+        }
+        Json::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(|el| from_serde_json_with_options(el, options))
+                .collect(),
+        ),
+        Json::Object(object) => Value::Map(
+            object
+                .into_iter()
+                .map(|(k, v)| {
+                    (
+                        Value::TextString(k),
+                        from_serde_json_with_options(v, options),
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Conversion with the default [`JsonCborOptions`], see [`from_serde_json_with_options`].
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        from_serde_json_with_options(json, &JsonCborOptions::default())
+    }
+}
+
+/// Conversion with the default [`JsonCborOptions`], see [`to_serde_json_with_options`].  Tags,
+/// undefined and non-text map keys are mapped lossily, as they have no JSON counterpart.
+#[cfg(feature = "serde_json")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = ConvertError;
+
+    fn try_from(value: Value) -> Result<Self, ConvertError> {
+        to_serde_json_with_options(&value, &JsonCborOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{
+        cbor_array, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null, cbor_simple,
+        cbor_tagged, cbor_text, cbor_true, cbor_undefined,
+    };
+
+    #[test]
+    fn test_to_json() {
+        let cases = vec![
+            (cbor_int!(0), "0"),
+            (Value::Unsigned(u64::MAX), "18446744073709551615"),
+            (cbor_int!(-1000), "-1000"),
+            (cbor_bytes!(vec![]), "\"\""),
+            (cbor_bytes!(vec![0xFB]), "\"-w\""),
+            (cbor_bytes!(vec![0xFB, 0xFF]), "\"-_8\""),
+            (cbor_bytes!(vec![0x01, 0x02, 0x03]), "\"AQID\""),
+            (cbor_text!("a\"\n"), "\"a\\\"\\n\""),
+            (cbor_array![1, cbor_array![2, "b"]], "[1,[2,\"b\"]]"),
+            (
+                cbor_map! {"a" => 1, 2 => cbor_array![], b"\x01" => cbor_map! {}},
+                "{\"a\":1,\"2\":[],\"AQ\":{}}",
+            ),
+            (
+                cbor_map! {cbor_array![1, "a"] => 0},
+                "{\"[1,\\\"a\\\"]\":0}",
+            ),
+            (
+                cbor_tagged!(1, cbor_int!(1363896240)),
+                "{\"tag\":1,\"value\":1363896240}",
+            ),
+            (cbor_false!(), "false"),
+            (cbor_true!(), "true"),
+            (cbor_null!(), "null"),
+            (cbor_undefined!(), "null"),
+            (cbor_simple!(16), "null"),
+            (Value::Float(1.0), "1.0"),
+            (Value::Float(-4.1e300), "-4.1e300"),
+            (Value::Float(f64::INFINITY), "null"),
+            (Value::Float(f64::NAN), "null"),
+        ];
+        for (value, json) in cases {
+            assert_eq!(to_json(&value), Ok(json.into()));
+        }
+    }
+
+    #[test]
+    fn test_to_json_with_options() {
+        let options = JsonCborOptions {
+            byte_string_encoding: ByteStringEncoding::Base64,
+            large_integers_as_strings: true,
+        };
+        let cases = vec![
+            (cbor_bytes!(vec![0xFB]), "\"+w==\""),
+            (cbor_bytes!(vec![0xFB, 0xFF]), "\"+/8=\""),
+            (cbor_bytes!(vec![0x01, 0x02, 0x03]), "\"AQID\""),
+            (cbor_map! {b"\xFB" => 0}, "{\"+w==\":0}"),
+            (Value::Unsigned(MAX_SAFE_INTEGER), "9007199254740991"),
+            (
+                Value::Unsigned(MAX_SAFE_INTEGER + 1),
+                "\"9007199254740992\"",
+            ),
+            (
+                Value::Negative(-(MAX_SAFE_INTEGER as i128)),
+                "-9007199254740991",
+            ),
+            (
+                Value::Negative(-(MAX_SAFE_INTEGER as i128) - 1),
+                "\"-9007199254740992\"",
+            ),
+        ];
+        for (value, json) in cases {
+            assert_eq!(to_json_with_options(&value, &options), Ok(json.into()));
+        }
+    }
+
+    #[test]
+    fn test_to_json_duplicate_key() {
+        let value = cbor_array![cbor_map! {"1" => 0, 1 => 0}];
+        assert_eq!(to_json(&value), Err(ConvertError::DuplicateKey));
+        let value = cbor_map! {b"\xFF" => 0, "_w" => 0};
+        assert_eq!(to_json(&value), Err(ConvertError::DuplicateKey));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let cases = vec![
+            ("0", cbor_int!(0)),
+            ("-0", cbor_int!(0)),
+            (" 18446744073709551615 ", Value::Unsigned(u64::MAX)),
+            (
+                "-18446744073709551616",
+                Value::Negative(-18446744073709551616),
+            ),
+            ("18446744073709551616", Value::Float(18446744073709551616.0)),
+            ("-1000", cbor_int!(-1000)),
+            ("1.0", Value::Float(1.0)),
+            ("-4.1e300", Value::Float(-4.1e300)),
+            ("2E-1", Value::Float(0.2)),
+            ("1e+2", Value::Float(100.0)),
+            ("\"\"", cbor_text!("")),
+            (
+                "\"a\\\"\\\\\\/\\b\\f\\n\\r\\t\\u00e9\\ud83d\\ude00\"",
+                cbor_text!("a\"\\/\u{8}\u{c}\n\r\té😀"),
+            ),
+            ("\"9007199254740992\"", cbor_text!("9007199254740992")),
+            ("[ 1 , [2, \"b\"] ]", cbor_array![1, cbor_array![2, "b"]]),
+            ("[]", cbor_array![]),
+            (
+                "{\"a\": 1, \"2\": [], \"AQ\": {}}",
+                cbor_map! {"a" => 1, "2" => cbor_array![], "AQ" => cbor_map! {}},
+            ),
+            ("true", cbor_true!()),
+            ("false", cbor_false!()),
+            ("\tnull\r\n", cbor_null!()),
+        ];
+        for (json, value) in cases {
+            assert_eq!(from_json(json), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_from_json_with_options() {
+        let options = JsonCborOptions {
+            large_integers_as_strings: true,
+            ..JsonCborOptions::default()
+        };
+        let cases = vec![
+            ("\"9007199254740991\"", cbor_text!("9007199254740991")),
+            (
+                "\"9007199254740992\"",
+                Value::Unsigned(MAX_SAFE_INTEGER + 1),
+            ),
+            (
+                "\"-9007199254740992\"",
+                Value::Negative(-(MAX_SAFE_INTEGER as i128) - 1),
+            ),
+            ("\"09007199254740992\"", cbor_text!("09007199254740992")),
+            (
+                "\"18446744073709551616\"",
+                cbor_text!("18446744073709551616"),
+            ),
+        ];
+        for (json, value) in cases {
+            assert_eq!(from_json_with_options(json, &options), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let cases = vec![
+            ("", ConvertError::InvalidJson),
+            ("01", ConvertError::InvalidJson),
+            ("-", ConvertError::InvalidJson),
+            ("1.", ConvertError::InvalidJson),
+            ("1e", ConvertError::InvalidJson),
+            ("+1", ConvertError::InvalidJson),
+            ("1 2", ConvertError::InvalidJson),
+            ("[1,]", ConvertError::InvalidJson),
+            ("[1", ConvertError::InvalidJson),
+            ("{\"a\" 1}", ConvertError::InvalidJson),
+            ("{1: 1}", ConvertError::InvalidJson),
+            ("\"a", ConvertError::InvalidJson),
+            ("\"\n\"", ConvertError::InvalidJson),
+            ("\"\\x\"", ConvertError::InvalidJson),
+            ("\"\\ud83d\"", ConvertError::InvalidJson),
+            ("\"\\ude00\"", ConvertError::InvalidJson),
+            ("nul", ConvertError::InvalidJson),
+            ("{\"a\": 1, \"a\": 2}", ConvertError::DuplicateKey),
+        ];
+        for (json, error) in cases {
+            assert_eq!(from_json(json), Err(error));
+        }
+
+        let deep_json = "[".repeat(MAX_NESTING) + &"]".repeat(MAX_NESTING);
+        assert!(from_json(&deep_json).is_ok());
+        let deep_json = "[".repeat(MAX_NESTING + 1) + &"]".repeat(MAX_NESTING + 1);
+        assert_eq!(from_json(&deep_json), Err(ConvertError::TooMuchNesting));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let options = JsonCborOptions {
+            large_integers_as_strings: true,
+            ..JsonCborOptions::default()
+        };
+        let value = cbor_map! {
+            "int" => cbor_array![0, -1, Value::Unsigned(u64::MAX), Value::Negative(-18446744073709551616)],
+            "float" => cbor_array![Value::Float(1.0), Value::Float(-0.0), Value::Float(1.5e-7)],
+            "text" => "é\u{1}\"",
+            "nested" => cbor_map! {"a" => cbor_array![cbor_true!(), cbor_false!(), cbor_null!()]},
+        };
+        for options in [JsonCborOptions::default(), options] {
+            let json = to_json_with_options(&value, &options).unwrap();
+            assert_eq!(from_json_with_options(&json, &options), Ok(value.clone()));
+        }
+
+        // Lossy conversions.
+        let value = cbor_array![
+            cbor_bytes!(vec![0x01]),
+            cbor_map! {1 => 0},
+            cbor_tagged!(1, cbor_int!(0)),
+            cbor_undefined!()
+        ];
+        let json = to_json(&value).unwrap();
+        assert_eq!(
+            from_json(&json),
+            Ok(cbor_array![
+                "AQ",
+                cbor_map! {"1" => 0},
+                cbor_map! {"tag" => 1, "value" => 0},
+                cbor_null!()
+            ])
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_to_serde_json() {
+        use serde_json::json;
+
+        let value = cbor_map! {
+            "a" => cbor_array![1, -2, Value::Float(1.5), cbor_bytes!(vec![0xFB, 0xFF])],
+            "b" => cbor_map! {1 => cbor_true!(), cbor_bytes!(vec![0x00]) => cbor_null!()},
+            "c" => cbor_tagged!(1, cbor_int!(1000)),
+            "d" => cbor_undefined!(),
+            "e" => Value::Float(f64::NAN),
+            "f" => Value::Unsigned(u64::MAX),
+            "g" => Value::Negative(-(1 << 64)),
+        };
+        assert_eq!(
+            serde_json::Value::try_from(value),
+            Ok(json!({
+                "a": [1, -2, 1.5, "-_8"],
+                "b": {"1": true, "AA": null},
+                "c": {"tag": 1, "value": 1000},
+                "d": null,
+                "e": null,
+                "f": u64::MAX,
+                "g": "-18446744073709551616",
+            }))
+        );
+
+        let options = JsonCborOptions {
+            byte_string_encoding: ByteStringEncoding::Base64,
+            large_integers_as_strings: true,
+        };
+        assert_eq!(
+            to_serde_json_with_options(
+                &cbor_array![cbor_bytes!(vec![0xFB, 0xFF]), 1i64 << 53, -(1i64 << 53)],
+                &options
+            ),
+            Ok(json!(["+/8=", "9007199254740992", "-9007199254740992"]))
+        );
+        assert_eq!(
+            serde_json::Value::try_from(cbor_map! {1 => 1, "1" => 2}),
+            Err(ConvertError::DuplicateKey)
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_from_serde_json() {
+        use serde_json::json;
+
+        let json = json!({
+            "a": [1, -2, 1.5, 1.0, "x"],
+            "b": {"c": true, "d": false, "e": null},
+            "f": "9007199254740992",
+        });
+        assert_eq!(
+            Value::from(json.clone()),
+            cbor_map! {
+                "a" => cbor_array![1, -2, Value::Float(1.5), Value::Float(1.0), "x"],
+                "b" => cbor_map! {"c" => cbor_true!(), "d" => cbor_false!(), "e" => cbor_null!()},
+                "f" => "9007199254740992",
+            }
+        );
+
+        let options = JsonCborOptions {
+            large_integers_as_strings: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            from_serde_json_with_options(json!(["9007199254740992", "1"]), &options),
+            cbor_array![1u64 << 53, "1"]
+        );
+
+        // Values with a JSON counterpart survive the round trip.
+        assert_eq!(
+            serde_json::Value::try_from(Value::from(json.clone())),
+            Ok(json)
+        );
+    }
+}
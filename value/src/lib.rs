@@ -15,14 +15,26 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub mod builder;
+pub mod diagnostic;
+pub mod json;
 pub mod macros;
 pub mod reader;
+pub mod scanner;
+pub mod schema;
 pub mod values;
+pub mod visitor;
 pub mod writer;
 
+#[cfg(test)]
+mod test;
+
 pub use self::{
     reader::read,
-    values::{SimpleValue, Value},
+    values::{MajorType, SimpleValue, Value, ValueRef},
+    visitor::Visitor,
     writer::write,
 };
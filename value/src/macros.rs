@@ -296,6 +296,17 @@ macro_rules! cbor_undefined {
     };
 }
 
+/// Creates a CBOR Value of type Simple with the given encoded value, panicking if it is reserved
+/// (24..=31) or does not fit in a byte.
+#[macro_export]
+macro_rules! cbor_simple {
+    ( $x:expr ) => {
+        $crate::values::Value::Simple(
+            $crate::values::SimpleValue::from_integer(($x) as u64).expect("invalid simple value"),
+        )
+    };
+}
+
 /// Creates a CBOR Value of type Simple with the given bool value.
 #[macro_export]
 macro_rules! cbor_bool {
@@ -312,6 +323,57 @@ macro_rules! cbor_unsigned {
     };
 }
 
+/// Creates a CBOR Value of type Unsigned with the given non-negative integer of any type.
+///
+/// Negative literals are rejected at compile time, other negative values trigger a debug
+/// assertion.
+///
+/// ```compile_fail
+/// # use oasis_cbor_value::cbor_uint;
+/// let value = cbor_uint!(-1);
+/// ```
+#[macro_export]
+macro_rules! cbor_uint {
+    ( - $x:literal ) => {
+        compile_error!("cbor_uint! requires a non-negative value")
+    };
+    ( $x:expr ) => {{
+        let x = $x;
+        debug_assert!(
+            ::core::convert::TryInto::<u64>::try_into(x).is_ok(),
+            "cbor_uint! requires a value in 0..=u64::MAX"
+        );
+        $crate::values::Value::Unsigned(x as u64)
+    }};
+}
+
+/// Creates a CBOR Value of type Negative with the given negative integer of any signed type.
+///
+/// Non-negative literals are rejected at compile time, other non-negative values trigger a debug
+/// assertion.
+///
+/// ```compile_fail
+/// # use oasis_cbor_value::cbor_nint;
+/// let value = cbor_nint!(1);
+/// ```
+#[macro_export]
+macro_rules! cbor_nint {
+    ( - $x:literal ) => {
+        $crate::cbor_nint!((-$x))
+    };
+    ( $x:literal ) => {
+        compile_error!("cbor_nint! requires a negative value")
+    };
+    ( $x:expr ) => {{
+        let x = $x as i128;
+        debug_assert!(
+            (-(1i128 << 64)..0).contains(&x),
+            "cbor_nint! requires a value in -2^64..=-1"
+        );
+        $crate::values::Value::Negative(x)
+    }};
+}
+
 /// Creates a CBOR Value of type Unsigned or Negative with the given numeric value.
 #[macro_export]
 macro_rules! cbor_int {
@@ -372,6 +434,18 @@ mod test {
         assert_eq!(cbor_false!(), Value::Simple(SimpleValue::FalseValue));
         assert_eq!(cbor_null!(), Value::Simple(SimpleValue::NullValue));
         assert_eq!(cbor_undefined!(), Value::Simple(SimpleValue::Undefined));
+        assert_eq!(cbor_simple!(16), Value::Simple(SimpleValue::Unassigned(16)));
+        assert_eq!(cbor_simple!(20), Value::Simple(SimpleValue::FalseValue));
+        assert_eq!(
+            cbor_simple!(255),
+            Value::Simple(SimpleValue::Unassigned(255))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cbor_simple_reserved() {
+        cbor_simple!(24);
     }
 
     #[test]
@@ -401,6 +475,40 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_cbor_uint() {
+        assert_eq!(cbor_uint!(0), Value::Unsigned(0));
+        assert_eq!(cbor_uint!(255u8), Value::Unsigned(255));
+        assert_eq!(cbor_uint!(u64::MAX), Value::Unsigned(u64::MAX));
+        let x: i32 = 7;
+        assert_eq!(cbor_uint!(x), Value::Unsigned(7));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_cbor_uint_negative_value() {
+        let x: i64 = -1;
+        cbor_uint!(x);
+    }
+
+    #[test]
+    fn test_cbor_nint() {
+        assert_eq!(cbor_nint!(-1), Value::Negative(-1));
+        assert_eq!(cbor_nint!(-123456), Value::Negative(-123456));
+        assert_eq!(cbor_nint!(i64::MIN), Value::Negative(i64::MIN as i128));
+        let x: i8 = -7;
+        assert_eq!(cbor_nint!(x), Value::Negative(-7));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_cbor_nint_non_negative_value() {
+        let x: i64 = 0;
+        cbor_nint!(x);
+    }
+
     #[test]
     fn test_cbor_int_literals() {
         let a = cbor_array![
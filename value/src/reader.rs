@@ -14,12 +14,15 @@
 
 //! Functionality for deserializing CBOR data into values.
 
-use alloc::{str, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, str, string::String, vec, vec::Vec};
+use core::convert::TryFrom;
 
-use super::values::{Constants, SimpleValue, Value};
-use crate::{
-    cbor_array_vec, cbor_bytes_lit, cbor_map_collection, cbor_tagged, cbor_text, cbor_unsigned,
+use super::{
+    scanner::f16_to_f64,
+    values::{Constants, SimpleValue, Value, MAX_NESTING_DEPTH},
+    writer::{shortest_float_bits, EncoderError},
 };
+use crate::{cbor_bytes, cbor_tagged, cbor_text, cbor_unsigned};
 
 /// Possible errors from a deserialization operation.
 #[derive(Debug, PartialEq)]
@@ -34,13 +37,59 @@ pub enum DecoderError {
     NonMinimalCborEncoding,
     UnsupportedSimpleValue,
     UnsupportedFloatingPointValue,
+    MalformedIndefiniteLengthItem,
+    InvalidTaggedValue,
+    LimitExceeded,
+    UnexpectedEof,
+    SchemaMismatch,
+    MissingMapKey,
+    UnknownMapKey,
+    LengthOverflow,
+}
+
+/// Possible errors from a deserialization operation from an [`std::io::Read`] source.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum ReadError {
+    /// The data could not be decoded.
+    Decoder(DecoderError),
+    /// The source failed to provide the data.
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "std")]
+impl From<DecoderError> for ReadError {
+    fn from(e: DecoderError) -> Self {
+        ReadError::Decoder(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ReadError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => ReadError::Decoder(DecoderError::UnexpectedEof),
+            _ => ReadError::Io(e),
+        }
+    }
+}
+
+/// A [`DecoderError`] along with the byte offset in the input of the item where it occurred.
+#[derive(Debug, PartialEq)]
+pub struct LocatedDecoderError {
+    /// The cause of the failure.
+    pub kind: DecoderError,
+    /// Offset of the first byte of the innermost item that could not be decoded, e.g. of a key
+    /// that is out of order, or of the first byte after the item for
+    /// [`DecoderError::ExtraneousData`].
+    pub offset: usize,
 }
 
 /// Deserialize CBOR binary data to produce a single [`Value`], expecting that there is no additional data.
 /// Maximum level of nesting supported is 127; more deeply nested structures will fail with
 /// [`DecoderError::TooMuchNesting`].
 pub fn read(encoded_cbor: &[u8]) -> Result<Value, DecoderError> {
-    read_nested(encoded_cbor, Some(i8::MAX))
+    read_nested(encoded_cbor, Some(MAX_NESTING_DEPTH))
 }
 
 /// Deserialize CBOR binary data to produce a single [`Value`], expecting that there is no additional data.  If
@@ -55,6 +104,340 @@ pub fn read_nested(encoded_cbor: &[u8], max_nest: Option<i8>) -> Result<Value, D
     Ok(value)
 }
 
+/// Deserialize the first data item of CBOR binary data to produce a single [`Value`], returning it
+/// along with the number of bytes consumed from the input; any additional data is left untouched.
+/// Maximum level of nesting supported is 127; more deeply nested structures will fail with
+/// [`DecoderError::TooMuchNesting`].
+pub fn read_prefix(encoded_cbor: &[u8]) -> Result<(Value, usize), DecoderError> {
+    let mut reader = Reader::new(encoded_cbor);
+    let value = reader.decode_complete_data_item(Some(MAX_NESTING_DEPTH))?;
+    Ok((value, encoded_cbor.len() - reader.remaining_cbor.len()))
+}
+
+/// Deserialize a CBOR sequence (RFC 8742), i.e. zero or more data items concatenated without any
+/// framing, to produce its [`Value`]s in order.  Empty data is an empty sequence.  Maximum level of
+/// nesting supported within each item is 127; more deeply nested structures will fail with
+/// [`DecoderError::TooMuchNesting`].
+pub fn read_sequence(encoded_cbor: &[u8]) -> Result<Vec<Value>, DecoderError> {
+    let mut values = Vec::new();
+    let mut remaining_cbor = encoded_cbor;
+    while !remaining_cbor.is_empty() {
+        let (value, consumed_len) = read_prefix(remaining_cbor)?;
+        values.push(value);
+        remaining_cbor = &remaining_cbor[consumed_len..];
+    }
+    Ok(values)
+}
+
+/// Deserialize a self-delimiting frame as produced by [`write_framed`](crate::writer::write_framed),
+/// i.e. a 4-byte big-endian length followed by exactly that many bytes of CBOR data holding a
+/// single item, returning its [`Value`] and the total length of the frame.  Any data after the
+/// frame is left unread.  A frame that is truncated, or whose length is zero as no item is empty,
+/// fails with [`DecoderError::IncompleteCborData`].  Maximum level of nesting supported is 127;
+/// more deeply nested structures will fail with [`DecoderError::TooMuchNesting`].
+pub fn read_framed(encoded_cbor: &[u8]) -> Result<(Value, usize), DecoderError> {
+    if encoded_cbor.len() < 4 {
+        return Err(DecoderError::IncompleteCborData);
+    }
+    let (len_bytes, remaining_cbor) = encoded_cbor.split_at(4);
+    let len = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+    if len == 0 || len > remaining_cbor.len() {
+        return Err(DecoderError::IncompleteCborData);
+    }
+    Ok((read(&remaining_cbor[..len])?, 4 + len))
+}
+
+/// Deserialize only the values of the given keys from CBOR binary data holding a single map,
+/// expecting that there is no additional data.  The result holds the value of each requested key
+/// in order, or `None` if the map lacks it.  Other values are validated as [`read`] would but
+/// skipped over without building a [`Value`], which saves work for wide maps.  Data that is not a
+/// map fails with [`DecoderError::UnsupportedMajorType`].  Maximum level of nesting supported is
+/// 127; more deeply nested structures will fail with [`DecoderError::TooMuchNesting`].
+pub fn read_fields(
+    encoded_cbor: &[u8],
+    keys: &[Value],
+) -> Result<Vec<Option<Value>>, DecoderError> {
+    // Canonical keys are compared by their encoding; keys that have none can match nothing.
+    let encoded_keys: Vec<Option<Vec<u8>>> = keys
+        .iter()
+        .map(|key| {
+            let mut encoded_key = Vec::new();
+            crate::writer::write_ref(key, &mut encoded_key)
+                .ok()
+                .map(|_| encoded_key)
+        })
+        .collect();
+    let mut fields = vec![None; keys.len()];
+    let mut reader = Reader::new(encoded_cbor);
+    let size_value = reader.read_map_len()?;
+    let remaining_depth = Some(MAX_NESTING_DEPTH - 1);
+    let mut last_key: Option<&[u8]> = None;
+    for _ in 0..size_value {
+        let key_start = reader.remaining_cbor;
+        reader.validate_complete_data_item(remaining_depth)?;
+        let key = &key_start[..key_start.len() - reader.remaining_cbor.len()];
+        if last_key.map_or(false, |last_key| last_key >= key) {
+            return Err(DecoderError::OutOfOrderKey);
+        }
+        last_key = Some(key);
+        match encoded_keys
+            .iter()
+            .position(|encoded_key| encoded_key.as_deref() == Some(key))
+        {
+            Some(index) => fields[index] = Some(reader.decode_complete_data_item(remaining_depth)?),
+            None => reader.validate_complete_data_item(remaining_depth)?,
+        }
+    }
+    if !reader.remaining_cbor.is_empty() {
+        return Err(DecoderError::ExtraneousData);
+    }
+    Ok(fields)
+}
+
+/// Limits enforced while deserializing untrusted data.
+///
+/// The default options impose no limits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReaderOptions {
+    /// Maximum number of elements of an array, or of entries of a map.
+    pub max_collection_len: usize,
+    /// Maximum number of data items in total, counting nested items.
+    pub max_total_items: usize,
+    /// Reject integers, lengths and tag numbers not encoded in their shortest form with
+    /// [`DecoderError::NonMinimalCborEncoding`] even when otherwise decoding non-strictly, as such
+    /// encodings make the data malleable.  Strict decoding always requires them.  This also applies
+    /// to floats when `decode_floats` is set.
+    pub require_minimal: bool,
+    /// Decode the byte string wrapped in an encoded CBOR data item (tag 24) in place, replacing
+    /// the tagged item with the embedded value.  The embedded item must be complete and is decoded
+    /// under the same rules and limits, counting towards them.  Otherwise such items are kept as
+    /// opaque tagged byte strings.  A [`TagHandler`] for tag 24 takes precedence.
+    pub decode_tag24: bool,
+    /// How to decode text strings that are not valid UTF-8, to recover the surrounding data from
+    /// faulty producers.  As this changes the decoded value, a recovered map key is checked for
+    /// canonical order as such.
+    pub invalid_utf8: InvalidUtf8Handling,
+    /// Decode half, single and double precision floats, widened to double precision.  Strict
+    /// decoding requires the shortest encoding preserving the value, as written by
+    /// [`write`](crate::writer::write).  Otherwise floats fail with
+    /// [`DecoderError::UnsupportedFloatingPointValue`], or are decoded as undefined when decoding
+    /// non-strictly, as floats are not deterministic enough for all uses.
+    pub decode_floats: bool,
+    /// Skip a self-described CBOR tag (tag 55799) heading the data, as written by
+    /// [`write_self_describing`](crate::writer::write_self_describing), so that the tagged value
+    /// is decoded instead.  Only the outermost tag is skipped, with its head as `0xD9D9F7`, and it
+    /// does not count towards the nesting limit.
+    pub strip_self_describing: bool,
+    /// How to decode maps with duplicate keys when decoding non-strictly, comparing the decoded
+    /// keys.  Strict decoding always fails with [`DecoderError::OutOfOrderKey`], as canonical keys
+    /// are sorted by their encoding.
+    pub on_duplicate_key: DuplicateKeyHandling,
+    /// Order of the entries of maps decoded non-strictly.  Strict decoding requires canonical
+    /// order, so that both orders are the same.
+    pub map_order: MapOrder,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        ReaderOptions {
+            max_collection_len: usize::MAX,
+            max_total_items: usize::MAX,
+            require_minimal: false,
+            decode_tag24: false,
+            invalid_utf8: InvalidUtf8Handling::default(),
+            decode_floats: false,
+            strip_self_describing: false,
+            on_duplicate_key: DuplicateKeyHandling::default(),
+            map_order: MapOrder::default(),
+        }
+    }
+}
+
+/// Handling of text strings that are not valid UTF-8.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InvalidUtf8Handling {
+    /// Fail with [`DecoderError::InvalidUtf8`].
+    #[default]
+    Error,
+    /// Decode a text string with invalid sequences replaced by U+FFFD REPLACEMENT CHARACTER.
+    Lossy,
+    /// Decode a byte string with the raw contents instead.
+    AsBytes,
+}
+
+/// Handling of duplicate map keys when decoding non-strictly.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicateKeyHandling {
+    /// Fail with [`DecoderError::OutOfOrderKey`], as when decoding strictly.
+    #[default]
+    Error,
+    /// Decode the first entry with the key, ignoring later ones.
+    KeepFirst,
+    /// Decode the value of the last entry with the key, in place of the first entry.
+    KeepLast,
+}
+
+/// Order of the entries of maps decoded non-strictly.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MapOrder {
+    /// Keep the entries in the order of the data, so that writing the value with
+    /// [`WriterOptions::preserve_map_order`](crate::writer::WriterOptions) set reproduces it.
+    #[default]
+    AsEncountered,
+    /// Sort the entries by their keys in canonical order, as [`Value::canonicalize`] does.
+    Canonical,
+}
+
+/// Deserialize CBOR binary data to produce a single [`Value`] within the limits of the given
+/// [`ReaderOptions`], expecting that there is no additional data.  If `max_nest` is `Some(max)`,
+/// then nested structures are only supported up to the given limit (returning
+/// [`DecoderError::TooMuchNesting`] if the limit is hit).  Exceeding the other limits fails with
+/// [`DecoderError::LimitExceeded`].
+pub fn read_with_options(
+    encoded_cbor: &[u8],
+    max_nest: Option<i8>,
+    options: &ReaderOptions,
+) -> Result<Value, DecoderError> {
+    read_located_with_options(encoded_cbor, max_nest, options).map_err(|e| e.kind)
+}
+
+/// Deserialize CBOR binary data to produce a single [`Value`] as [`read`] does, reporting where in
+/// the data the decoding failed, if it does.
+pub fn read_located(encoded_cbor: &[u8]) -> Result<Value, LocatedDecoderError> {
+    read_located_with_options(
+        encoded_cbor,
+        Some(MAX_NESTING_DEPTH),
+        &ReaderOptions::default(),
+    )
+}
+
+/// Deserialize CBOR binary data to produce a single [`Value`] as [`read_with_options`] does,
+/// reporting where in the data the decoding failed, if it does.
+pub fn read_located_with_options(
+    encoded_cbor: &[u8],
+    max_nest: Option<i8>,
+    options: &ReaderOptions,
+) -> Result<Value, LocatedDecoderError> {
+    let mut reader = Reader::new(encoded_cbor);
+    reader.options = options.clone();
+    reader.decode_located(max_nest)
+}
+
+/// Decoder of CBOR data for high-throughput decoding, which reuses the buffers of values that are
+/// no longer needed for the values it decodes next, amortizing their allocations.
+///
+/// Each call to [`Decoder::decode`] behaves as [`read_with_options`].  Values handed back with
+/// [`Decoder::recycle`] are broken up into their byte string, text string, array and map buffers,
+/// which are kept (with their capacity) until the decoder is dropped.
+pub struct Decoder {
+    max_nest: Option<i8>,
+    options: ReaderOptions,
+    pool: BufferPool,
+}
+
+impl Decoder {
+    /// Create a decoder with the default options, supporting up to 127 levels of nesting.
+    pub fn new() -> Decoder {
+        Decoder::with_options(Some(MAX_NESTING_DEPTH), ReaderOptions::default())
+    }
+
+    /// Create a decoder with the given options.  If `max_nest` is `Some(max)`, then nested
+    /// structures are only supported up to the given limit (returning
+    /// [`DecoderError::TooMuchNesting`] if the limit is hit).
+    pub fn with_options(max_nest: Option<i8>, options: ReaderOptions) -> Decoder {
+        Decoder {
+            max_nest,
+            options,
+            pool: BufferPool::default(),
+        }
+    }
+
+    /// Deserialize CBOR binary data to produce a single [`Value`], expecting that there is no
+    /// additional data, and drawing its buffers from the recycled ones where possible.
+    pub fn decode(&mut self, encoded_cbor: &[u8]) -> Result<Value, DecoderError> {
+        let mut reader = Reader::new(encoded_cbor);
+        reader.options = self.options.clone();
+        reader.pool = core::mem::take(&mut self.pool);
+        let result = reader.decode_located(self.max_nest);
+        self.pool = core::mem::take(&mut reader.pool);
+        result.map_err(|e| e.kind)
+    }
+
+    /// Hand back a [`Value`] that is no longer needed, so that its buffers are reused by the next
+    /// calls to [`Decoder::decode`].  The value is traversed without recursion.
+    pub fn recycle(&mut self, value: Value) {
+        self.pool.recycle(value);
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new()
+    }
+}
+
+/// Deserialize a single [`Value`] from the given source, reading exactly the bytes of one data
+/// item and leaving any following data in the source. The item's bytes are buffered, but the
+/// source need not be read completely first. If the source ends within the item, this fails with
+/// [`DecoderError::UnexpectedEof`]. Maximum level of nesting supported is 127; more deeply nested
+/// structures will fail with [`DecoderError::TooMuchNesting`].
+#[cfg(feature = "std")]
+pub fn read_from<R: std::io::Read>(source: &mut R) -> Result<Value, ReadError> {
+    let encoded_cbor = read_item_bytes(source)?;
+    Ok(read(&encoded_cbor)?)
+}
+
+/// Read the bytes of a single data item from the given source, framing it from the data item
+/// heads only.  Indefinite-length items are rejected as [`read`] would.
+#[cfg(feature = "std")]
+fn read_item_bytes<R: std::io::Read>(source: &mut R) -> Result<Vec<u8>, ReadError> {
+    use std::io::Read;
+
+    let mut encoded_cbor = Vec::new();
+    // Number of data items still to be read.
+    let mut pending = 1u64;
+    while pending > 0 {
+        pending -= 1;
+        let mut first_byte = [0u8];
+        source.read_exact(&mut first_byte)?;
+        encoded_cbor.push(first_byte[0]);
+        let major_type_value = first_byte[0] >> Constants::MAJOR_TYPE_BIT_SHIFT;
+        let additional_info = first_byte[0] & Constants::ADDITIONAL_INFORMATION_MASK;
+        let additional_bytes_num = match additional_info {
+            0..=Constants::ADDITIONAL_INFORMATION_MAX_INT => 0,
+            Constants::ADDITIONAL_INFORMATION_1_BYTE => 1,
+            Constants::ADDITIONAL_INFORMATION_2_BYTES => 2,
+            Constants::ADDITIONAL_INFORMATION_4_BYTES => 4,
+            Constants::ADDITIONAL_INFORMATION_8_BYTES => 8,
+            _ => return Err(DecoderError::UnknownAdditionalInfo.into()),
+        };
+        let mut size_value = additional_info as u64;
+        if additional_bytes_num > 0 {
+            let mut bytes = [0u8; 8];
+            source.read_exact(&mut bytes[8 - additional_bytes_num..])?;
+            encoded_cbor.extend_from_slice(&bytes[8 - additional_bytes_num..]);
+            size_value = u64::from_be_bytes(bytes);
+        }
+        match major_type_value {
+            2 | 3 => {
+                if usize::try_from(size_value).is_err() {
+                    return Err(DecoderError::LengthOverflow.into());
+                }
+                // Don't reserve the declared length, it is an unsanitized input.
+                let read_len = source.take(size_value).read_to_end(&mut encoded_cbor)?;
+                if (read_len as u64) < size_value {
+                    return Err(DecoderError::UnexpectedEof.into());
+                }
+            }
+            4 => pending = pending.saturating_add(size_value),
+            5 => pending = pending.saturating_add(size_value.saturating_mul(2)),
+            6 => pending += 1,
+            _ => {}
+        }
+    }
+    Ok(encoded_cbor)
+}
+
 /// Deserialize CBOR binary data to produce a single [`Value`].  If `max_nest` is `Some(max)`, then
 /// nested structures are only supported up to the given limit (returning
 /// [`DecoderError::TooMuchNesting`] if the limit is hit).
@@ -67,23 +450,223 @@ pub fn read_nested_non_strict(
     Ok(value)
 }
 
+/// Deserialize CBOR binary data to produce a single [`Value`] within the limits of the given
+/// [`ReaderOptions`], accepting non-canonical encodings unless the options require minimal ones.
+/// If `max_nest` is `Some(max)`, then nested structures are only supported up to the given limit
+/// (returning [`DecoderError::TooMuchNesting`] if the limit is hit).  Exceeding the other limits
+/// fails with [`DecoderError::LimitExceeded`].
+pub fn read_non_strict_with_options(
+    encoded_cbor: &[u8],
+    max_nest: Option<i8>,
+    options: &ReaderOptions,
+) -> Result<Value, DecoderError> {
+    let mut reader = Reader::new_non_strict(encoded_cbor);
+    reader.options = options.clone();
+    reader.strip_self_describing_tag();
+    reader.decode_complete_data_item(max_nest)
+}
+
+/// Handler for the content of a tagged value, returning the value replacing the whole tagged item.
+pub type TagHandler<'a> = dyn Fn(Value) -> Result<Value, DecoderError> + 'a;
+
+/// Registry of [`TagHandler`]s by tag number, used by [`read_with_tags`].
+#[derive(Default)]
+pub struct TagHandlers<'a> {
+    handlers: BTreeMap<u64, Box<TagHandler<'a>>>,
+}
+
+impl<'a> TagHandlers<'a> {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register the handler for the given tag number, replacing any previous one.
+    ///
+    /// The handler receives the content of the tagged value. It can validate it, failing with
+    /// e.g. [`DecoderError::InvalidTaggedValue`], and transform it; to keep the tag, it has to
+    /// return a [`Value::Tag`] itself.
+    pub fn insert<F>(&mut self, tag: u64, handler: F)
+    where
+        F: Fn(Value) -> Result<Value, DecoderError> + 'a,
+    {
+        self.handlers.insert(tag, Box::new(handler));
+    }
+}
+
+/// Deserialize CBOR binary data to produce a single [`Value`], expecting that there is no additional data,
+/// and passing the content of tagged values to the matching handler. Tagged values without a handler are
+/// returned unchanged. Maximum level of nesting supported is 127; more deeply nested structures will fail
+/// with [`DecoderError::TooMuchNesting`].
+pub fn read_with_tags(
+    encoded_cbor: &[u8],
+    tag_handlers: &TagHandlers,
+) -> Result<Value, DecoderError> {
+    let mut reader = Reader::new(encoded_cbor);
+    reader.tag_handlers = Some(tag_handlers);
+    let value = reader.decode_complete_data_item(Some(MAX_NESTING_DEPTH))?;
+    if !reader.remaining_cbor.is_empty() {
+        return Err(DecoderError::ExtraneousData);
+    }
+    Ok(value)
+}
+
+/// Re-encode CBOR binary data holding a single data item in canonical form, expecting that there
+/// is no additional data.  Non-minimal encodings, unsorted map keys and indefinite-length items
+/// are accepted and normalized; duplicate map keys fail with [`DecoderError::OutOfOrderKey`].
+/// Maximum level of nesting supported is 127; more deeply nested structures will fail with
+/// [`DecoderError::TooMuchNesting`].
+pub fn canonicalize_bytes(encoded_cbor: &[u8]) -> Result<Vec<u8>, DecoderError> {
+    let (canonical_cbor, consumed_len) = canonicalize_prefix(encoded_cbor)?;
+    if consumed_len != encoded_cbor.len() {
+        return Err(DecoderError::ExtraneousData);
+    }
+    Ok(canonical_cbor)
+}
+
+/// Re-encode the first data item of CBOR binary data in canonical form, returning it along with
+/// the number of bytes consumed from the input.  See [`canonicalize_bytes`].
+pub fn canonicalize_prefix(encoded_cbor: &[u8]) -> Result<(Vec<u8>, usize), DecoderError> {
+    let mut reader = Reader::new(encoded_cbor);
+    reader.allow_non_canonical = true;
+    let value = reader.decode_complete_data_item(Some(MAX_NESTING_DEPTH))?;
+    let consumed_len = encoded_cbor.len() - reader.remaining_cbor.len();
+    let mut canonical_cbor = Vec::with_capacity(consumed_len);
+    crate::writer::write(value, &mut canonical_cbor).map_err(|e| match e {
+        EncoderError::TooMuchNesting => DecoderError::TooMuchNesting,
+        EncoderError::DuplicateMapKey { .. } => DecoderError::OutOfOrderKey,
+        EncoderError::FrameTooLarge
+        | EncoderError::OutputTooLarge
+        | EncoderError::InvalidSimpleValue
+        | EncoderError::LengthMismatch
+        | EncoderError::OutOfOrderKey => {
+            unreachable!("decoded values are valid and unlimited")
+        }
+    })?;
+    Ok((canonical_cbor, consumed_len))
+}
+
+/// Check that CBOR binary data holds a single data item in canonical form, as produced by
+/// [`write`](crate::writer::write): all lengths are definite and minimally encoded, floats are in
+/// their shortest encoding preserving their value (with NaN as a half-precision quiet NaN), and
+/// all map keys are unique and sorted by their encoding.  The data is walked without building a
+/// [`Value`].  Maximum level of nesting supported is 127; more deeply nested structures will fail
+/// with [`DecoderError::TooMuchNesting`].
+pub fn validate_canonical(encoded_cbor: &[u8]) -> Result<(), DecoderError> {
+    let mut reader = Reader::new(encoded_cbor);
+    reader.options.decode_floats = true;
+    reader.validate_complete_data_item(Some(MAX_NESTING_DEPTH))?;
+    if !reader.remaining_cbor.is_empty() {
+        return Err(DecoderError::ExtraneousData);
+    }
+    Ok(())
+}
+
+/// Return whether CBOR binary data is in canonical form, see [`validate_canonical`].
+pub fn is_canonical(encoded_cbor: &[u8]) -> bool {
+    validate_canonical(encoded_cbor).is_ok()
+}
+
+/// Buffers of discarded values, emptied and kept for decoding further values.
+#[derive(Default)]
+struct BufferPool {
+    bytes: Vec<Vec<u8>>,
+    values: Vec<Vec<Value>>,
+    entries: Vec<Vec<(Value, Value)>>,
+}
+
+impl BufferPool {
+    /// Return a copy of the given data, in a recycled buffer if there is one.
+    fn bytes_from(&mut self, data: &[u8]) -> Vec<u8> {
+        match self.bytes.pop() {
+            Some(mut buffer) => {
+                buffer.extend_from_slice(data);
+                buffer
+            }
+            None => data.to_vec(),
+        }
+    }
+
+    fn take_bytes(&mut self) -> Vec<u8> {
+        self.bytes.pop().unwrap_or_default()
+    }
+
+    fn take_values(&mut self) -> Vec<Value> {
+        self.values.pop().unwrap_or_default()
+    }
+
+    fn take_entries(&mut self) -> Vec<(Value, Value)> {
+        self.entries.pop().unwrap_or_default()
+    }
+
+    /// Empty the buffers of the value into the pool.
+    fn recycle(&mut self, value: Value) {
+        let mut pending = vec![value];
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::ByteString(mut byte_string) => {
+                    byte_string.clear();
+                    self.bytes.push(byte_string);
+                }
+                Value::TextString(text_string) => {
+                    let mut byte_string = text_string.into_bytes();
+                    byte_string.clear();
+                    self.bytes.push(byte_string);
+                }
+                Value::Array(mut array) => {
+                    pending.append(&mut array);
+                    self.values.push(array);
+                }
+                Value::Map(mut map) => {
+                    pending.extend(map.drain(..).flat_map(|(k, v)| [k, v]));
+                    self.entries.push(map);
+                }
+                Value::Tag(_, inner_value) => pending.push(*inner_value),
+                _ => {}
+            }
+        }
+    }
+}
+
 struct Reader<'a> {
     non_strict: bool,
+    /// Accept non-minimal encodings, unsorted map keys and indefinite-length items. Implied by
+    /// `non_strict`, which additionally accepts unsupported simple values.
+    allow_non_canonical: bool,
     remaining_cbor: &'a [u8],
+    tag_handlers: Option<&'a TagHandlers<'a>>,
+    options: ReaderOptions,
+    total_items: usize,
+    /// Length of the data remaining at the start of the innermost item that failed to decode.
+    error_remaining_len: Option<usize>,
+    /// Recycled buffers for the decoded values.
+    pool: BufferPool,
 }
 
 impl<'a> Reader<'a> {
     pub fn new(cbor: &'a [u8]) -> Reader<'a> {
         Reader {
             non_strict: false,
+            allow_non_canonical: false,
             remaining_cbor: cbor,
+            tag_handlers: None,
+            options: ReaderOptions::default(),
+            total_items: 0,
+            error_remaining_len: None,
+            pool: BufferPool::default(),
         }
     }
 
     pub fn new_non_strict(cbor: &'a [u8]) -> Reader<'a> {
         Reader {
             non_strict: true,
+            allow_non_canonical: true,
             remaining_cbor: cbor,
+            tag_handlers: None,
+            options: ReaderOptions::default(),
+            total_items: 0,
+            error_remaining_len: None,
+            pool: BufferPool::default(),
         }
     }
 
@@ -91,15 +674,77 @@ impl<'a> Reader<'a> {
         &mut self,
         remaining_depth: Option<i8>,
     ) -> Result<Value, DecoderError> {
+        let remaining_len = self.remaining_cbor.len();
+        let result = self.decode_data_item(remaining_depth);
+        if result.is_err() && self.error_remaining_len.is_none() {
+            self.error_remaining_len = Some(remaining_len);
+        }
+        result
+    }
+
+    /// Decode a single data item making up the whole data, after any self-described CBOR tag head
+    /// the options ask to strip, reporting where in the data the decoding failed.
+    fn decode_located(&mut self, max_nest: Option<i8>) -> Result<Value, LocatedDecoderError> {
+        let input_len = self.remaining_cbor.len();
+        self.strip_self_describing_tag();
+        let result = self.decode_complete_data_item(max_nest).and_then(|value| {
+            if !self.remaining_cbor.is_empty() {
+                self.error_remaining_len = Some(self.remaining_cbor.len());
+                return Err(DecoderError::ExtraneousData);
+            }
+            Ok(value)
+        });
+        result.map_err(|kind| LocatedDecoderError {
+            kind,
+            offset: input_len - self.error_remaining_len.unwrap_or(0),
+        })
+    }
+
+    /// Skip the head of a self-described CBOR tag at the start of the data, if enabled by the
+    /// options.
+    fn strip_self_describing_tag(&mut self) {
+        if self.options.strip_self_describing
+            && self
+                .remaining_cbor
+                .starts_with(&Constants::SELF_DESCRIBED_CBOR_HEAD)
+        {
+            self.remaining_cbor = &self.remaining_cbor[Constants::SELF_DESCRIBED_CBOR_HEAD.len()..];
+        }
+    }
+
+    fn decode_data_item(&mut self, remaining_depth: Option<i8>) -> Result<Value, DecoderError> {
         if remaining_depth.map_or(false, |d| d < 0) {
             return Err(DecoderError::TooMuchNesting);
         }
+        self.total_items += 1;
+        if self.total_items > self.options.max_total_items {
+            return Err(DecoderError::LimitExceeded);
+        }
 
         match self.read_bytes(1) {
             Some([first_byte]) => {
                 // Unsigned byte means logical shift, so only zeros get shifted in.
                 let major_type_value = first_byte >> Constants::MAJOR_TYPE_BIT_SHIFT;
                 let additional_info = first_byte & Constants::ADDITIONAL_INFORMATION_MASK;
+                if additional_info == Constants::ADDITIONAL_INFORMATION_INDEFINITE_LENGTH
+                    && self.allow_non_canonical
+                {
+                    return match major_type_value {
+                        2 => self.read_indefinite_byte_string_content(),
+                        3 => self.read_indefinite_text_string_content(),
+                        4 => self.read_indefinite_array_content(remaining_depth),
+                        5 => self.read_indefinite_map_content(remaining_depth),
+                        _ => Err(DecoderError::UnknownAdditionalInfo),
+                    };
+                }
+                if major_type_value == 7
+                    && self.options.decode_floats
+                    && (Constants::ADDITIONAL_INFORMATION_2_BYTES
+                        ..=Constants::ADDITIONAL_INFORMATION_8_BYTES)
+                        .contains(&additional_info)
+                {
+                    return self.read_float(additional_info);
+                }
                 let size_value = self.read_variadic_length_integer(additional_info)?;
                 match major_type_value {
                     0 => self.decode_value_to_unsigned(size_value),
@@ -117,7 +762,87 @@ impl<'a> Reader<'a> {
         }
     }
 
-    fn read_bytes(&mut self, num_bytes: usize) -> Option<&[u8]> {
+    fn validate_complete_data_item(
+        &mut self,
+        remaining_depth: Option<i8>,
+    ) -> Result<(), DecoderError> {
+        if remaining_depth.map_or(false, |d| d < 0) {
+            return Err(DecoderError::TooMuchNesting);
+        }
+
+        match self.read_bytes(1) {
+            Some([first_byte]) => {
+                let major_type_value = first_byte >> Constants::MAJOR_TYPE_BIT_SHIFT;
+                let additional_info = first_byte & Constants::ADDITIONAL_INFORMATION_MASK;
+                if major_type_value == 7
+                    && self.options.decode_floats
+                    && (Constants::ADDITIONAL_INFORMATION_2_BYTES
+                        ..=Constants::ADDITIONAL_INFORMATION_8_BYTES)
+                        .contains(&additional_info)
+                {
+                    return self.read_float(additional_info).map(|_| ());
+                }
+                let size_value = self.read_variadic_length_integer(additional_info)?;
+                match major_type_value {
+                    0 | 1 => Ok(()),
+                    2 => self.read_content(size_value).map(|_| ()),
+                    3 => str::from_utf8(self.read_content(size_value)?)
+                        .map(|_| ())
+                        .map_err(|_| DecoderError::InvalidUtf8),
+                    4 => {
+                        for _ in 0..size_value {
+                            self.validate_complete_data_item(remaining_depth.map(|d| d - 1))?;
+                        }
+                        Ok(())
+                    }
+                    5 => self.validate_map_content(size_value, remaining_depth),
+                    6 => self.validate_complete_data_item(remaining_depth.map(|d| d - 1)),
+                    7 => self
+                        .decode_to_simple_value(size_value, additional_info)
+                        .map(|_| ()),
+                    _ => Err(DecoderError::UnsupportedMajorType),
+                }
+            }
+            _ => Err(DecoderError::IncompleteCborData),
+        }
+    }
+
+    fn validate_map_content(
+        &mut self,
+        size_value: u64,
+        remaining_depth: Option<i8>,
+    ) -> Result<(), DecoderError> {
+        let mut last_key: Option<&[u8]> = None;
+        for _ in 0..size_value {
+            let key_start = self.remaining_cbor;
+            self.validate_complete_data_item(remaining_depth.map(|d| d - 1))?;
+            let key = &key_start[..key_start.len() - self.remaining_cbor.len()];
+            // Canonical keys are sorted by their encoding, which also rules out duplicates.
+            if last_key.map_or(false, |last_key| last_key >= key) {
+                return Err(DecoderError::OutOfOrderKey);
+            }
+            last_key = Some(key);
+            self.validate_complete_data_item(remaining_depth.map(|d| d - 1))?;
+        }
+        Ok(())
+    }
+
+    /// Read the head of a map, returning its number of entries.
+    fn read_map_len(&mut self) -> Result<u64, DecoderError> {
+        match self.read_bytes(1) {
+            Some([first_byte]) => {
+                let major_type_value = first_byte >> Constants::MAJOR_TYPE_BIT_SHIFT;
+                let additional_info = first_byte & Constants::ADDITIONAL_INFORMATION_MASK;
+                if major_type_value != 5 {
+                    return Err(DecoderError::UnsupportedMajorType);
+                }
+                self.read_variadic_length_integer(additional_info)
+            }
+            _ => Err(DecoderError::IncompleteCborData),
+        }
+    }
+
+    fn read_bytes(&mut self, num_bytes: usize) -> Option<&'a [u8]> {
         if num_bytes > self.remaining_cbor.len() {
             None
         } else {
@@ -127,6 +852,14 @@ impl<'a> Reader<'a> {
         }
     }
 
+    /// Read the content of a string of the given declared length, failing with
+    /// [`DecoderError::LengthOverflow`] rather than truncating a length that does not fit in
+    /// `usize`, as on 32-bit targets.
+    fn read_content(&mut self, size_value: u64) -> Result<&'a [u8], DecoderError> {
+        let len = usize::try_from(size_value).map_err(|_| DecoderError::LengthOverflow)?;
+        self.read_bytes(len).ok_or(DecoderError::IncompleteCborData)
+    }
+
     fn read_variadic_length_integer(&mut self, additional_info: u8) -> Result<u64, DecoderError> {
         let additional_bytes_num = match additional_info {
             0..=Constants::ADDITIONAL_INFORMATION_MAX_INT => return Ok(additional_info as u64),
@@ -145,7 +878,7 @@ impl<'a> Reader<'a> {
                 }
                 if ((additional_bytes_num == 1 && size_value < 24)
                     || size_value < (1u64 << (8 * (additional_bytes_num >> 1))))
-                    && !self.non_strict
+                    && (!self.allow_non_canonical || self.options.require_minimal)
                 {
                     Err(DecoderError::NonMinimalCborEncoding)
                 } else {
@@ -164,34 +897,143 @@ impl<'a> Reader<'a> {
         Ok(Value::Negative(-(size_value as i128) - 1))
     }
 
-    fn read_byte_string_content(&mut self, size_value: u64) -> Result<Value, DecoderError> {
-        match self.read_bytes(size_value as usize) {
-            Some(bytes) => Ok(cbor_bytes_lit!(bytes)),
-            None => Err(DecoderError::IncompleteCborData),
+    /// Check the declared length of a collection whose entries take `min_entry_len` bytes at least.
+    fn check_collection_len(
+        &self,
+        size_value: u64,
+        min_entry_len: u64,
+    ) -> Result<(), DecoderError> {
+        if size_value > self.options.max_collection_len as u64 {
+            return Err(DecoderError::LimitExceeded);
         }
+        if size_value.saturating_mul(min_entry_len) > self.remaining_cbor.len() as u64 {
+            return Err(DecoderError::IncompleteCborData);
+        }
+        Ok(())
+    }
+
+    fn read_byte_string_content(&mut self, size_value: u64) -> Result<Value, DecoderError> {
+        let bytes = self.read_content(size_value)?;
+        Ok(cbor_bytes!(self.pool.bytes_from(bytes)))
     }
 
     fn read_text_string_content(&mut self, size_value: u64) -> Result<Value, DecoderError> {
-        match self.read_bytes(size_value as usize) {
-            Some(bytes) => match str::from_utf8(bytes) {
-                Ok(s) => Ok(cbor_text!(s)),
-                Err(_) => Err(DecoderError::InvalidUtf8),
-            },
+        let invalid_utf8 = self.options.invalid_utf8;
+        let bytes = self.read_content(size_value)?;
+        match String::from_utf8(self.pool.bytes_from(bytes)) {
+            Ok(text_string) => Ok(Value::TextString(text_string)),
+            Err(e) => {
+                let byte_string = e.into_bytes();
+                match invalid_utf8 {
+                    InvalidUtf8Handling::Error => Err(DecoderError::InvalidUtf8),
+                    InvalidUtf8Handling::Lossy => {
+                        Ok(cbor_text!(String::from_utf8_lossy(&byte_string)))
+                    }
+                    InvalidUtf8Handling::AsBytes => Ok(cbor_bytes!(byte_string)),
+                }
+            }
+        }
+    }
+
+    /// Consume the break byte terminating an indefinite-length item, returning whether it was
+    /// present.
+    fn read_break(&mut self) -> Result<bool, DecoderError> {
+        match self.remaining_cbor.first() {
+            Some(&Constants::BREAK) => {
+                self.remaining_cbor = &self.remaining_cbor[1..];
+                Ok(true)
+            }
+            Some(_) => Ok(false),
             None => Err(DecoderError::IncompleteCborData),
         }
     }
 
+    /// Read the next definite-length chunk of an indefinite-length string of the given major
+    /// type.
+    fn read_string_chunk(&mut self, major_type_value: u8) -> Result<&[u8], DecoderError> {
+        let first_byte = match self.read_bytes(1) {
+            Some([first_byte]) => *first_byte,
+            _ => return Err(DecoderError::IncompleteCborData),
+        };
+        let additional_info = first_byte & Constants::ADDITIONAL_INFORMATION_MASK;
+        if first_byte >> Constants::MAJOR_TYPE_BIT_SHIFT != major_type_value
+            || additional_info == Constants::ADDITIONAL_INFORMATION_INDEFINITE_LENGTH
+        {
+            return Err(DecoderError::MalformedIndefiniteLengthItem);
+        }
+        let size_value = self.read_variadic_length_integer(additional_info)?;
+        self.read_content(size_value)
+    }
+
+    fn read_indefinite_byte_string_content(&mut self) -> Result<Value, DecoderError> {
+        let mut byte_string = self.pool.take_bytes();
+        while !self.read_break()? {
+            byte_string.extend_from_slice(self.read_string_chunk(2)?);
+        }
+        Ok(cbor_bytes!(byte_string))
+    }
+
+    fn read_indefinite_text_string_content(&mut self) -> Result<Value, DecoderError> {
+        let invalid_utf8 = self.options.invalid_utf8;
+        let mut text_string = String::new();
+        // Raw contents, only kept in case they have to be decoded as a byte string.
+        let mut byte_string = Vec::new();
+        let mut is_valid = true;
+        while !self.read_break()? {
+            let chunk = self.read_string_chunk(3)?;
+            if invalid_utf8 == InvalidUtf8Handling::AsBytes {
+                byte_string.extend_from_slice(chunk);
+            }
+            // Each chunk must be valid UTF-8 on its own.
+            match str::from_utf8(chunk) {
+                Ok(s) => text_string.push_str(s),
+                Err(_) => match invalid_utf8 {
+                    InvalidUtf8Handling::Error => return Err(DecoderError::InvalidUtf8),
+                    InvalidUtf8Handling::Lossy => {
+                        text_string.push_str(&String::from_utf8_lossy(chunk))
+                    }
+                    InvalidUtf8Handling::AsBytes => is_valid = false,
+                },
+            }
+        }
+        if is_valid {
+            Ok(cbor_text!(text_string))
+        } else {
+            Ok(cbor_bytes!(byte_string))
+        }
+    }
+
+    fn read_indefinite_array_content(
+        &mut self,
+        remaining_depth: Option<i8>,
+    ) -> Result<Value, DecoderError> {
+        let mut value_array = self.pool.take_values();
+        while !self.read_break()? {
+            self.check_collection_len(value_array.len() as u64 + 1, 0)?;
+            value_array.push(self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?);
+        }
+        Ok(Value::Array(value_array))
+    }
+
+    fn read_indefinite_map_content(
+        &mut self,
+        remaining_depth: Option<i8>,
+    ) -> Result<Value, DecoderError> {
+        self.read_map_entries(None, remaining_depth)
+    }
+
     fn read_array_content(
         &mut self,
         size_value: u64,
         remaining_depth: Option<i8>,
     ) -> Result<Value, DecoderError> {
+        self.check_collection_len(size_value, 1)?;
         // Don't set the capacity already, it is an unsanitized input.
-        let mut value_array = Vec::new();
+        let mut value_array = self.pool.take_values();
         for _ in 0..size_value {
             value_array.push(self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?);
         }
-        Ok(cbor_array_vec!(value_array))
+        Ok(Value::Array(value_array))
     }
 
     fn read_map_content(
@@ -199,20 +1041,112 @@ impl<'a> Reader<'a> {
         size_value: u64,
         remaining_depth: Option<i8>,
     ) -> Result<Value, DecoderError> {
-        let mut value_map = Vec::<(Value, Value)>::new();
-        for _ in 0..size_value {
+        self.check_collection_len(size_value, 2)?;
+        self.read_map_entries(Some(size_value), remaining_depth)
+    }
+
+    /// Read the given number of map entries, or entries up to a break byte if `None`, checking
+    /// their order when decoding strictly and otherwise handling duplicate keys and ordering the
+    /// entries as set by the options.
+    fn read_map_entries(
+        &mut self,
+        size_value: Option<u64>,
+        remaining_depth: Option<i8>,
+    ) -> Result<Value, DecoderError> {
+        let mut value_map: Vec<(Value, Value)> = self.pool.take_entries();
+        // Input positions of the keys read non-strictly, to report duplicates once all entries are
+        // read.
+        let mut key_positions = Vec::new();
+        let mut keys_ascending = true;
+        let mut entries_read = 0u64;
+        loop {
+            match size_value {
+                Some(size_value) if entries_read == size_value => break,
+                Some(_) => {}
+                None => {
+                    if self.read_break()? {
+                        break;
+                    }
+                    self.check_collection_len(entries_read + 1, 0)?;
+                }
+            }
+            entries_read += 1;
+            let key_remaining_len = self.remaining_cbor.len();
             let key = self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?;
-            if let Some(last_item) = value_map.last() {
-                if last_item.0 >= key && !self.non_strict {
+            if value_map
+                .last()
+                .map_or(false, |last_item| last_item.0 >= key)
+            {
+                if !self.allow_non_canonical {
+                    self.error_remaining_len = Some(key_remaining_len);
                     return Err(DecoderError::OutOfOrderKey);
                 }
+                keys_ascending = false;
             }
-            value_map.push((
-                key,
-                self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?,
-            ));
+            if self.allow_non_canonical {
+                key_positions.push(key_remaining_len);
+            }
+            let value = self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?;
+            value_map.push((key, value));
+        }
+        // Keys in ascending order are unique and already sorted.
+        if !keys_ascending {
+            self.remove_duplicate_keys(&mut value_map, &key_positions)?;
+            if self.options.map_order == MapOrder::Canonical {
+                // Keys are unique by now, so that the order of equal keys does not matter.
+                value_map.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            }
+        }
+        Ok(Value::Map(value_map))
+    }
+
+    /// Apply [`ReaderOptions::on_duplicate_key`] to the entries of a map read non-strictly, given
+    /// the remaining length of the data at each key.  The entries are sorted by key through their
+    /// indices, so that this takes O(n log n) time and leaves them in encountered order.
+    fn remove_duplicate_keys(
+        &mut self,
+        value_map: &mut Vec<(Value, Value)>,
+        key_positions: &[usize],
+    ) -> Result<(), DecoderError> {
+        let mut sorted_indices: Vec<usize> = (0..value_map.len()).collect();
+        // The sort is stable, so that equal keys stay in encountered order.
+        sorted_indices.sort_by(|&i, &j| value_map[i].0.cmp(&value_map[j].0));
+        let mut keep = vec![true; value_map.len()];
+        let mut first_duplicate: Option<usize> = None;
+        let mut start = 0;
+        while start < sorted_indices.len() {
+            let first = sorted_indices[start];
+            let mut end = start + 1;
+            while end < sorted_indices.len()
+                && value_map[sorted_indices[end]].0 == value_map[first].0
+            {
+                end += 1;
+            }
+            if end - start > 1 {
+                let duplicate = sorted_indices[start + 1];
+                first_duplicate = Some(first_duplicate.map_or(duplicate, |d| d.min(duplicate)));
+                if self.options.on_duplicate_key == DuplicateKeyHandling::KeepLast {
+                    let (head, tail) = value_map.split_at_mut(sorted_indices[end - 1]);
+                    core::mem::swap(&mut head[first].1, &mut tail[0].1);
+                }
+                for &index in &sorted_indices[start + 1..end] {
+                    keep[index] = false;
+                }
+            }
+            start = end;
+        }
+        match first_duplicate {
+            None => return Ok(()),
+            Some(index) if self.options.on_duplicate_key == DuplicateKeyHandling::Error => {
+                // The earliest duplicate in the data is reported, as when decoding strictly.
+                self.error_remaining_len = Some(key_positions[index]);
+                return Err(DecoderError::OutOfOrderKey);
+            }
+            Some(_) => {}
         }
-        Ok(cbor_map_collection!(value_map))
+        let mut keep = keep.into_iter();
+        value_map.retain(|_| keep.next().unwrap_or(true));
+        Ok(())
     }
 
     fn read_tagged_content(
@@ -221,7 +1155,73 @@ impl<'a> Reader<'a> {
         remaining_depth: Option<i8>,
     ) -> Result<Value, DecoderError> {
         let inner_value = self.decode_complete_data_item(remaining_depth.map(|d| d - 1))?;
-        Ok(cbor_tagged!(tag_value, inner_value))
+        match self
+            .tag_handlers
+            .and_then(|tag_handlers| tag_handlers.handlers.get(&tag_value))
+        {
+            Some(handler) => handler(inner_value),
+            None if tag_value == Constants::TAG_ENCODED_CBOR && self.options.decode_tag24 => {
+                match inner_value {
+                    Value::ByteString(encoded_cbor) => {
+                        self.decode_embedded_data_item(&encoded_cbor, remaining_depth)
+                    }
+                    _ => Err(DecoderError::InvalidTaggedValue),
+                }
+            }
+            None => Ok(cbor_tagged!(tag_value, inner_value)),
+        }
+    }
+
+    /// Decode a complete data item embedded in a byte string with the same rules and limits,
+    /// counting its items towards the total.
+    fn decode_embedded_data_item(
+        &mut self,
+        encoded_cbor: &[u8],
+        remaining_depth: Option<i8>,
+    ) -> Result<Value, DecoderError> {
+        let mut reader = Reader {
+            non_strict: self.non_strict,
+            allow_non_canonical: self.allow_non_canonical,
+            remaining_cbor: encoded_cbor,
+            tag_handlers: self.tag_handlers,
+            options: self.options.clone(),
+            total_items: self.total_items,
+            error_remaining_len: None,
+            pool: core::mem::take(&mut self.pool),
+        };
+        let result = reader.decode_complete_data_item(remaining_depth.map(|d| d - 1));
+        self.pool = core::mem::take(&mut reader.pool);
+        let value = result?;
+        if !reader.remaining_cbor.is_empty() {
+            return Err(DecoderError::ExtraneousData);
+        }
+        self.total_items = reader.total_items;
+        Ok(value)
+    }
+
+    /// Read a half, single or double precision float, widened to double precision.  Unless
+    /// non-canonical data is allowed, the float must be in its shortest encoding preserving its
+    /// value, as written by the writer, with NaN as a half-precision quiet NaN.
+    fn read_float(&mut self, additional_info: u8) -> Result<Value, DecoderError> {
+        let bits = match self
+            .read_bytes(1 << (additional_info - Constants::ADDITIONAL_INFORMATION_1_BYTE))
+        {
+            Some(bytes) => bytes
+                .iter()
+                .fold(0u64, |bits, byte| (bits << 8) | *byte as u64),
+            None => return Err(DecoderError::IncompleteCborData),
+        };
+        let float = match additional_info {
+            Constants::ADDITIONAL_INFORMATION_2_BYTES => f16_to_f64(bits as u16),
+            Constants::ADDITIONAL_INFORMATION_4_BYTES => f32::from_bits(bits as u32) as f64,
+            _ => f64::from_bits(bits),
+        };
+        if shortest_float_bits(float) != (additional_info, bits)
+            && (!self.allow_non_canonical || self.options.require_minimal)
+        {
+            return Err(DecoderError::NonMinimalCborEncoding);
+        }
+        Ok(Value::Float(float))
     }
 
     fn decode_to_simple_value(
@@ -237,7 +1237,12 @@ impl<'a> Reader<'a> {
             // Also, why not just disallow ANY additional_info != size_value?
             return Err(DecoderError::UnsupportedFloatingPointValue);
         }
-        match SimpleValue::from_integer(size_value) {
+        // Simple values below 32 must use the one-byte form (RFC 8949 §3.3).
+        let simple_value = match additional_info {
+            Constants::ADDITIONAL_INFORMATION_1_BYTE if size_value < 32 => None,
+            _ => SimpleValue::from_integer(size_value),
+        };
+        match simple_value {
             Some(simple_value) => Ok(Value::Simple(simple_value)),
             None if self.non_strict => Ok(Value::Simple(SimpleValue::Undefined)),
             None => Err(DecoderError::UnsupportedSimpleValue),
@@ -251,8 +1256,9 @@ mod test {
 
     use super::*;
     use crate::{
-        cbor_array, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null, cbor_true,
-        cbor_undefined,
+        cbor_array, cbor_array_vec, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null,
+        cbor_simple, cbor_tagged, cbor_text, cbor_true, cbor_undefined,
+        writer::{write_framed, write_self_describing},
     };
 
     #[test]
@@ -615,6 +1621,54 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_read_nested_tags() {
+        // Tags count against the nesting limit like containers, as they do in the writer.
+        let nested_tags = |depth: usize| {
+            let mut cbor = vec![0xc1; depth];
+            cbor.push(0x00);
+            cbor
+        };
+        let cbor = nested_tags(200);
+        assert_eq!(read(&cbor), Err(DecoderError::TooMuchNesting));
+        assert_eq!(
+            read_nested_non_strict(&cbor, Some(MAX_NESTING_DEPTH)),
+            Err(DecoderError::TooMuchNesting)
+        );
+        assert_eq!(validate_canonical(&cbor), Err(DecoderError::TooMuchNesting));
+        let mut map_cbor = vec![0xa1, 0x01];
+        map_cbor.extend_from_slice(&cbor);
+        assert_eq!(
+            read_fields(&map_cbor, &[cbor_int!(2)]),
+            Err(DecoderError::TooMuchNesting)
+        );
+
+        let mut value = cbor_int!(0);
+        for _ in 0..MAX_NESTING_DEPTH {
+            value = cbor_tagged!(1, value);
+        }
+        let cbor = nested_tags(MAX_NESTING_DEPTH as usize);
+        assert_eq!(read(&cbor).as_ref(), Ok(&value));
+        let mut encoded_cbor = Vec::new();
+        crate::writer::write(value.clone(), &mut encoded_cbor).unwrap();
+        assert_eq!(encoded_cbor, cbor);
+        let value = cbor_tagged!(1, value);
+        assert_eq!(
+            read(&nested_tags(MAX_NESTING_DEPTH as usize + 1)),
+            Err(DecoderError::TooMuchNesting)
+        );
+        assert_eq!(
+            crate::writer::write(value, &mut Vec::new()),
+            Err(crate::writer::EncoderError::TooMuchNesting)
+        );
+
+        assert_eq!(
+            read_nested(&nested_tags(4), Some(3)),
+            Err(DecoderError::TooMuchNesting)
+        );
+        assert!(read_nested(&nested_tags(3), Some(3)).is_ok());
+    }
+
     #[test]
     fn test_read_integer_out_of_range() {
         let cases = vec![
@@ -657,6 +1711,109 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_read_floats() {
+        let options = ReaderOptions {
+            decode_floats: true,
+            ..Default::default()
+        };
+        // Examples from RFC 8949 Appendix A, in their shortest encoding.
+        let cases = vec![
+            (vec![0xF9, 0x00, 0x00], 0.0),
+            (vec![0xF9, 0x80, 0x00], -0.0),
+            (vec![0xF9, 0x3C, 0x00], 1.0),
+            (
+                vec![0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A],
+                1.1,
+            ),
+            (vec![0xF9, 0x3E, 0x00], 1.5),
+            (vec![0xF9, 0x7B, 0xFF], 65504.0),
+            (vec![0xFA, 0x47, 0xC3, 0x50, 0x00], 100000.0),
+            (vec![0xFA, 0x7F, 0x7F, 0xFF, 0xFF], f32::MAX as f64),
+            (
+                vec![0xFB, 0x7E, 0x37, 0xE4, 0x3C, 0x88, 0x00, 0x75, 0x9C],
+                1.0e+300,
+            ),
+            (vec![0xF9, 0x00, 0x01], 5.960464477539063e-8),
+            (vec![0xF9, 0x04, 0x00], 0.00006103515625),
+            (vec![0xF9, 0xC4, 0x00], -4.0),
+            (
+                vec![0xFB, 0xC0, 0x10, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66],
+                -4.1,
+            ),
+            (vec![0xF9, 0x7C, 0x00], f64::INFINITY),
+            (vec![0xF9, 0x7E, 0x00], f64::NAN),
+            (vec![0xF9, 0xFC, 0x00], f64::NEG_INFINITY),
+        ];
+        for (cbor, float) in cases {
+            for value in [
+                read_with_options(&cbor, None, &options).unwrap(),
+                read_non_strict_with_options(&cbor, None, &options).unwrap(),
+            ] {
+                match value {
+                    Value::Float(f) if float.is_nan() => assert!(f.is_nan()),
+                    Value::Float(f) => assert_eq!(f.to_bits(), float.to_bits()),
+                    _ => panic!("expected a float for {:02X?}", cbor),
+                }
+                let mut encoded_cbor = Vec::new();
+                crate::writer::write(value, &mut encoded_cbor).unwrap();
+                assert_eq!(encoded_cbor, cbor);
+            }
+        }
+
+        // Examples from RFC 8949 Appendix A that are not in their shortest encoding.
+        let cases = vec![
+            (vec![0xFA, 0x7F, 0x80, 0x00, 0x00], f64::INFINITY),
+            (vec![0xFA, 0x7F, 0xC0, 0x00, 0x00], f64::NAN),
+            (vec![0xFA, 0xFF, 0x80, 0x00, 0x00], f64::NEG_INFINITY),
+            (
+                vec![0xFB, 0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                f64::INFINITY,
+            ),
+            (
+                vec![0xFB, 0x7F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                f64::NAN,
+            ),
+            (
+                vec![0xFB, 0xFF, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                f64::NEG_INFINITY,
+            ),
+            (vec![0xFA, 0x3F, 0xC0, 0x00, 0x00], 1.5),
+        ];
+        for (cbor, float) in cases {
+            assert_eq!(
+                read_with_options(&cbor, None, &options),
+                Err(DecoderError::NonMinimalCborEncoding)
+            );
+            assert_eq!(
+                read_non_strict_with_options(&cbor, None, &options),
+                Ok(Value::Float(float))
+            );
+            let minimal_options = ReaderOptions {
+                require_minimal: true,
+                ..options.clone()
+            };
+            assert_eq!(
+                read_non_strict_with_options(&cbor, None, &minimal_options),
+                Err(DecoderError::NonMinimalCborEncoding)
+            );
+        }
+
+        assert_eq!(
+            read_with_options(&[0xF9, 0x3C], None, &options),
+            Err(DecoderError::IncompleteCborData)
+        );
+        assert_eq!(
+            read_with_options(&[0x82, 0xF9, 0x3E, 0x00, 0x01], None, &options),
+            Ok(cbor_array![Value::Float(1.5), cbor_int!(1)])
+        );
+        // Without the option, floats stay unsupported.
+        assert_eq!(
+            read_nested_non_strict(&[0xF9, 0x3E, 0x00], None),
+            Ok(cbor_undefined!())
+        );
+    }
+
     #[test]
     fn test_read_incomplete_cbor_data_error() {
         let cases = vec![
@@ -679,6 +1836,43 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_read_length_overflow_error() {
+        // Strings of 2^32 bytes, whose length only fits in `usize` on 64-bit targets.
+        let overflow = |incomplete| {
+            if cfg!(target_pointer_width = "64") {
+                incomplete
+            } else {
+                DecoderError::LengthOverflow
+            }
+        };
+        let cases = vec![
+            vec![0x5B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+            vec![0x7B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+            vec![0x81, 0x5B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for cbor in cases {
+            assert_eq!(read(&cbor), Err(overflow(DecoderError::IncompleteCborData)));
+            assert_eq!(
+                validate_canonical(&cbor),
+                Err(overflow(DecoderError::IncompleteCborData))
+            );
+            #[cfg(feature = "std")]
+            assert!(matches!(
+                read_from(&mut &cbor[..]),
+                Err(ReadError::Decoder(e)) if e == overflow(DecoderError::UnexpectedEof)
+            ));
+        }
+        // Chunk of an indefinite-length byte string.
+        let cbor = vec![
+            0x5F, 0x5B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xFF,
+        ];
+        assert_eq!(
+            read_nested_non_strict(&cbor, None),
+            Err(overflow(DecoderError::IncompleteCborData))
+        );
+    }
+
     #[test]
     fn test_read_unknown_additional_info_error() {
         let cases = vec![
@@ -823,29 +2017,1151 @@ mod test {
     #[test]
     fn test_read_unsupported_simple_type() {
         let cases = vec![
-            vec![0xE0],
-            vec![0xF3],
+            // Reserved values.
             vec![0xF8, 0x18],
             vec![0xF8, 0x1C],
             vec![0xF8, 0x1D],
             vec![0xF8, 0x1E],
             vec![0xF8, 0x1F],
-            vec![0xF8, 0x20],
-            vec![0xF8, 0xFF],
         ];
         for cbor in cases {
             assert_eq!(read(&cbor), Err(DecoderError::UnsupportedSimpleValue));
         }
+
+        // The two-byte form of values below 32 is not well-formed, and not minimal below 24.
+        for cbor in [vec![0xF8, 0x00], vec![0xF8, 0x10], vec![0xF8, 0x14]] {
+            assert_eq!(read(&cbor), Err(DecoderError::NonMinimalCborEncoding));
+            assert_eq!(read_nested_non_strict(&cbor, None), Ok(cbor_undefined!()));
+        }
     }
 
     #[test]
-    fn test_read_super_long_content_dont_crash() {
+    fn test_read_unassigned_simple_values() {
         let cases = vec![
-            vec![0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
-            vec![0xBB, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            (cbor_simple!(0), vec![0xE0]),
+            (cbor_simple!(16), vec![0xF0]),
+            (cbor_simple!(19), vec![0xF3]),
+            (cbor_simple!(32), vec![0xF8, 0x20]),
+            (cbor_simple!(255), vec![0xF8, 0xFF]),
+        ];
+        for (value, cbor) in cases {
+            assert_eq!(read(&cbor), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_read_indefinite_length() {
+        let cases = vec![
+            (cbor_array![], vec![0x9F, 0xFF]),
+            (
+                cbor_array![1, cbor_array![2, 3]],
+                vec![0x9F, 0x01, 0x82, 0x02, 0x03, 0xFF],
+            ),
+            (
+                cbor_map! {"a" => 1, "b" => cbor_array![]},
+                vec![0xBF, 0x61, 0x61, 0x01, 0x61, 0x62, 0x9F, 0xFF, 0xFF],
+            ),
+            (cbor_bytes!(vec![]), vec![0x5F, 0xFF]),
+            (
+                cbor_bytes!(vec![0x01, 0x02, 0x03]),
+                vec![0x5F, 0x42, 0x01, 0x02, 0x41, 0x03, 0xFF],
+            ),
+            (
+                cbor_text!("streaming"),
+                vec![
+                    0x7F, 0x65, 0x73, 0x74, 0x72, 0x65, 0x61, 0x64, 0x6D, 0x69, 0x6E, 0x67, 0xFF,
+                ],
+            ),
+        ];
+        for (value, cbor) in cases {
+            assert_eq!(read_nested_non_strict(&cbor, None), Ok(value));
+            assert_eq!(read(&cbor), Err(DecoderError::UnknownAdditionalInfo));
+        }
+    }
+
+    #[test]
+    fn test_read_malformed_indefinite_length() {
+        let cases = vec![
+            (vec![0x9F, 0x01], DecoderError::IncompleteCborData),
+            (vec![0xBF, 0x01], DecoderError::IncompleteCborData),
+            (vec![0x5F, 0x41], DecoderError::IncompleteCborData),
+            // Chunk of a different major type.
+            (
+                vec![0x5F, 0x61, 0x61, 0xFF],
+                DecoderError::MalformedIndefiniteLengthItem,
+            ),
+            // Nested indefinite-length chunk.
+            (
+                vec![0x7F, 0x7F, 0xFF, 0xFF],
+                DecoderError::MalformedIndefiniteLengthItem,
+            ),
+            // Chunk splitting a character.
+            (
+                vec![0x7F, 0x61, 0xC3, 0x61, 0xBC, 0xFF],
+                DecoderError::InvalidUtf8,
+            ),
+            // Stray break and indefinite-length integers.
+            (vec![0xFF], DecoderError::UnknownAdditionalInfo),
+            (vec![0x1F], DecoderError::UnknownAdditionalInfo),
+            (vec![0xDF, 0x01], DecoderError::UnknownAdditionalInfo),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(read_nested_non_strict(&cbor, None), Err(error));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_from() {
+        let mut source = std::io::Cursor::new(vec![
+            0xa2, 0x01, 0x82, 0x02, 0x03, 0x61, 0x61, 0xc1, 0x43, 0x01, 0x02, 0x03, // map
+            0x18, 0x64, // 100
+            0x63, 0x61, // truncated text string
+        ]);
+        assert_eq!(
+            read_from(&mut source).unwrap(),
+            cbor_map! {1 => cbor_array![2, 3], "a" => cbor_tagged!(1, cbor_bytes!(vec![1, 2, 3]))}
+        );
+        assert_eq!(source.position(), 12);
+        assert_eq!(read_from(&mut source).unwrap(), cbor_int!(100));
+        assert!(matches!(
+            read_from(&mut source),
+            Err(ReadError::Decoder(DecoderError::UnexpectedEof))
+        ));
+        assert!(matches!(
+            read_from(&mut source),
+            Err(ReadError::Decoder(DecoderError::UnexpectedEof))
+        ));
+
+        let cases = vec![
+            (vec![0x82, 0x01], DecoderError::UnexpectedEof),
+            (vec![0x1a, 0x01, 0x02], DecoderError::UnexpectedEof),
+            (vec![0x9f, 0x01, 0xff], DecoderError::UnknownAdditionalInfo),
+            (vec![0x18, 0x17], DecoderError::NonMinimalCborEncoding),
+            (
+                vec![0xa2, 0x02, 0x00, 0x01, 0x00],
+                DecoderError::OutOfOrderKey,
+            ),
+            (
+                vec![0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+                DecoderError::UnexpectedEof,
+            ),
+            (
+                vec![0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+                DecoderError::UnexpectedEof,
+            ),
+        ];
+        for (cbor, error) in cases {
+            match read_from(&mut &cbor[..]) {
+                Err(ReadError::Decoder(e)) => assert_eq!(e, error),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+
+        let mut deep = vec![0x81; 128];
+        deep.push(0x00);
+        assert!(matches!(
+            read_from(&mut &deep[..]),
+            Err(ReadError::Decoder(DecoderError::TooMuchNesting))
+        ));
+    }
+
+    #[test]
+    fn test_read_integer_major_types() {
+        // Major type 0 holds 0..=2^64-1 and major type 1 holds -2^64..=-1, so no integer has two
+        // encodings and the major type is preserved as is.
+        let cases = vec![
+            (vec![0x00], Value::Unsigned(0)),
+            (vec![0x17], Value::Unsigned(23)),
+            (
+                vec![0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+                Value::Unsigned(u64::MAX),
+            ),
+            (vec![0x20], Value::Negative(-1)),
+            (vec![0x37], Value::Negative(-24)),
+            (
+                vec![0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+                Value::Negative(-(1i128 << 64)),
+            ),
+        ];
+        for (cbor, value) in cases {
+            let decoded = read(&cbor).unwrap();
+            assert_eq!(decoded.type_label().to_label(), cbor[0] >> 5);
+            assert_eq!(decoded, value);
+            let mut encoded_cbor = Vec::new();
+            crate::writer::write(decoded, &mut encoded_cbor).unwrap();
+            assert_eq!(encoded_cbor, cbor);
+        }
+    }
+
+    #[test]
+    fn test_read_prefix() {
+        let cbor = [0x18, 0x64, 0x20];
+        assert_eq!(read_prefix(&cbor), Ok((cbor_int!(100), 2)));
+        assert_eq!(read_prefix(&cbor[2..]), Ok((cbor_int!(-1), 1)));
+        assert_eq!(
+            read_prefix(&[0x82, 0x01, 0x61, 0x61, 0xf6]),
+            Ok((cbor_array![1, "a"], 4))
+        );
+        assert_eq!(read_prefix(&[]), Err(DecoderError::IncompleteCborData));
+        assert_eq!(
+            read_prefix(&[0x82, 0x01]),
+            Err(DecoderError::IncompleteCborData)
+        );
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder = Decoder::new();
+        let cases = vec![
+            vec![0x01],
+            vec![0x43, 0x01, 0x02, 0x03],
+            vec![0x82, 0x61, 0x61, 0xa1, 0x01, 0x41, 0x00],
+            vec![0xc1, 0x81, 0x62, 0x61, 0x62],
         ];
         for cbor in cases {
-            assert_eq!(read(&cbor), Err(DecoderError::IncompleteCborData));
+            let value = decoder.decode(&cbor).unwrap();
+            assert_eq!(Ok(&value), read(&cbor).as_ref());
+            decoder.recycle(value);
+        }
+        assert_eq!(
+            decoder.decode(&[0x01, 0x02]),
+            Err(DecoderError::ExtraneousData)
+        );
+        assert_eq!(
+            decoder.decode(&[0xa2, 0x02, 0x00, 0x01, 0x00]),
+            Err(DecoderError::OutOfOrderKey)
+        );
+
+        // Buffers of recycled values are reused.
+        let value = decoder
+            .decode(&[0x82, 0x43, 0x01, 0x02, 0x03, 0x61, 0x61])
+            .unwrap();
+        let (array_ptr, bytes_ptr) = match &value {
+            Value::Array(array) => match &array[0] {
+                Value::ByteString(byte_string) => (array.as_ptr(), byte_string.as_ptr()),
+                _ => panic!("expected a byte string"),
+            },
+            _ => panic!("expected an array"),
+        };
+        decoder.recycle(value);
+        match decoder.decode(&[0x81, 0x42, 0x04, 0x05]).unwrap() {
+            Value::Array(array) => {
+                assert_eq!(array.as_ptr(), array_ptr);
+                assert_eq!(array, vec![cbor_bytes!(vec![0x04, 0x05])]);
+                match &array[0] {
+                    Value::ByteString(byte_string) => assert_eq!(byte_string.as_ptr(), bytes_ptr),
+                    _ => panic!("expected a byte string"),
+                }
+            }
+            _ => panic!("expected an array"),
+        }
+
+        let mut decoder = Decoder::with_options(
+            Some(0),
+            ReaderOptions {
+                strip_self_describing: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(decoder.decode(&[0xd9, 0xd9, 0xf7, 0x01]), Ok(cbor_int!(1)));
+        assert_eq!(
+            decoder.decode(&[0x81, 0x01]),
+            Err(DecoderError::TooMuchNesting)
+        );
+    }
+
+    #[test]
+    fn test_read_self_describing() {
+        let options = ReaderOptions {
+            strip_self_describing: true,
+            ..Default::default()
+        };
+        let mut cbor = Vec::new();
+        write_self_describing(cbor_array![1, "a"], &mut cbor).unwrap();
+        assert_eq!(&cbor[..3], &[0xd9, 0xd9, 0xf7]);
+        assert_eq!(
+            read_with_options(&cbor, None, &options),
+            Ok(cbor_array![1, "a"])
+        );
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &options),
+            Ok(cbor_array![1, "a"])
+        );
+        // The tag is kept unless stripping is enabled.
+        assert_eq!(read(&cbor), Ok(cbor_array![1, "a"].self_describing()));
+        // Untagged data and other tags are left alone, and only the outermost tag is stripped.
+        let cases = vec![
+            (vec![0x01], cbor_int!(1)),
+            (vec![0xc1, 0x01], cbor_tagged!(1, cbor_int!(1))),
+            (
+                vec![0xd9, 0xd9, 0xf7, 0xd9, 0xd9, 0xf7, 0x01],
+                cbor_int!(1).self_describing(),
+            ),
+        ];
+        for (cbor, value) in cases {
+            assert_eq!(read_with_options(&cbor, None, &options), Ok(value));
+        }
+        // Errors are located in the original data.
+        assert_eq!(
+            read_located_with_options(&[0xd9, 0xd9, 0xf7, 0x01, 0x02], None, &options),
+            Err(LocatedDecoderError {
+                kind: DecoderError::ExtraneousData,
+                offset: 4,
+            })
+        );
+        // The stripped tag does not count towards the nesting limit.
+        assert_eq!(
+            read_with_options(&[0xd9, 0xd9, 0xf7, 0x81, 0x01], Some(1), &options),
+            Ok(cbor_array![1])
+        );
+        assert_eq!(
+            read_with_options(&[0xd9, 0xd9, 0xf7], None, &options),
+            Err(DecoderError::IncompleteCborData)
+        );
+    }
+
+    #[test]
+    fn test_read_framed() {
+        let frames = [0x00, 0x00, 0x00, 0x04, 0x82, 0x01, 0x61, 0x61, 0x00, 0x00];
+        assert_eq!(read_framed(&frames), Ok((cbor_array![1, "a"], 8)));
+        assert_eq!(
+            read_framed(&[0x00, 0x00, 0x00, 0x01, 0xf6]),
+            Ok((cbor_null!(), 5))
+        );
+
+        let cases = vec![
+            // Truncated length.
+            (vec![], DecoderError::IncompleteCborData),
+            (vec![0x00, 0x00, 0x01], DecoderError::IncompleteCborData),
+            // Empty frame.
+            (
+                vec![0x00, 0x00, 0x00, 0x00, 0x01],
+                DecoderError::IncompleteCborData,
+            ),
+            // Length exceeding the remaining data.
+            (
+                vec![0x00, 0x00, 0x00, 0x02, 0x01],
+                DecoderError::IncompleteCborData,
+            ),
+            (
+                vec![0xff, 0xff, 0xff, 0xff, 0x01],
+                DecoderError::IncompleteCborData,
+            ),
+            // Item exceeding the frame.
+            (
+                vec![0x00, 0x00, 0x00, 0x02, 0x82, 0x01, 0x02],
+                DecoderError::IncompleteCborData,
+            ),
+            // Frame holding more than one item.
+            (
+                vec![0x00, 0x00, 0x00, 0x02, 0x01, 0x02],
+                DecoderError::ExtraneousData,
+            ),
+            // Non-canonical item.
+            (
+                vec![0x00, 0x00, 0x00, 0x02, 0x18, 0x01],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+        ];
+        for (frame, error) in cases {
+            assert_eq!(read_framed(&frame), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_framed_round_trip() {
+        let values = vec![
+            cbor_int!(0),
+            cbor_map! {3 => cbor_bytes!(vec![0x04; 1000]), "a" => cbor_array![1, -2]},
+            cbor_tagged!(5, cbor_text!("b")),
+        ];
+        let mut frames = Vec::new();
+        for value in &values {
+            write_framed(value.clone(), &mut frames).unwrap();
+        }
+        let mut remaining = &frames[..];
+        for value in values {
+            let (decoded, frame_len) = read_framed(remaining).unwrap();
+            assert_eq!(decoded, value);
+            remaining = &remaining[frame_len..];
+        }
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn test_read_sequence() {
+        assert_eq!(read_sequence(&[]), Ok(vec![]));
+        assert_eq!(read_sequence(&[0x01]), Ok(vec![cbor_int!(1)]));
+        assert_eq!(
+            read_sequence(&[0x01, 0x82, 0x02, 0x61, 0x61, 0xa0, 0xf6]),
+            Ok(vec![
+                cbor_int!(1),
+                cbor_array![2, "a"],
+                cbor_map! {},
+                cbor_null!()
+            ])
+        );
+        assert_eq!(
+            read_sequence(&[0x01, 0x82, 0x02]),
+            Err(DecoderError::IncompleteCborData)
+        );
+        assert_eq!(
+            read_sequence(&[0x01, 0x18, 0x01]),
+            Err(DecoderError::NonMinimalCborEncoding)
+        );
+    }
+
+    #[test]
+    fn test_read_fields() {
+        let value = cbor_map! {
+            1 => cbor_array![cbor_map! {"x" => cbor_array![1, 2, 3]}, 4],
+            2 => "b",
+            "a" => cbor_tagged!(1, cbor_map! {3 => 4}),
+            "c" => cbor_bytes!(vec![0x01, 0x02]),
+        };
+        let mut cbor = Vec::new();
+        crate::writer::write(value, &mut cbor).unwrap();
+        assert_eq!(
+            read_fields(
+                &cbor,
+                &[cbor_text!("c"), cbor_int!(3), cbor_int!(2), cbor_array![]]
+            ),
+            Ok(vec![
+                Some(cbor_bytes!(vec![0x01, 0x02])),
+                None,
+                Some(cbor_text!("b")),
+                None
+            ])
+        );
+        assert_eq!(read_fields(&cbor, &[]), Ok(vec![]));
+        assert_eq!(read_fields(&[0xa0], &[cbor_int!(1)]), Ok(vec![None]));
+
+        let cases = vec![
+            (vec![0x80], DecoderError::UnsupportedMajorType),
+            (vec![0xa1, 0x01], DecoderError::IncompleteCborData),
+            (vec![0xa1, 0x02, 0x81], DecoderError::IncompleteCborData),
+            (vec![0xa1, 0x02, 0x61, 0xff], DecoderError::InvalidUtf8),
+            (
+                vec![0xa1, 0x02, 0x9f, 0xff],
+                DecoderError::UnknownAdditionalInfo,
+            ),
+            (
+                vec![0xa1, 0x02, 0x18, 0x01],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+            (
+                vec![0xa2, 0x02, 0x00, 0x01, 0x00],
+                DecoderError::OutOfOrderKey,
+            ),
+            (
+                vec![0xa2, 0x02, 0x00, 0x02, 0x00],
+                DecoderError::OutOfOrderKey,
+            ),
+            (vec![0xa1, 0x01, 0x00, 0x00], DecoderError::ExtraneousData),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(read_fields(&cbor, &[cbor_int!(1)]), Err(error));
+        }
+
+        let mut nested_cbor = vec![0xa1, 0x02];
+        nested_cbor.extend(core::iter::repeat(0x81).take(127));
+        nested_cbor.push(0x00);
+        assert_eq!(
+            read_fields(&nested_cbor, &[cbor_int!(1)]),
+            Err(DecoderError::TooMuchNesting)
+        );
+    }
+
+    #[test]
+    fn test_read_invalid_utf8() {
+        // "a", then 0xC3 starting a two-byte sequence but followed by "(" instead of a
+        // continuation byte.
+        let definite_cbor = vec![0x63, 0x61, 0xC3, 0x28];
+        let indefinite_cbor = vec![0x7F, 0x61, 0x61, 0x62, 0xC3, 0x28, 0xFF];
+        let cases = vec![
+            (InvalidUtf8Handling::Error, Err(DecoderError::InvalidUtf8)),
+            (InvalidUtf8Handling::Lossy, Ok(cbor_text!("a\u{FFFD}("))),
+            (
+                InvalidUtf8Handling::AsBytes,
+                Ok(cbor_bytes!(vec![0x61, 0xC3, 0x28])),
+            ),
+        ];
+        for (invalid_utf8, expected) in cases {
+            let options = ReaderOptions {
+                invalid_utf8,
+                ..Default::default()
+            };
+            assert_eq!(read_with_options(&definite_cbor, None, &options), expected);
+            assert_eq!(
+                read_non_strict_with_options(&indefinite_cbor, None, &options),
+                expected
+            );
+            // Valid text strings and the surrounding structure are unaffected.
+            assert_eq!(
+                read_with_options(&[0x82, 0x61, 0x62, 0x62, 0xC3, 0xA9], None, &options),
+                Ok(cbor_array!["b", "é"])
+            );
+            let mut nested_cbor = vec![0x82, 0x01];
+            nested_cbor.extend(&definite_cbor);
+            let nested = read_with_options(&nested_cbor, None, &options);
+            match expected {
+                Ok(value) => assert_eq!(nested, Ok(cbor_array![cbor_int!(1), value])),
+                Err(error) => assert_eq!(nested, Err(error)),
+            }
+        }
+        assert_eq!(read(&definite_cbor), Err(DecoderError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_read_located() {
+        let cases = vec![
+            // Truncated item in an array.
+            (
+                vec![0x82, 0x01, 0x19, 0x01],
+                DecoderError::IncompleteCborData,
+                2,
+            ),
+            // Non-minimal integer in a map value.
+            (
+                vec![0xA1, 0x01, 0x82, 0x02, 0x18, 0x03],
+                DecoderError::NonMinimalCborEncoding,
+                4,
+            ),
+            // Out of order key.
+            (
+                vec![0xA2, 0x02, 0x00, 0x01, 0x00],
+                DecoderError::OutOfOrderKey,
+                3,
+            ),
+            // Invalid UTF-8 within a tag.
+            (vec![0xC1, 0x62, 0xC3, 0x28], DecoderError::InvalidUtf8, 1),
+            (vec![0x01, 0x02], DecoderError::ExtraneousData, 1),
+            (vec![], DecoderError::IncompleteCborData, 0),
+        ];
+        for (cbor, kind, offset) in cases {
+            assert_eq!(
+                read_located(&cbor),
+                Err(LocatedDecoderError { kind, offset })
+            );
+        }
+        assert_eq!(read_located(&[0x81, 0x01]), Ok(cbor_array![1]));
+
+        let options = ReaderOptions {
+            max_collection_len: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_located_with_options(&[0x81, 0x82, 0x01, 0x02], None, &options),
+            Err(LocatedDecoderError {
+                kind: DecoderError::LimitExceeded,
+                offset: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_with_options() {
+        let options = ReaderOptions {
+            max_collection_len: 3,
+            max_total_items: 6,
+            ..Default::default()
+        };
+        let cases = vec![
+            (vec![0x83, 0x01, 0x02, 0x03], Ok(cbor_array![1, 2, 3])),
+            (
+                vec![0x84, 0x01, 0x02, 0x03, 0x04],
+                Err(DecoderError::LimitExceeded),
+            ),
+            (
+                vec![0xA1, 0x01, 0x82, 0x02, 0x03],
+                Ok(cbor_map! {1 => cbor_array![2, 3]}),
+            ),
+            (
+                vec![0xA4, 0x01, 0x01, 0x02, 0x02, 0x03, 0x03, 0x04, 0x04],
+                Err(DecoderError::LimitExceeded),
+            ),
+            (
+                vec![0x82, 0x82, 0x01, 0x02, 0x83, 0x01, 0x02, 0x03],
+                Err(DecoderError::LimitExceeded),
+            ),
+            (
+                vec![0x82, 0x82, 0x01, 0x02, 0x81, 0x01],
+                Ok(cbor_array![cbor_array![1, 2], cbor_array![1]]),
+            ),
+        ];
+        for (cbor, result) in cases {
+            assert_eq!(read_with_options(&cbor, None, &options), result);
+        }
+
+        let mut cbor = vec![0x9F, 0x01, 0x02, 0x03, 0x04, 0xFF];
+        let mut reader = Reader::new_non_strict(&cbor);
+        reader.options = options.clone();
+        assert_eq!(
+            reader.decode_complete_data_item(None),
+            Err(DecoderError::LimitExceeded)
+        );
+        cbor.remove(4);
+        let mut reader = Reader::new_non_strict(&cbor);
+        reader.options = options;
+        assert_eq!(
+            reader.decode_complete_data_item(None),
+            Ok(cbor_array![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn test_read_non_strict_duplicate_key() {
+        let cbor = vec![
+            0xa4, // map of 4 pairs:
+            0x02, 0x61, 0x61, // 2 => "a"
+            0x01, 0x61, 0x62, // 1 => "b"
+            0x18, 0x02, 0x61, 0x63, // 2 => "c" (Duplicate key encoded with extra byte)
+            0x02, 0x61, 0x64, // 2 => "d" (Duplicate key)
+        ];
+        let with_duplicate_key = |on_duplicate_key| ReaderOptions {
+            on_duplicate_key,
+            ..ReaderOptions::default()
+        };
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &ReaderOptions::default()),
+            Err(DecoderError::OutOfOrderKey)
+        );
+        assert_eq!(
+            read_non_strict_with_options(
+                &cbor,
+                None,
+                &with_duplicate_key(DuplicateKeyHandling::KeepFirst)
+            ),
+            Ok(Value::Map(vec![
+                (cbor_int!(2), cbor_text!("a")),
+                (cbor_int!(1), cbor_text!("b")),
+            ]))
+        );
+        assert_eq!(
+            read_non_strict_with_options(
+                &cbor,
+                None,
+                &with_duplicate_key(DuplicateKeyHandling::KeepLast)
+            ),
+            Ok(Value::Map(vec![
+                (cbor_int!(2), cbor_text!("d")),
+                (cbor_int!(1), cbor_text!("b")),
+            ]))
+        );
+
+        // The first duplicate key is reported.
+        let mut reader = Reader::new_non_strict(&cbor);
+        assert_eq!(
+            reader.decode_located(None),
+            Err(LocatedDecoderError {
+                kind: DecoderError::OutOfOrderKey,
+                offset: 7,
+            })
+        );
+
+        // Indefinite-length maps are handled the same.
+        let cbor = vec![
+            0xbf, // indefinite-length map:
+            0x01, 0x02, // 1 => 2
+            0x01, 0x03, // 1 => 3 (Duplicate key)
+            0xff, // break
+        ];
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &ReaderOptions::default()),
+            Err(DecoderError::OutOfOrderKey)
+        );
+        assert_eq!(
+            read_non_strict_with_options(
+                &cbor,
+                None,
+                &with_duplicate_key(DuplicateKeyHandling::KeepFirst)
+            ),
+            Ok(cbor_map! {1 => 2})
+        );
+        assert_eq!(
+            read_non_strict_with_options(
+                &cbor,
+                None,
+                &with_duplicate_key(DuplicateKeyHandling::KeepLast)
+            ),
+            Ok(cbor_map! {1 => 3})
+        );
+
+        // Strict decoding rejects duplicate keys regardless.
+        let cbor = vec![0xa2, 0x01, 0x61, 0x61, 0x01, 0x61, 0x62];
+        for on_duplicate_key in [
+            DuplicateKeyHandling::Error,
+            DuplicateKeyHandling::KeepFirst,
+            DuplicateKeyHandling::KeepLast,
+        ] {
+            assert_eq!(
+                read_with_options(&cbor, None, &with_duplicate_key(on_duplicate_key)),
+                Err(DecoderError::OutOfOrderKey)
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_non_strict_large_reverse_map() {
+        // Duplicate keys are found in O(n log n) time, whatever the order of the keys.
+        const LEN: u32 = 100_000;
+        let mut cbor = vec![0xba];
+        cbor.extend_from_slice(&(LEN + 1).to_be_bytes());
+        for key in (0..LEN).rev() {
+            cbor.push(0x1a);
+            cbor.extend_from_slice(&key.to_be_bytes());
+            cbor.push(0x00);
+        }
+        // 0 => 1 (Duplicate key)
+        cbor.extend_from_slice(&[0x00, 0x01]);
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &ReaderOptions::default()),
+            Err(DecoderError::OutOfOrderKey)
+        );
+        let options = ReaderOptions {
+            on_duplicate_key: DuplicateKeyHandling::KeepLast,
+            ..ReaderOptions::default()
+        };
+        let mut expected_map = (0..LEN as u64)
+            .rev()
+            .map(|key| (cbor_unsigned!(key), cbor_unsigned!(0)))
+            .collect::<Vec<_>>();
+        expected_map[LEN as usize - 1].1 = cbor_unsigned!(1);
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &options),
+            Ok(Value::Map(expected_map))
+        );
+    }
+
+    #[test]
+    fn test_read_non_strict_map_order() {
+        let cbor = vec![
+            0xa3, // map of 3 pairs:
+            0x62, 0x61, 0x61, 0x01, // "aa" => 1
+            0x61, 0x62, // "b" =>
+            0xa2, 0x02, 0x00, 0x01, 0x00, // {2 => 0, 1 => 0}
+            0x18, 0x0a, 0x00, // 10 => 0 (Key encoded with extra byte)
+        ];
+        let as_encountered = read_non_strict_with_options(&cbor, None, &ReaderOptions::default());
+        assert_eq!(
+            as_encountered,
+            Ok(Value::Map(vec![
+                (cbor_text!("aa"), cbor_int!(1)),
+                (
+                    cbor_text!("b"),
+                    Value::Map(vec![
+                        (cbor_int!(2), cbor_int!(0)),
+                        (cbor_int!(1), cbor_int!(0))
+                    ])
+                ),
+                (cbor_int!(10), cbor_int!(0)),
+            ]))
+        );
+        let preserve_map_order = crate::writer::WriterOptions {
+            preserve_map_order: true,
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        crate::writer::write_with_options(
+            as_encountered.unwrap(),
+            &mut encoded_cbor,
+            None,
+            &preserve_map_order,
+        )
+        .unwrap();
+        let mut expected_cbor = cbor.clone();
+        expected_cbor.remove(12);
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        let canonical = ReaderOptions {
+            map_order: MapOrder::Canonical,
+            ..ReaderOptions::default()
+        };
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &canonical),
+            Ok(Value::Map(vec![
+                (cbor_int!(10), cbor_int!(0)),
+                (cbor_text!("b"), cbor_map! {1 => 0, 2 => 0}),
+                (cbor_text!("aa"), cbor_int!(1)),
+            ]))
+        );
+
+        // Indefinite-length maps are ordered the same.
+        let cbor = vec![
+            0xbf, // indefinite-length map:
+            0x02, 0x02, // 2 => 2
+            0x01, 0xbf, 0x04, 0x00, 0x03, 0x00, 0xff, // 1 => {_ 4 => 0, 3 => 0}
+            0xff, // break
+        ];
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &ReaderOptions::default()),
+            Ok(Value::Map(vec![
+                (cbor_int!(2), cbor_int!(2)),
+                (
+                    cbor_int!(1),
+                    Value::Map(vec![
+                        (cbor_int!(4), cbor_int!(0)),
+                        (cbor_int!(3), cbor_int!(0))
+                    ])
+                ),
+            ]))
+        );
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &canonical),
+            Ok(Value::Map(vec![
+                (cbor_int!(1), cbor_map! {3 => 0, 4 => 0}),
+                (cbor_int!(2), cbor_int!(2)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_read_non_strict_with_options() {
+        let require_minimal = ReaderOptions {
+            require_minimal: true,
+            ..ReaderOptions::default()
+        };
+        let cases = vec![
+            (vec![0x18, 0x00], cbor_int!(0)),
+            (vec![0x39, 0x00, 0x01], cbor_int!(-2)),
+            (vec![0x58, 0x01, 0x01], cbor_bytes!(vec![0x01])),
+            (vec![0x78, 0x01, 0x61], cbor_text!("a")),
+            (vec![0x99, 0x00, 0x01, 0x01], cbor_array![1]),
+            (
+                vec![0xba, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00],
+                cbor_map! {1 => 0},
+            ),
+            (vec![0xd8, 0x01, 0x00], cbor_tagged!(1, cbor_int!(0))),
+        ];
+        for (cbor, value) in cases {
+            assert_eq!(
+                read_non_strict_with_options(&cbor, None, &ReaderOptions::default()),
+                Ok(value)
+            );
+            assert_eq!(
+                read_non_strict_with_options(&cbor, None, &require_minimal),
+                Err(DecoderError::NonMinimalCborEncoding)
+            );
+        }
+
+        // Other non-canonical encodings are still accepted.
+        let cbor = vec![0xbf, 0x02, 0x00, 0x01, 0x9f, 0x18, 0x18, 0xff, 0xff];
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &require_minimal),
+            Ok(cbor_map! {2 => 0, 1 => cbor_array![24]})
+        );
+        let limits = ReaderOptions {
+            max_collection_len: 1,
+            ..require_minimal
+        };
+        assert_eq!(
+            read_non_strict_with_options(&cbor, None, &limits),
+            Err(DecoderError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_read_huge_declared_length() {
+        // Huge declared lengths followed by only three bytes of content: a million elements, map
+        // entries or bytes, and the largest 8-byte length.
+        let cases = vec![
+            vec![0x9A, 0x00, 0x0F, 0x42, 0x40],
+            vec![0xBA, 0x00, 0x0F, 0x42, 0x40],
+            vec![0x5A, 0x00, 0x0F, 0x42, 0x40],
+            vec![0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        ];
+        for mut cbor in cases {
+            cbor.extend_from_slice(&[0x00, 0x00, 0x00]);
+            assert_eq!(read(&cbor), Err(DecoderError::IncompleteCborData));
+        }
+        // The largest array that can be declared in three bytes.
+        assert_eq!(
+            read(&[0x99, 0xFF, 0xFF]),
+            Err(DecoderError::IncompleteCborData)
+        );
+        let options = ReaderOptions {
+            max_collection_len: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with_options(&[0x9A, 0x00, 0x0F, 0x42, 0x40, 0x00], None, &options),
+            Err(DecoderError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_read_with_tags() {
+        let mut tag_handlers = TagHandlers::new();
+        // Tag 0 must wrap a date/time string, and is kept.
+        tag_handlers.insert(0, |value| match value {
+            Value::TextString(_) => Ok(cbor_tagged!(0, value)),
+            _ => Err(DecoderError::InvalidTaggedValue),
+        });
+        // Tag 24 wraps an encoded data item, which is decoded in place.
+        tag_handlers.insert(24, |value| match value {
+            Value::ByteString(bytes) => read(&bytes),
+            _ => Err(DecoderError::InvalidTaggedValue),
+        });
+
+        let cases = vec![
+            (vec![0xC0, 0x61, 0x61], Ok(cbor_tagged!(0, cbor_text!("a")))),
+            (vec![0xC0, 0x01], Err(DecoderError::InvalidTaggedValue)),
+            (
+                vec![0x82, 0xD8, 0x18, 0x42, 0x81, 0x01, 0xC1, 0x01],
+                Ok(cbor_array![cbor_array![1], cbor_tagged!(1, cbor_int!(1))]),
+            ),
+            (
+                vec![0xD8, 0x18, 0x41, 0x18],
+                Err(DecoderError::IncompleteCborData),
+            ),
+            (vec![0xC1, 0x01, 0x01], Err(DecoderError::ExtraneousData)),
+        ];
+        for (cbor, result) in cases {
+            assert_eq!(read_with_tags(&cbor, &tag_handlers), result);
+        }
+        assert_eq!(read(&[0xC0, 0x01]), Ok(cbor_tagged!(0, cbor_int!(1))));
+    }
+
+    #[test]
+    fn test_read_tag24() {
+        let value = cbor_map! {1 => cbor_array![2], "a" => cbor_bytes!(vec![0x03])};
+        let tagged = value.clone().tagged_embedded_cbor().unwrap();
+        let mut cbor = Vec::new();
+        crate::writer::write(tagged.clone(), &mut cbor).unwrap();
+
+        // Lazily, the embedded item is kept as an opaque byte string.
+        let lazy = ReaderOptions::default();
+        assert_eq!(read_with_options(&cbor, None, &lazy), Ok(tagged));
+
+        let eager = ReaderOptions {
+            decode_tag24: true,
+            ..ReaderOptions::default()
+        };
+        assert_eq!(read_with_options(&cbor, None, &eager), Ok(value));
+        let mut nested_cbor = vec![0x81];
+        nested_cbor.extend_from_slice(&cbor);
+        assert_eq!(
+            read_with_options(&nested_cbor, Some(2), &eager),
+            Err(DecoderError::TooMuchNesting)
+        );
+        let limited = ReaderOptions {
+            max_total_items: 6,
+            ..eager.clone()
+        };
+        assert_eq!(
+            read_with_options(&cbor, None, &limited),
+            Err(DecoderError::LimitExceeded)
+        );
+
+        let cases = vec![
+            (
+                vec![0xD8, 0x18, 0x61, 0x61],
+                DecoderError::InvalidTaggedValue,
+            ),
+            (
+                vec![0xD8, 0x18, 0x42, 0x01, 0x02],
+                DecoderError::ExtraneousData,
+            ),
+            (
+                vec![0xD8, 0x18, 0x41, 0x18],
+                DecoderError::IncompleteCborData,
+            ),
+            (
+                vec![0xD8, 0x18, 0x42, 0x18, 0x01],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(read_with_options(&cbor, None, &eager), Err(error));
+        }
+        assert_eq!(
+            read_non_strict_with_options(&[0xD8, 0x18, 0x42, 0x18, 0x01], None, &eager),
+            Ok(cbor_int!(1))
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_bytes() {
+        let cases = vec![
+            (vec![0x18, 0x17], vec![0x17]),
+            (
+                vec![0x1b, 0, 0, 0, 0, 0, 0, 0x01, 0x00],
+                vec![0x19, 0x01, 0x00],
+            ),
+            (
+                vec![0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02],
+                vec![0xa2, 0x61, 0x61, 0x02, 0x61, 0x62, 0x01],
+            ),
+            (
+                vec![
+                    0x9f, 0x5f, 0x41, 0x01, 0x41, 0x02, 0xff, 0xbf, 0x02, 0x00, 0x01, 0x00, 0xff,
+                    0xff,
+                ],
+                vec![0x82, 0x42, 0x01, 0x02, 0xa2, 0x01, 0x00, 0x02, 0x00],
+            ),
+        ];
+        for (cbor, canonical_cbor) in cases {
+            assert_eq!(canonicalize_bytes(&cbor), Ok(canonical_cbor.clone()));
+            // Canonical input is returned unchanged.
+            assert_eq!(canonicalize_bytes(&canonical_cbor), Ok(canonical_cbor));
+        }
+
+        let canonical_cbor = crate::writer::write_with_capacity(
+            cbor_map! {
+                1 => cbor_array![cbor_bytes!(vec![0x01]), cbor_text!("a"), cbor_null!()],
+                -1 => cbor_tagged!(2, cbor_bytes!(vec![0x01, 0x00])),
+                "a" => cbor_map! {cbor_array![] => cbor_true!()},
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(canonicalize_bytes(&canonical_cbor), Ok(canonical_cbor));
+
+        let cases = vec![
+            (
+                vec![0xa2, 0x01, 0x00, 0x01, 0x01],
+                DecoderError::OutOfOrderKey,
+            ),
+            (vec![0x01, 0x02], DecoderError::ExtraneousData),
+            (vec![0x82, 0x01], DecoderError::IncompleteCborData),
+            (vec![0xf8, 0x18], DecoderError::UnsupportedSimpleValue),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(canonicalize_bytes(&cbor), Err(error));
+        }
+        assert_eq!(
+            canonicalize_prefix(&[0x18, 0x01, 0x02]),
+            Ok((vec![0x01], 2))
+        );
+    }
+
+    #[test]
+    fn test_validate_canonical() {
+        let cases = vec![
+            vec![0x00],
+            vec![0x18, 0x18],
+            vec![0x39, 0x01, 0x00],
+            vec![0x62, 0x49, 0x45],
+            vec![0x82, 0x01, 0x81, 0x02],
+            vec![0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0],
+            vec![0xA2, 0x01, 0xF4, 0x61, 0x61, 0xF6],
+            vec![0xA2, 0x61, 0x62, 0x00, 0x62, 0x61, 0x61, 0x00],
+            // Floats in their shortest encoding.
+            vec![0xF9, 0x3E, 0x00],
+            vec![0xF9, 0x7E, 0x00],
+            vec![0xFA, 0x47, 0xC3, 0x50, 0x00],
+            vec![0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A],
+        ];
+        for cbor in cases {
+            assert_eq!(validate_canonical(&cbor), Ok(()));
+            assert!(is_canonical(&cbor));
+        }
+        for float in [0.0, -0.0, 1.5, 100000.0, 1.1, f64::INFINITY, f64::NAN] {
+            let encoded = crate::writer::encode_to_vec(cbor_array![Value::Float(float)]).unwrap();
+            assert!(is_canonical(&encoded), "{:?}", float);
+        }
+        let encoded = crate::writer::write_with_capacity(
+            cbor_map! {
+                "a" => cbor_array![1, cbor_bytes!(vec![0x01])],
+                -24 => cbor_map! {2 => cbor_null!(), 1 => cbor_true!()},
+                1000 => cbor_text!("x"),
+            },
+            0,
+        )
+        .unwrap();
+        assert!(is_canonical(&encoded));
+    }
+
+    #[test]
+    fn test_validate_canonical_errors() {
+        let cases = vec![
+            // Uint 23 encoded with 1 byte.
+            (vec![0x18, 0x17], DecoderError::NonMinimalCborEncoding),
+            // Text string length 1 encoded with 1 byte.
+            (vec![0x78, 0x01, 0x61], DecoderError::NonMinimalCborEncoding),
+            // Nested uint 255 encoded with 2 bytes.
+            (
+                vec![0x81, 0xA1, 0x01, 0x19, 0x00, 0xFF],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+            // Unsorted keys.
+            (
+                vec![0xA2, 0x02, 0x00, 0x01, 0x00],
+                DecoderError::OutOfOrderKey,
+            ),
+            (
+                vec![0xA2, 0x62, 0x61, 0x61, 0x00, 0x61, 0x62, 0x00],
+                DecoderError::OutOfOrderKey,
+            ),
+            // Duplicate keys.
+            (
+                vec![0xA2, 0x01, 0x00, 0x01, 0x01],
+                DecoderError::OutOfOrderKey,
+            ),
+            // Indefinite length.
+            (vec![0x9F, 0xFF], DecoderError::UnknownAdditionalInfo),
+            (vec![0x82, 0x01], DecoderError::IncompleteCborData),
+            (vec![0x62, 0xC3, 0x28], DecoderError::InvalidUtf8),
+            (vec![0x01, 0x01], DecoderError::ExtraneousData),
+            // Floats with a shorter encoding.
+            (
+                vec![0xFA, 0x3F, 0xC0, 0x00, 0x00],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+            (
+                vec![0xFB, 0x7F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                DecoderError::NonMinimalCborEncoding,
+            ),
+            (vec![0xF9, 0x7E, 0x01], DecoderError::NonMinimalCborEncoding),
+            (vec![0xF9, 0x3E], DecoderError::IncompleteCborData),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(validate_canonical(&cbor), Err(error));
+            assert!(!is_canonical(&cbor));
+        }
+        let mut deep = vec![0x81; 128];
+        deep.push(0x00);
+        assert_eq!(validate_canonical(&deep), Err(DecoderError::TooMuchNesting));
+    }
+
+    #[test]
+    fn test_read_super_long_content_dont_crash() {
+        let cases = vec![
+            vec![0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            vec![0xBB, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        ];
+        for cbor in cases {
+            assert_eq!(read(&cbor), Err(DecoderError::IncompleteCborData));
+        }
+    }
+
+    #[test]
+    fn test_read_truncated_dont_crash() {
+        let cases = vec![
+            vec![0x1B, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+            vec![0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE],
+            vec![0x5A, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02],
+            vec![0x7B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x61],
+            vec![
+                0x9A, 0x00, 0x00, 0x00, 0x01, 0xDB, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07,
+                0x00,
+            ],
+            vec![0xB9, 0x00, 0x01, 0x61, 0x61, 0xC2, 0x41, 0x01],
+            vec![0x9F, 0x5F, 0x41, 0x01, 0xFF, 0xBF, 0x00, 0x01, 0xFF, 0xFF],
+        ];
+        for cbor in cases {
+            for len in 0..cbor.len() {
+                let truncated = &cbor[..len];
+                assert_eq!(
+                    read_nested_non_strict(truncated, Some(i8::MAX)),
+                    Err(DecoderError::IncompleteCborData)
+                );
+                assert!(read(truncated).is_err());
+                assert!(validate_canonical(truncated).is_err());
+                assert!(canonicalize_bytes(truncated).is_err());
+            }
+            assert!(read_nested_non_strict(&cbor, Some(i8::MAX)).is_ok());
         }
     }
 }
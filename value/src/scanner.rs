@@ -0,0 +1,413 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functionality for scanning CBOR data as a sequence of events, without allocating.
+
+use core::{convert::TryFrom, str};
+
+use super::{
+    reader::DecoderError,
+    values::{Constants, SimpleValue, MAX_NESTING_DEPTH},
+};
+
+/// Event produced when scanning CBOR data, borrowing from the input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    /// Unsigned integer value (uint).
+    Unsigned(u64),
+    /// Negative integer value (nint).
+    Negative(i128),
+    /// Definite-length byte string, or chunk of an indefinite-length one.
+    Bytes(&'a [u8]),
+    /// Definite-length text string, or chunk of an indefinite-length one.
+    Text(&'a str),
+    /// Start of an indefinite-length byte string, whose chunks follow until a [`Event::Break`].
+    BytesStart,
+    /// Start of an indefinite-length text string, whose chunks follow until a [`Event::Break`].
+    TextStart,
+    /// Start of an array with the given number of elements, or of an indefinite-length array
+    /// terminated by a [`Event::Break`] if `None`.
+    ArrayStart(Option<u64>),
+    /// Start of a map with the given number of key-value pairs, or of an indefinite-length map
+    /// terminated by a [`Event::Break`] if `None`.
+    MapStart(Option<u64>),
+    /// Tag applying to the following item.
+    Tag(u64),
+    /// Simple value.
+    Simple(SimpleValue),
+    /// Floating-point value, of any precision.
+    Float(f64),
+    /// End of an indefinite-length item.
+    Break,
+}
+
+/// Pull parser yielding the [`Event`]s of CBOR data one by one.
+///
+/// The scanner only decodes the data item heads; it neither checks that the data is in canonical
+/// form nor that events are well nested (e.g. it reports a [`Event::Break`] wherever one occurs).
+pub struct Scanner<'a> {
+    remaining_cbor: &'a [u8],
+}
+
+impl<'a> Scanner<'a> {
+    /// Create a scanner over the given CBOR data.
+    pub fn new(cbor: &'a [u8]) -> Scanner<'a> {
+        Scanner {
+            remaining_cbor: cbor,
+        }
+    }
+
+    /// Return the data that has not been scanned yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.remaining_cbor
+    }
+
+    /// Return whether all data has been scanned.
+    pub fn is_empty(&self) -> bool {
+        self.remaining_cbor.is_empty()
+    }
+
+    /// Scan the next event, failing with [`DecoderError::IncompleteCborData`] at the end of the
+    /// data.
+    pub fn next_event(&mut self) -> Result<Event<'a>, DecoderError> {
+        let first_byte = *self
+            .read_bytes(1)
+            .ok_or(DecoderError::IncompleteCborData)?
+            .first()
+            .unwrap();
+        let major_type_value = first_byte >> Constants::MAJOR_TYPE_BIT_SHIFT;
+        let additional_info = first_byte & Constants::ADDITIONAL_INFORMATION_MASK;
+        if additional_info == Constants::ADDITIONAL_INFORMATION_INDEFINITE_LENGTH {
+            return match major_type_value {
+                2 => Ok(Event::BytesStart),
+                3 => Ok(Event::TextStart),
+                4 => Ok(Event::ArrayStart(None)),
+                5 => Ok(Event::MapStart(None)),
+                7 => Ok(Event::Break),
+                _ => Err(DecoderError::UnknownAdditionalInfo),
+            };
+        }
+        if major_type_value == 7 {
+            return self.read_simple_or_float(additional_info);
+        }
+        let size_value = self.read_argument(additional_info)?;
+        match major_type_value {
+            0 => Ok(Event::Unsigned(size_value)),
+            1 => Ok(Event::Negative(-(size_value as i128) - 1)),
+            2 => Ok(Event::Bytes(self.read_content(size_value)?)),
+            3 => str::from_utf8(self.read_content(size_value)?)
+                .map(Event::Text)
+                .map_err(|_| DecoderError::InvalidUtf8),
+            4 => Ok(Event::ArrayStart(Some(size_value))),
+            5 => Ok(Event::MapStart(Some(size_value))),
+            _ => Ok(Event::Tag(size_value)),
+        }
+    }
+
+    /// Skip the next data item, including all its nested items.
+    ///
+    /// Definitely-sized items are skipped without recursion; only indefinite-length items
+    /// recurse, so that indefinite-length items nested more than [`MAX_NESTING_DEPTH`] levels
+    /// deep fail with [`DecoderError::TooMuchNesting`] rather than overflow the stack.
+    pub fn skip_item(&mut self) -> Result<(), DecoderError> {
+        self.skip_item_nested(MAX_NESTING_DEPTH)
+    }
+
+    /// Skip the next data item, with indefinite-length items only supported up to the given level
+    /// of nesting.
+    fn skip_item_nested(&mut self, remaining_depth: i8) -> Result<(), DecoderError> {
+        let mut pending = 1u64;
+        while pending > 0 {
+            pending -= 1;
+            match self.next_event()? {
+                Event::ArrayStart(Some(len)) => pending = pending.saturating_add(len),
+                Event::MapStart(Some(len)) => {
+                    pending = pending.saturating_add(len.saturating_mul(2))
+                }
+                Event::Tag(_) => pending += 1,
+                Event::BytesStart | Event::TextStart | Event::ArrayStart(None) => {
+                    if remaining_depth == 0 {
+                        return Err(DecoderError::TooMuchNesting);
+                    }
+                    while !self.read_break()? {
+                        self.skip_item_nested(remaining_depth - 1)?;
+                    }
+                }
+                Event::MapStart(None) => {
+                    if remaining_depth == 0 {
+                        return Err(DecoderError::TooMuchNesting);
+                    }
+                    while !self.read_break()? {
+                        self.skip_item_nested(remaining_depth - 1)?;
+                        self.skip_item_nested(remaining_depth - 1)?;
+                    }
+                }
+                Event::Break => return Err(DecoderError::MalformedIndefiniteLengthItem),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, num_bytes: usize) -> Option<&'a [u8]> {
+        if num_bytes > self.remaining_cbor.len() {
+            None
+        } else {
+            let (left, right) = self.remaining_cbor.split_at(num_bytes);
+            self.remaining_cbor = right;
+            Some(left)
+        }
+    }
+
+    /// Consume the break byte terminating an indefinite-length item, returning whether it was
+    /// present.
+    fn read_break(&mut self) -> Result<bool, DecoderError> {
+        match self.remaining_cbor.first() {
+            Some(&Constants::BREAK) => {
+                self.remaining_cbor = &self.remaining_cbor[1..];
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(DecoderError::IncompleteCborData),
+        }
+    }
+
+    fn read_argument(&mut self, additional_info: u8) -> Result<u64, DecoderError> {
+        let additional_bytes_num = match additional_info {
+            0..=Constants::ADDITIONAL_INFORMATION_MAX_INT => return Ok(additional_info as u64),
+            Constants::ADDITIONAL_INFORMATION_1_BYTE => 1,
+            Constants::ADDITIONAL_INFORMATION_2_BYTES => 2,
+            Constants::ADDITIONAL_INFORMATION_4_BYTES => 4,
+            Constants::ADDITIONAL_INFORMATION_8_BYTES => 8,
+            _ => return Err(DecoderError::UnknownAdditionalInfo),
+        };
+        let bytes = self
+            .read_bytes(additional_bytes_num)
+            .ok_or(DecoderError::IncompleteCborData)?;
+        Ok(bytes
+            .iter()
+            .fold(0u64, |size_value, byte| (size_value << 8) | *byte as u64))
+    }
+
+    fn read_content(&mut self, size_value: u64) -> Result<&'a [u8], DecoderError> {
+        let size = usize::try_from(size_value).map_err(|_| DecoderError::LengthOverflow)?;
+        self.read_bytes(size)
+            .ok_or(DecoderError::IncompleteCborData)
+    }
+
+    fn read_simple_or_float(&mut self, additional_info: u8) -> Result<Event<'a>, DecoderError> {
+        match additional_info {
+            Constants::ADDITIONAL_INFORMATION_2_BYTES => {
+                let bits = self.read_argument(additional_info)?;
+                Ok(Event::Float(f16_to_f64(bits as u16)))
+            }
+            Constants::ADDITIONAL_INFORMATION_4_BYTES => {
+                let bits = self.read_argument(additional_info)?;
+                Ok(Event::Float(f32::from_bits(bits as u32) as f64))
+            }
+            Constants::ADDITIONAL_INFORMATION_8_BYTES => {
+                let bits = self.read_argument(additional_info)?;
+                Ok(Event::Float(f64::from_bits(bits)))
+            }
+            _ => {
+                let size_value = self.read_argument(additional_info)?;
+                // Simple values below 32 must use the one-byte form (RFC 8949 §3.3).
+                if additional_info == Constants::ADDITIONAL_INFORMATION_1_BYTE && size_value < 32 {
+                    return Err(DecoderError::UnsupportedSimpleValue);
+                }
+                SimpleValue::from_integer(size_value)
+                    .map(Event::Simple)
+                    .ok_or(DecoderError::UnsupportedSimpleValue)
+            }
+        }
+    }
+}
+
+/// Convert the bits of an IEEE 754 half-precision float to a double-precision float.
+pub(crate) fn f16_to_f64(half: u16) -> f64 {
+    let sign = ((half & 0x8000) as u64) << 48;
+    let exponent = ((half >> 10) & 0x1F) as u64;
+    let mantissa = (half & 0x3FF) as u64;
+    match exponent {
+        // Subnormal: mantissa * 2^-24, which is exact in double precision.
+        0 => {
+            let magnitude = mantissa as f64 * f64::from_bits((1023 - 24) << 52);
+            f64::from_bits(sign | magnitude.to_bits())
+        }
+        // Infinity or NaN, keeping the payload.
+        0x1F => f64::from_bits(sign | (0x7FF << 52) | (mantissa << 42)),
+        _ => f64::from_bits(sign | ((exponent + 1023 - 15) << 52) | (mantissa << 42)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+
+    fn scan_all(cbor: &[u8]) -> Result<vec::Vec<Event>, DecoderError> {
+        let mut scanner = Scanner::new(cbor);
+        let mut events = vec::Vec::new();
+        while !scanner.is_empty() {
+            events.push(scanner.next_event()?);
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn test_scan() {
+        let cbor = vec![
+            0xa3, // map of 3 pairs
+            0x01, 0x38, 0x63, // 1 => -100
+            0x61, 0x61, 0x42, 0x01, 0x02, // "a" => h'0102'
+            0x61, 0x62, // "b"
+            0x9f, // indefinite-length array
+            0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0, // 1(1363896240)
+            0xf5, // true
+            0xf9, 0x3e, 0x00, // 1.5
+            0x7f, 0x61, 0x78, 0x61, 0x79, 0xff, // (_ "x", "y")
+            0xff, // break
+        ];
+        assert_eq!(
+            scan_all(&cbor),
+            Ok(vec![
+                Event::MapStart(Some(3)),
+                Event::Unsigned(1),
+                Event::Negative(-100),
+                Event::Text("a"),
+                Event::Bytes(&[0x01, 0x02]),
+                Event::Text("b"),
+                Event::ArrayStart(None),
+                Event::Tag(1),
+                Event::Unsigned(1363896240),
+                Event::Simple(SimpleValue::TrueValue),
+                Event::Float(1.5),
+                Event::TextStart,
+                Event::Text("x"),
+                Event::Text("y"),
+                Event::Break,
+                Event::Break,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_scan_floats() {
+        let cases = vec![
+            (vec![0xf9, 0x00, 0x00], 0.0),
+            (vec![0xf9, 0x80, 0x00], -0.0),
+            (vec![0xf9, 0x00, 0x01], 5.960464477539063e-8),
+            (vec![0xf9, 0x03, 0xff], 6.097555160522461e-5),
+            (vec![0xf9, 0x7b, 0xff], 65504.0),
+            (vec![0xf9, 0xc4, 0x00], -4.0),
+            (vec![0xf9, 0x7c, 0x00], f64::INFINITY),
+            (vec![0xfa, 0x47, 0xc3, 0x50, 0x00], 100000.0),
+            (
+                vec![0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a],
+                1.1,
+            ),
+        ];
+        for (cbor, float) in cases {
+            match Scanner::new(&cbor).next_event() {
+                Ok(Event::Float(f)) => assert_eq!(f.to_bits(), float.to_bits()),
+                event => panic!("unexpected event {:?}", event),
+            }
+        }
+        match Scanner::new(&[0xf9, 0x7e, 0x00]).next_event() {
+            Ok(Event::Float(f)) => assert!(f.is_nan()),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        let cases = vec![
+            (vec![], DecoderError::IncompleteCborData),
+            (vec![0x19, 0x01], DecoderError::IncompleteCborData),
+            (vec![0x43, 0x01, 0x02], DecoderError::IncompleteCborData),
+            (vec![0x62, 0xc3, 0x28], DecoderError::InvalidUtf8),
+            (vec![0x1c], DecoderError::UnknownAdditionalInfo),
+            (vec![0x3f], DecoderError::UnknownAdditionalInfo),
+            (vec![0xf8, 0x18], DecoderError::UnsupportedSimpleValue),
+            (vec![0xf8, 0x10], DecoderError::UnsupportedSimpleValue),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(Scanner::new(&cbor).next_event(), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_skip_item() {
+        let cbor = vec![
+            0x83, // array of 3 elements
+            0xa2, 0x01, 0x81, 0x02, 0x03, 0xc1, 0x04, // {1: [2], 3: 1(4)}
+            0xbf, 0x01, 0x9f, 0x02, 0xff, 0xff, // {_ 1: [_ 2]}
+            0x5f, 0x41, 0x01, 0xff, // (_ h'01')
+            0x07, // 7
+        ];
+        let mut scanner = Scanner::new(&cbor);
+        scanner.skip_item().unwrap();
+        assert_eq!(scanner.next_event(), Ok(Event::Unsigned(7)));
+        assert!(scanner.is_empty());
+
+        let mut scanner = Scanner::new(&cbor);
+        assert_eq!(scanner.next_event(), Ok(Event::ArrayStart(Some(3))));
+        scanner.skip_item().unwrap();
+        scanner.skip_item().unwrap();
+        assert_eq!(scanner.next_event(), Ok(Event::BytesStart));
+        assert_eq!(scanner.remaining(), &[0x41, 0x01, 0xff, 0x07]);
+
+        let cases = vec![
+            (vec![0x82, 0x01], DecoderError::IncompleteCborData),
+            (vec![0x9f, 0x01], DecoderError::IncompleteCborData),
+            (
+                vec![0x81, 0xff],
+                DecoderError::MalformedIndefiniteLengthItem,
+            ),
+        ];
+        for (cbor, error) in cases {
+            assert_eq!(Scanner::new(&cbor).skip_item(), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_skip_item_too_much_nesting() {
+        let depth = MAX_NESTING_DEPTH as usize;
+        let mut cbor = vec![0x9f; depth];
+        cbor.extend(vec![0xff; depth]);
+        let mut scanner = Scanner::new(&cbor);
+        assert_eq!(scanner.skip_item(), Ok(()));
+        assert!(scanner.is_empty());
+
+        let mut cbor = vec![0x9f; depth + 1];
+        cbor.extend(vec![0xff; depth + 1]);
+        assert_eq!(
+            Scanner::new(&cbor).skip_item(),
+            Err(DecoderError::TooMuchNesting)
+        );
+
+        // Hostile input fails instead of overflowing the stack.
+        for first_byte in [0x9f, 0xbf, 0x5f] {
+            assert_eq!(
+                Scanner::new(&vec![first_byte; 1_000_000]).skip_item(),
+                Err(DecoderError::TooMuchNesting)
+            );
+        }
+        // Definite-length items do not count towards the limit.
+        let mut cbor = vec![0x81; 1_000_000];
+        cbor.push(0x00);
+        assert_eq!(Scanner::new(&cbor).skip_item(), Ok(()));
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lightweight schemas for validating the shape of decoded CBOR data.
+
+use alloc::{boxed::Box, vec};
+
+use super::{
+    reader::{read, DecoderError},
+    values::{SimpleValue, Value},
+};
+
+/// Expected shape of a [`Value`], for validating untrusted data such as protocol messages without
+/// defining types for it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema<'a> {
+    /// Any value.
+    Any,
+    /// Boolean.
+    Bool,
+    /// Unsigned integer.
+    Uint,
+    /// Unsigned or negative integer.
+    Int,
+    /// Byte string.
+    Bytes,
+    /// Text string.
+    Text,
+    /// Floating-point value.
+    Float,
+    /// Array whose elements all match the given schema.
+    Array(Box<Schema<'a>>),
+    /// Map with the given keys, whose values match the corresponding schemas, rejecting any other
+    /// key with [`DecoderError::UnknownMapKey`].
+    Map(&'a [(Value, Schema<'a>)]),
+    /// Map with the given keys, whose values match the corresponding schemas, ignoring any other
+    /// key.
+    OpenMap(&'a [(Value, Schema<'a>)]),
+    /// Value tagged with the given tag number, whose content matches the given schema.
+    Tag(u64, Box<Schema<'a>>),
+    /// Null, or a value matching the given schema.  As the schema of a map entry, the key may also
+    /// be absent.
+    Optional(Box<Schema<'a>>),
+}
+
+impl Schema<'_> {
+    /// Check that the [`Value`] matches the schema, failing with [`DecoderError::SchemaMismatch`]
+    /// for a value of the wrong type or tag, [`DecoderError::MissingMapKey`] for a map lacking a
+    /// required key, or [`DecoderError::UnknownMapKey`] for a map with a key not in its schema.
+    ///
+    /// The value is traversed without recursion, so that checking deeply nested untrusted input
+    /// cannot overflow the stack.
+    pub fn check(&self, value: &Value) -> Result<(), DecoderError> {
+        let mut pending = vec![(self, value)];
+        while let Some((schema, value)) = pending.pop() {
+            match (schema, value) {
+                (Schema::Any, _)
+                | (Schema::Bool, Value::Simple(SimpleValue::FalseValue | SimpleValue::TrueValue))
+                | (Schema::Uint, Value::Unsigned(_))
+                | (Schema::Int, Value::Unsigned(_) | Value::Negative(_))
+                | (Schema::Bytes, Value::ByteString(_))
+                | (Schema::Text, Value::TextString(_))
+                | (Schema::Float, Value::Float(_))
+                | (Schema::Optional(_), Value::Simple(SimpleValue::NullValue)) => {}
+                (Schema::Optional(inner_schema), _) => pending.push((inner_schema, value)),
+                (Schema::Array(element_schema), Value::Array(array)) => {
+                    pending.extend(array.iter().map(|el| (&**element_schema, el)))
+                }
+                (Schema::Map(fields), Value::Map(map))
+                | (Schema::OpenMap(fields), Value::Map(map)) => {
+                    let mut found = 0;
+                    for (key, field_schema) in fields.iter() {
+                        match map.iter().find(|(k, _)| k == key) {
+                            Some((_, v)) => {
+                                found += 1;
+                                pending.push((field_schema, v));
+                            }
+                            None if matches!(field_schema, Schema::Optional(_)) => {}
+                            None => return Err(DecoderError::MissingMapKey),
+                        }
+                    }
+                    if matches!(schema, Schema::Map(_)) && found < map.len() {
+                        return Err(DecoderError::UnknownMapKey);
+                    }
+                }
+                (Schema::Tag(tag, inner_schema), Value::Tag(value_tag, inner_value))
+                    if tag == value_tag =>
+                {
+                    pending.push((inner_schema, inner_value))
+                }
+                _ => return Err(DecoderError::SchemaMismatch),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize CBOR binary data to produce a single [`Value`] as [`read`] does, then check that it
+/// matches the given schema as [`Schema::check`] does.
+pub fn read_checked(encoded_cbor: &[u8], schema: &Schema) -> Result<Value, DecoderError> {
+    let value = read(encoded_cbor)?;
+    schema.check(&value)?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{
+        cbor_array, cbor_bool, cbor_bytes, cbor_int, cbor_map, cbor_null, cbor_tagged, cbor_text,
+        writer::write,
+    };
+
+    fn encode(value: Value) -> Vec<u8> {
+        let mut encoded_cbor = Vec::new();
+        write(value, &mut encoded_cbor).unwrap();
+        encoded_cbor
+    }
+
+    #[test]
+    fn test_check_scalars() {
+        let cases = vec![
+            (Schema::Any, cbor_array![1]),
+            (Schema::Bool, cbor_bool!(true)),
+            (Schema::Uint, cbor_int!(1)),
+            (Schema::Int, cbor_int!(1)),
+            (Schema::Int, cbor_int!(-1)),
+            (Schema::Bytes, cbor_bytes!(vec![0x01])),
+            (Schema::Text, cbor_text!("a")),
+            (Schema::Float, Value::Float(1.5)),
+            (Schema::Optional(Box::new(Schema::Uint)), cbor_null!()),
+            (Schema::Optional(Box::new(Schema::Uint)), cbor_int!(1)),
+            (
+                Schema::Tag(1, Box::new(Schema::Uint)),
+                cbor_tagged!(1, cbor_int!(1)),
+            ),
+        ];
+        for (schema, value) in cases {
+            assert_eq!(schema.check(&value), Ok(()));
+        }
+
+        let cases = vec![
+            (Schema::Bool, cbor_null!()),
+            (Schema::Uint, cbor_int!(-1)),
+            (Schema::Int, cbor_text!("1")),
+            (Schema::Bytes, cbor_text!("a")),
+            (Schema::Text, cbor_bytes!(vec![0x61])),
+            (Schema::Float, cbor_int!(1)),
+            (Schema::Uint, cbor_null!()),
+            (Schema::Optional(Box::new(Schema::Uint)), cbor_text!("a")),
+            (
+                Schema::Tag(1, Box::new(Schema::Uint)),
+                cbor_tagged!(2, cbor_int!(1)),
+            ),
+            (
+                Schema::Tag(1, Box::new(Schema::Uint)),
+                cbor_tagged!(1, cbor_text!("a")),
+            ),
+            (Schema::Tag(1, Box::new(Schema::Uint)), cbor_int!(1)),
+        ];
+        for (schema, value) in cases {
+            assert_eq!(schema.check(&value), Err(DecoderError::SchemaMismatch));
+        }
+    }
+
+    #[test]
+    fn test_check_collections() {
+        let fields = [
+            (cbor_text!("id"), Schema::Uint),
+            (cbor_text!("tags"), Schema::Array(Box::new(Schema::Text))),
+            (cbor_text!("note"), Schema::Optional(Box::new(Schema::Text))),
+        ];
+        let schema = Schema::Map(&fields);
+        let open_schema = Schema::OpenMap(&fields);
+
+        let value = cbor_map! {"id" => 1, "tags" => cbor_array!["a", "b"]};
+        assert_eq!(schema.check(&value), Ok(()));
+        let value = cbor_map! {"id" => 1, "tags" => cbor_array![], "note" => "c"};
+        assert_eq!(schema.check(&value), Ok(()));
+        let value = cbor_map! {"id" => 1, "tags" => cbor_array![], "note" => cbor_null!()};
+        assert_eq!(schema.check(&value), Ok(()));
+
+        let value = cbor_map! {"id" => 1, "tags" => cbor_array!["a", 2]};
+        assert_eq!(schema.check(&value), Err(DecoderError::SchemaMismatch));
+        let value = cbor_map! {"tags" => cbor_array![]};
+        assert_eq!(schema.check(&value), Err(DecoderError::MissingMapKey));
+        assert_eq!(open_schema.check(&value), Err(DecoderError::MissingMapKey));
+        assert_eq!(
+            schema.check(&cbor_array![]),
+            Err(DecoderError::SchemaMismatch)
+        );
+
+        let value = cbor_map! {"id" => 1, "tags" => cbor_array![], "extra" => 0};
+        assert_eq!(schema.check(&value), Err(DecoderError::UnknownMapKey));
+        assert_eq!(open_schema.check(&value), Ok(()));
+
+        // Nested schemas are checked without recursion.
+        let mut schema = Schema::Uint;
+        let mut value = cbor_int!(0);
+        for _ in 0..100_000 {
+            schema = Schema::Array(Box::new(schema));
+            value = cbor_array![value];
+        }
+        assert_eq!(schema.check(&value), Ok(()));
+        let mut dismantled = value;
+        while let Value::Array(mut array) = dismantled {
+            dismantled = array.pop().unwrap();
+        }
+        while let Schema::Array(element_schema) = schema {
+            schema = *element_schema;
+        }
+    }
+
+    #[test]
+    fn test_read_checked() {
+        let fields = [
+            (cbor_int!(1), Schema::Text),
+            (cbor_int!(2), Schema::Optional(Box::new(Schema::Bytes))),
+        ];
+        let schema = Schema::Map(&fields);
+
+        let value = cbor_map! {1 => "a", 2 => cbor_bytes!(vec![0x01])};
+        assert_eq!(read_checked(&encode(value.clone()), &schema), Ok(value));
+        assert_eq!(
+            read_checked(&encode(cbor_map! {1 => 1}), &schema),
+            Err(DecoderError::SchemaMismatch)
+        );
+        assert_eq!(
+            read_checked(&encode(cbor_map! {1 => "a", 3 => 0}), &schema),
+            Err(DecoderError::UnknownMapKey)
+        );
+        assert_eq!(
+            read_checked(&[0xa1, 0x01], &schema),
+            Err(DecoderError::IncompleteCborData)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Round-trip property tests between the writer and the reader.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use quickcheck::{Arbitrary, Gen, QuickCheck};
+
+use crate::{reader, values::SimpleValue, writer, Value};
+
+/// Maximum nesting depth of generated values.
+const MAX_DEPTH: usize = 4;
+/// Maximum number of elements of generated arrays and maps.
+const MAX_LEN: usize = 5;
+
+/// Integers around the boundaries between encoding sizes.
+const INTERESTING_UNSIGNED: &[u64] = &[
+    0,
+    23,
+    24,
+    0xFF,
+    0x100,
+    0xFFFF,
+    0x1_0000,
+    0xFFFF_FFFF,
+    0x1_0000_0000,
+    u64::MAX,
+];
+
+impl Arbitrary for Value {
+    fn arbitrary(g: &mut Gen) -> Self {
+        arbitrary_value(g, MAX_DEPTH)
+    }
+}
+
+fn arbitrary_len(g: &mut Gen) -> usize {
+    usize::arbitrary(g) % (MAX_LEN + 1)
+}
+
+fn arbitrary_unsigned(g: &mut Gen) -> u64 {
+    if bool::arbitrary(g) {
+        *g.choose(INTERESTING_UNSIGNED).unwrap()
+    } else {
+        u64::arbitrary(g)
+    }
+}
+
+/// Generate a value nested at most `depth` levels deep, with maps sorted by key and free of
+/// duplicate keys, as the reader returns them.  Floats are not generated, as the reader only
+/// decodes them when asked to, see `float_round_trip`.
+fn arbitrary_value(g: &mut Gen, depth: usize) -> Value {
+    let choices: &[u8] = if depth == 0 {
+        &[0, 1, 2, 3, 4]
+    } else {
+        &[0, 1, 2, 3, 4, 5, 6, 7]
+    };
+    match g.choose(choices).unwrap() {
+        0 => Value::Unsigned(arbitrary_unsigned(g)),
+        1 => Value::Negative(-(arbitrary_unsigned(g) as i128) - 1),
+        2 => Value::ByteString(Vec::arbitrary(g)),
+        3 => Value::TextString(String::arbitrary(g)),
+        4 => Value::Simple(
+            g.choose(&[
+                SimpleValue::FalseValue,
+                SimpleValue::TrueValue,
+                SimpleValue::NullValue,
+                SimpleValue::Undefined,
+            ])
+            .unwrap()
+            .clone(),
+        ),
+        5 => Value::Array(
+            (0..arbitrary_len(g))
+                .map(|_| arbitrary_value(g, depth - 1))
+                .collect(),
+        ),
+        6 => {
+            let mut map: Vec<_> = (0..arbitrary_len(g))
+                .map(|_| (arbitrary_value(g, depth - 1), arbitrary_value(g, depth - 1)))
+                .collect();
+            map.sort_by(|a, b| a.0.cmp(&b.0));
+            map.dedup_by(|a, b| a.0 == b.0);
+            Value::Map(map)
+        }
+        _ => Value::Tag(
+            arbitrary_unsigned(g),
+            Box::new(arbitrary_value(g, depth - 1)),
+        ),
+    }
+}
+
+fn round_trip(value: Value) -> bool {
+    let mut encoded_cbor = Vec::new();
+    writer::write(value.clone(), &mut encoded_cbor).unwrap();
+    reader::is_canonical(&encoded_cbor) && reader::read(&encoded_cbor) == Ok(value)
+}
+
+#[test]
+fn test_round_trip() {
+    QuickCheck::new()
+        .tests(5000)
+        .quickcheck(round_trip as fn(Value) -> bool);
+}
+
+fn float_round_trip(float: f64) -> bool {
+    let options = reader::ReaderOptions {
+        decode_floats: true,
+        ..Default::default()
+    };
+    let mut encoded_cbor = Vec::new();
+    writer::write(Value::Float(float), &mut encoded_cbor).unwrap();
+    reader::read_with_options(&encoded_cbor, None, &options) == Ok(Value::Float(float))
+}
+
+#[test]
+fn test_float_round_trip() {
+    QuickCheck::new()
+        .tests(5000)
+        .quickcheck(float_round_trip as fn(f64) -> bool);
+}
@@ -15,18 +15,34 @@
 //! Types for expressing CBOR values.
 
 use alloc::{
+    borrow::Cow,
     boxed::Box,
+    collections::BTreeMap,
     string::{String, ToString},
+    vec,
     vec::Vec,
 };
-use core::cmp::Ordering;
+use core::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::net::IpAddr;
+
+use crate::{
+    reader::DecoderError,
+    writer::{shortest_float_bits, EncoderError},
+};
 
 /// Possible CBOR values.
 #[derive(Clone, Debug)]
 pub enum Value {
     /// Unsigned integer value (uint).
     Unsigned(u64),
-    /// Signed integer value (nint). Only 63 bits of information are used here.
+    /// Negative integer value (nint), which must be in the range -2^64..=-1.
     Negative(i128),
     /// Byte string (bstr).
     ByteString(Vec<u8>),
@@ -40,17 +56,94 @@ pub enum Value {
     Tag(u64, Box<Value>),
     /// Simple value.
     Simple(SimpleValue),
+    /// Floating-point value (IEEE 754 half, single or double precision).
+    Float(f64),
+}
+
+/// CBOR value borrowing its strings, so that data which is not owned can be serialized with
+/// [`crate::writer::write_value_ref`] without first being copied into a [`Value`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// Unsigned integer value (uint).
+    Unsigned(u64),
+    /// Negative integer value (nint), which must be in the range -2^64..=-1.
+    Negative(i128),
+    /// Borrowed byte string (bstr).
+    ByteString(&'a [u8]),
+    /// Borrowed text string (tstr).
+    TextString(&'a str),
+    /// Array/tuple of values.
+    Array(Vec<ValueRef<'a>>),
+    /// Map of key-value pairs.
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    /// Tagged value.
+    Tag(u64, Box<ValueRef<'a>>),
+    /// Simple value.
+    Simple(SimpleValue),
+    /// Floating-point value (IEEE 754 half, single or double precision).
+    Float(f64),
+    /// Borrowed [`Value`], serialized as is.
+    Value(&'a Value),
+    /// Borrowed serialized data of a single data item, written verbatim to splice cached data into
+    /// the output without decoding it.  The data is trusted to be a single well-formed item,
+    /// which is only checked in debug builds, and is neither subject to the nesting limit nor to
+    /// the writer options.  For the output to be canonical, the data must be canonical too; it
+    /// must be when used within a map key, for keys to be sorted and deduplicated correctly.
+    PreEncoded(&'a [u8]),
 }
 
 /// Specific simple CBOR values.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SimpleValue {
-    FalseValue = 20,
-    TrueValue = 21,
-    NullValue = 22,
-    Undefined = 23,
+    FalseValue,
+    TrueValue,
+    NullValue,
+    Undefined,
+    /// Simple value without a name, which must be in the range 0..=19 or 32..=255.  The values
+    /// 20..=23 are named above and 24..=31 are reserved, so that neither can be written as such.
+    Unassigned(u8),
 }
 
+/// CBOR major types, i.e. the top three bits of the initial byte of a data item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MajorType {
+    Unsigned = 0,
+    Negative = 1,
+    ByteString = 2,
+    TextString = 3,
+    Array = 4,
+    Map = 5,
+    Tag = 6,
+    SimpleFloat = 7,
+}
+
+impl MajorType {
+    /// Return the major type of a data item starting with the given byte.
+    pub fn from_initial_byte(b: u8) -> MajorType {
+        match b >> Constants::MAJOR_TYPE_BIT_SHIFT {
+            0 => MajorType::Unsigned,
+            1 => MajorType::Negative,
+            2 => MajorType::ByteString,
+            3 => MajorType::TextString,
+            4 => MajorType::Array,
+            5 => MajorType::Map,
+            6 => MajorType::Tag,
+            _ => MajorType::SimpleFloat,
+        }
+    }
+
+    /// Return the numeric value of the major type.
+    pub fn to_label(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Maximum level of nesting supported by default when reading and writing, i.e. the maximum
+/// [`Value::nesting_depth`] of values that [`read`](crate::reader::read) and
+/// [`write`](crate::writer::write) accept.  Tags count as a level of nesting like arrays and maps,
+/// so that chains of tags cannot nest deeper either.
+pub const MAX_NESTING_DEPTH: i8 = i8::MAX;
+
 /// Constant values required for CBOR encoding.
 pub struct Constants {}
 
@@ -70,6 +163,26 @@ impl Constants {
     pub const ADDITIONAL_INFORMATION_4_BYTES: u8 = 26;
     /// Additional information value indicating that an 8-byte length follows.
     pub const ADDITIONAL_INFORMATION_8_BYTES: u8 = 27;
+    /// Additional information value indicating an indefinite-length item.
+    pub const ADDITIONAL_INFORMATION_INDEFINITE_LENGTH: u8 = 31;
+    /// Byte terminating an indefinite-length item.
+    pub const BREAK: u8 = 0xFF;
+    /// Tag number of an unsigned bignum.
+    pub const TAG_UNSIGNED_BIGNUM: u64 = 2;
+    /// Tag number of a negative bignum.
+    pub const TAG_NEGATIVE_BIGNUM: u64 = 3;
+    /// Tag number of an embedded CBOR data item, held in a byte string.
+    pub const TAG_ENCODED_CBOR: u64 = 24;
+    /// Tag number of an IPv4 address, prefix or interface (RFC 9164).
+    pub const TAG_IPV4: u64 = 52;
+    /// Tag number of an IPv6 address, prefix or interface (RFC 9164).
+    pub const TAG_IPV6: u64 = 54;
+    /// Tag number of a duration (RFC 9581).
+    pub const TAG_DURATION: u64 = 1002;
+    /// Tag number marking self-described CBOR, whose head `0xD9D9F7` serves as a magic number.
+    pub const TAG_SELF_DESCRIBED_CBOR: u64 = 55799;
+    /// Encoded head of the self-described CBOR tag.
+    pub const SELF_DESCRIBED_CBOR_HEAD: [u8; 3] = [0xD9, 0xD9, 0xF7];
 }
 
 impl Value {
@@ -83,6 +196,43 @@ impl Value {
         }
     }
 
+    /// Create a CBOR negative integer value (nint), panicking if `n` is not negative.  Prefer this
+    /// to constructing [`Value::Negative`] directly, whose range is not checked.
+    pub fn negative(n: i64) -> Value {
+        assert!(n < 0, "Value::negative requires a negative value");
+        Value::Negative(n as i128)
+    }
+
+    /// Create an appropriate CBOR integer value (uint/nint) or, for values that do not fit in 64
+    /// bits, an unsigned bignum (tag 2).
+    pub fn from_u128(int: u128) -> Value {
+        match u64::try_from(int) {
+            Ok(unsigned) => Value::Unsigned(unsigned),
+            Err(_) => Value::Tag(
+                Constants::TAG_UNSIGNED_BIGNUM,
+                Box::new(Value::ByteString(bignum_bytes(int))),
+            ),
+        }
+    }
+
+    /// Create an appropriate CBOR integer value (uint/nint) or, for values that do not fit in 64
+    /// bits, an unsigned (tag 2) or negative (tag 3) bignum.
+    pub fn from_i128(int: i128) -> Value {
+        if int >= 0 {
+            return Value::from_u128(int as u128);
+        }
+        // Negative integers are encoded as -1 - n.
+        let n = (-(int + 1)) as u128;
+        if n <= u64::MAX as u128 {
+            Value::Negative(int)
+        } else {
+            Value::Tag(
+                Constants::TAG_NEGATIVE_BIGNUM,
+                Box::new(Value::ByteString(bignum_bytes(n))),
+            )
+        }
+    }
+
     /// Create a CBOR boolean simple value.
     pub fn bool_value(b: bool) -> Value {
         if b {
@@ -93,26 +243,941 @@ impl Value {
     }
 
     /// Return the major type for the [`Value`].
-    pub fn type_label(&self) -> u8 {
-        // TODO use enum discriminant instead when stable
-        // https://github.com/rust-lang/rust/issues/60553
+    pub fn type_label(&self) -> MajorType {
+        match self {
+            Value::Unsigned(_) => MajorType::Unsigned,
+            Value::Negative(_) => MajorType::Negative,
+            Value::ByteString(_) => MajorType::ByteString,
+            Value::TextString(_) => MajorType::TextString,
+            Value::Array(_) => MajorType::Array,
+            Value::Map(_) => MajorType::Map,
+            Value::Tag(_, _) => MajorType::Tag,
+            Value::Simple(_) | Value::Float(_) => MajorType::SimpleFloat,
+        }
+    }
+
+    /// Return a short name for the kind of the [`Value`], for diagnostics such as "expected text,
+    /// found array".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Unsigned(_) => "unsigned",
+            Value::Negative(_) => "negative",
+            Value::ByteString(_) => "bytes",
+            Value::TextString(_) => "text",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Tag(_, _) => "tag",
+            Value::Simple(_) => "simple",
+            Value::Float(_) => "float",
+        }
+    }
+
+    /// Return a lower bound on the size of the canonical encoding of the [`Value`], which can be
+    /// used to pre-allocate the output buffer.
+    pub fn encoded_len_hint(&self) -> usize {
+        match self {
+            Value::Unsigned(unsigned) => header_len(*unsigned),
+            Value::Negative(negative) => header_len(-(negative + 1) as u64),
+            Value::ByteString(byte_string) => {
+                header_len(byte_string.len() as u64) + byte_string.len()
+            }
+            Value::TextString(text_string) => {
+                header_len(text_string.len() as u64) + text_string.len()
+            }
+            Value::Array(array) => {
+                header_len(array.len() as u64)
+                    + array.iter().map(Value::encoded_len_hint).sum::<usize>()
+            }
+            Value::Map(map) => {
+                header_len(map.len() as u64)
+                    + map
+                        .iter()
+                        .map(|(k, v)| k.encoded_len_hint() + v.encoded_len_hint())
+                        .sum::<usize>()
+            }
+            Value::Tag(tag, inner_value) => header_len(*tag) + inner_value.encoded_len_hint(),
+            Value::Simple(simple_value) => header_len(simple_value.to_integer().into()),
+            // Floats take at least a half-precision encoding.
+            Value::Float(_) => 3,
+        }
+    }
+
+    /// Return the maximum nesting depth of containers (arrays, maps and tags) in the [`Value`], with
+    /// scalars having a depth of 0.  Any value whose depth does not exceed `max_nest` can be written
+    /// by [`write_nested`](crate::writer::write_nested).
+    ///
+    /// The value is traversed without recursion, so that measuring deeply nested untrusted input
+    /// cannot overflow the stack.
+    pub fn nesting_depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack = vec![(self, 0)];
+        while let Some((value, depth)) = stack.pop() {
+            let depth = match value {
+                Value::Array(array) => {
+                    stack.extend(array.iter().map(|el| (el, depth + 1)));
+                    depth + 1
+                }
+                Value::Map(map) => {
+                    stack.extend(
+                        map.iter()
+                            .flat_map(|(k, v)| [(k, depth + 1), (v, depth + 1)]),
+                    );
+                    depth + 1
+                }
+                Value::Tag(_, inner_value) => {
+                    stack.push((inner_value, depth + 1));
+                    depth + 1
+                }
+                _ => depth,
+            };
+            max_depth = max_depth.max(depth);
+        }
+        max_depth
+    }
+
+    /// Return whether the [`Value`] is nested at most [`MAX_NESTING_DEPTH`] levels deep, so that
+    /// it can be written with [`write`](crate::writer::write).
+    pub fn fits_default_nesting(&self) -> bool {
+        self.nesting_depth() <= MAX_NESTING_DEPTH as usize
+    }
+
+    /// Return whether the [`Value`] contains a float anywhere, including within map keys and tags,
+    /// for rejecting data in protocols that forbid floats for determinism.  The value is traversed
+    /// without recursion and the search stops at the first float found.
+    pub fn contains_float(&self) -> bool {
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Float(_) => return true,
+                Value::Array(array) => stack.extend(array),
+                Value::Map(map) => stack.extend(map.iter().flat_map(|(k, v)| [k, v])),
+                Value::Tag(_, inner_value) => stack.push(inner_value),
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Return the number of bytes allocated on the heap by the [`Value`], for enforcing memory
+    /// budgets on decoded data.  This counts the capacity (not the length) of the buffers backing
+    /// byte strings, text strings, arrays and maps, as well as the boxes holding tagged values,
+    /// recursively.  The size of the [`Value`] itself is not included, as it may live on the stack.
+    ///
+    /// The value is traversed without recursion, so that measuring deeply nested untrusted input
+    /// cannot overflow the stack.
+    pub fn heap_size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::ByteString(byte_string) => size += byte_string.capacity(),
+                Value::TextString(text_string) => size += text_string.capacity(),
+                Value::Array(array) => {
+                    size += array.capacity() * core::mem::size_of::<Value>();
+                    stack.extend(array.iter());
+                }
+                Value::Map(map) => {
+                    size += map.capacity() * core::mem::size_of::<(Value, Value)>();
+                    stack.extend(map.iter().flat_map(|(k, v)| [k, v]));
+                }
+                Value::Tag(_, inner_value) => {
+                    size += core::mem::size_of::<Value>();
+                    stack.push(inner_value);
+                }
+                _ => {}
+            }
+        }
+        size
+    }
+
+    /// Sort the entries of all maps in the [`Value`] in place by the bytewise order of their
+    /// encoded keys, failing with [`EncoderError::DuplicateMapKey`] if a map has duplicate keys.
+    ///
+    /// Writing a canonicalized value produces the same bytes as writing the original one, and can
+    /// be done with [`WriterOptions::presorted_maps`](crate::writer::WriterOptions) set to skip
+    /// sorting.
+    pub fn canonicalize(&mut self) -> Result<(), EncoderError> {
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Array(array) => stack.extend(array.iter_mut()),
+                Value::Map(map) => {
+                    let mut entries = core::mem::take(map)
+                        .into_iter()
+                        .map(|(k, v)| {
+                            let mut encoded_key = Vec::new();
+                            crate::writer::write(k.clone(), &mut encoded_key)?;
+                            Ok((encoded_key, (k, v)))
+                        })
+                        .collect::<Result<Vec<_>, EncoderError>>()?;
+                    entries.sort_by(|a, b| a.0.cmp(&b.0));
+                    if let Some(w) = entries.windows(2).find(|w| w[0].0 == w[1].0) {
+                        return Err(EncoderError::DuplicateMapKey {
+                            key: w[0].0.clone(),
+                        });
+                    }
+                    *map = entries.into_iter().map(|(_, entry)| entry).collect();
+                    for (k, v) in map.iter_mut() {
+                        stack.push(k);
+                        stack.push(v);
+                    }
+                }
+                Value::Tag(_, inner_value) => stack.push(inner_value),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all tags from the [`Value`], replacing each tagged value by its content, including
+    /// within arrays, maps and other tags.  Nested values are visited without recursion, so that
+    /// deeply nested input cannot overflow the stack.
+    pub fn strip_tags(mut self) -> Value {
+        let mut stack = vec![&mut self];
+        while let Some(value) = stack.pop() {
+            while let Value::Tag(_, inner_value) = value {
+                *value = inner_value.take();
+            }
+            match value {
+                Value::Array(array) => stack.extend(array.iter_mut()),
+                Value::Map(map) => {
+                    for (k, v) in map.iter_mut() {
+                        stack.push(k);
+                        stack.push(v);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// Return the element of a one-element array, or the [`Value`] unchanged otherwise.
+    pub fn unwrap_singleton(self) -> Value {
         match self {
-            Value::Unsigned(_) => 0,
-            Value::Negative(_) => 1,
-            Value::ByteString(_) => 2,
-            Value::TextString(_) => 3,
-            Value::Array(_) => 4,
-            Value::Map(_) => 5,
-            Value::Tag(_, _) => 6,
-            Value::Simple(_) => 7,
+            Value::Array(mut array) if array.len() == 1 => array.pop().unwrap(),
+            value => value,
+        }
+    }
+
+    /// Replace all one-element arrays in the [`Value`] by their element, repeatedly, and including
+    /// within arrays, maps and tags.  Nested values are visited without recursion, so that deeply
+    /// nested input cannot overflow the stack.
+    pub fn unwrap_singletons(mut self) -> Value {
+        let mut stack = vec![&mut self];
+        while let Some(value) = stack.pop() {
+            while let Value::Array(array) = value {
+                if array.len() != 1 {
+                    break;
+                }
+                *value = array.pop().unwrap();
+            }
+            match value {
+                Value::Array(array) => stack.extend(array.iter_mut()),
+                Value::Map(map) => {
+                    for (k, v) in map.iter_mut() {
+                        stack.push(k);
+                        stack.push(v);
+                    }
+                }
+                Value::Tag(_, inner_value) => stack.push(inner_value),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// Keep only the entries of a map for which `f` returns true, as with [`Vec::retain`].  Any
+    /// other variant is left untouched.
+    pub fn retain<F: FnMut(&Value, &Value) -> bool>(&mut self, mut f: F) {
+        if let Value::Map(map) = self {
+            map.retain(|(k, v)| f(k, v));
+        }
+    }
+
+    /// Keep only the entries for which `f` returns true in every map of the [`Value`], including
+    /// maps within arrays, tags and the values of retained entries.  Maps within keys are left
+    /// untouched, as changing keys could make them collide.  Nested values are visited without
+    /// recursion, so that deeply nested input cannot overflow the stack.
+    pub fn retain_recursive<F: FnMut(&Value, &Value) -> bool>(&mut self, mut f: F) {
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Array(array) => stack.extend(array.iter_mut()),
+                Value::Map(map) => {
+                    map.retain(|(k, v)| f(k, v));
+                    stack.extend(map.iter_mut().map(|(_, v)| v));
+                }
+                Value::Tag(_, inner_value) => stack.push(inner_value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Shorten every text and byte string in the [`Value`] longer than `max_len` bytes, including
+    /// strings within map keys, for logging untrusted data without flooding the logs.  Byte
+    /// strings are cut to `max_len` bytes, and text strings to at most `max_len` bytes at a
+    /// character boundary, followed by a `…` marker.  As the marker takes 3 bytes in UTF-8, a
+    /// truncated text string can be up to `max_len + 3` bytes long.  Nested values are visited
+    /// without recursion, so that deeply nested input cannot overflow the stack.
+    ///
+    /// This is lossy: truncated strings cannot be told apart from original ones ending the same
+    /// way, and truncated map keys may collide, so the result is only meant for display.
+    pub fn truncate_strings(&mut self, max_len: usize) {
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::ByteString(bytes) => bytes.truncate(max_len),
+                Value::TextString(text) if text.len() > max_len => {
+                    let mut len = max_len;
+                    while !text.is_char_boundary(len) {
+                        len -= 1;
+                    }
+                    text.truncate(len);
+                    text.push('…');
+                }
+                Value::Array(array) => stack.extend(array.iter_mut()),
+                Value::Map(map) => {
+                    for (k, v) in map.iter_mut() {
+                        stack.push(k);
+                        stack.push(v);
+                    }
+                }
+                Value::Tag(_, inner_value) => stack.push(inner_value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Sort the elements of an array by the canonical [`Ord`] of [`Value`], for arrays that
+    /// represent sets.  Any other variant is left untouched, as are nested arrays.
+    ///
+    /// Note that this changes the meaning of the array, as CBOR arrays are ordered in general, and
+    /// is not part of canonical CBOR: it is only meant for fields with set semantics that must
+    /// encode (and hash) the same whatever the order in which their elements were gathered.
+    pub fn sort_array_elements(&mut self) {
+        if let Value::Array(array) = self {
+            array.sort();
+        }
+    }
+
+    /// Sort the elements of every array in the [`Value`] as [`Value::sort_array_elements`] does,
+    /// including arrays within maps and tags, sorting nested arrays before the arrays that hold
+    /// them.  Arrays within map keys are left untouched, as changing keys could make them collide.
+    /// Nested values are visited without recursion, so that deeply nested input cannot overflow
+    /// the stack.
+    ///
+    /// As with [`Value::sort_array_elements`], this changes the meaning of every array and is not
+    /// part of canonical CBOR.
+    pub fn sort_all_arrays(&mut self) {
+        /// Container whose nested values are being sorted.
+        enum Frame {
+            Array(Vec<Value>, vec::IntoIter<Value>),
+            Map(Vec<(Value, Value)>, Value, vec::IntoIter<(Value, Value)>),
+            Tag(u64),
+        }
+
+        let mut frames = Vec::new();
+        let mut next = Some(self.take());
+        loop {
+            // Descend into the next value until reaching one without nested values.
+            let mut sorted = match next.take() {
+                Some(Value::Array(array)) => {
+                    frames.push(Frame::Array(
+                        Vec::with_capacity(array.len()),
+                        array.into_iter(),
+                    ));
+                    None
+                }
+                Some(Value::Map(map)) => {
+                    let entries = Vec::with_capacity(map.len());
+                    frames.push(Frame::Map(
+                        entries,
+                        Value::Simple(SimpleValue::NullValue),
+                        map.into_iter(),
+                    ));
+                    None
+                }
+                Some(Value::Tag(tag, inner_value)) => {
+                    frames.push(Frame::Tag(tag));
+                    next = Some(*inner_value);
+                    continue;
+                }
+                value => value,
+            };
+            // Ascend to the innermost container with values left to sort, finishing the others.
+            while next.is_none() {
+                match frames.last_mut() {
+                    None => {
+                        *self = sorted.take().unwrap();
+                        return;
+                    }
+                    Some(Frame::Array(elements, rest)) => {
+                        elements.extend(sorted.take());
+                        next = rest.next();
+                        if next.is_none() {
+                            let mut elements = core::mem::take(elements);
+                            elements.sort();
+                            sorted = Some(Value::Array(elements));
+                            frames.pop();
+                        }
+                    }
+                    Some(Frame::Map(entries, key, rest)) => {
+                        if let Some(value) = sorted.take() {
+                            entries.push((key.take(), value));
+                        }
+                        match rest.next() {
+                            Some((k, v)) => {
+                                *key = k;
+                                next = Some(v);
+                            }
+                            None => {
+                                sorted = Some(Value::Map(core::mem::take(entries)));
+                                frames.pop();
+                            }
+                        }
+                    }
+                    Some(Frame::Tag(tag)) => {
+                        sorted = Some(Value::Tag(*tag, Box::new(sorted.take().unwrap())));
+                        frames.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Merge `other` into `self`, with the entries of `other` overriding those of `self` with
+    /// the same key and the others being appended.  If `deep` is set, map values present in both
+    /// are merged recursively instead of being overridden.  If either value is not a map, `self`
+    /// is replaced by `other` wholesale.
+    ///
+    /// Fails with [`EncoderError::DuplicateMapKey`] if `other` has duplicate keys at a merged
+    /// level, as it is then unclear which entry wins; `self` is left partially merged.
+    pub fn merge(&mut self, other: Value, deep: bool) -> Result<(), EncoderError> {
+        let (map, other_map) = match (self, other) {
+            (Value::Map(map), Value::Map(other_map)) => (map, other_map),
+            (value, other) => {
+                *value = other;
+                return Ok(());
+            }
+        };
+        let original_len = map.len();
+        let mut merged = vec![false; original_len];
+        for (k, v) in other_map {
+            match map.iter().position(|(key, _)| *key == k) {
+                Some(i) if i >= original_len || merged[i] => {
+                    return Err(crate::writer::duplicate_key_error(&k))
+                }
+                Some(i) => {
+                    merged[i] = true;
+                    if deep {
+                        map[i].1.merge(v, deep)?;
+                    } else {
+                        map[i].1 = v;
+                    }
+                }
+                None => map.push((k, v)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Return whether two values are equal when maps are considered as unordered collections of
+    /// entries, at any level of nesting.  Unlike `==`, a map compares equal to the same entries in
+    /// any order.
+    ///
+    /// Matching map entries takes time quadratic in the size of the maps.
+    pub fn semantic_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Array(a1), Value::Array(a2)) => {
+                a1.len() == a2.len() && a1.iter().zip(a2).all(|(e1, e2)| e1.semantic_eq(e2))
+            }
+            (Value::Map(m1), Value::Map(m2)) => {
+                if m1.len() != m2.len() {
+                    return false;
+                }
+                // Match each entry with a distinct equal entry of the other map, which is enough
+                // since semantic equality is an equivalence relation.
+                let mut matched = vec![false; m2.len()];
+                m1.iter().all(|(k1, v1)| {
+                    let found = m2
+                        .iter()
+                        .zip(matched.iter())
+                        .position(|((k2, v2), m)| !*m && k1.semantic_eq(k2) && v1.semantic_eq(v2));
+                    match found {
+                        Some(i) => {
+                            matched[i] = true;
+                            true
+                        }
+                        None => false,
+                    }
+                })
+            }
+            (Value::Tag(t1, v1), Value::Tag(t2, v2)) => t1 == t2 && v1.semantic_eq(v2),
+            _ => self == other,
+        }
+    }
+
+    /// Return the value of an unsigned integer, or `None` for any other variant.
+    pub fn as_unsigned(&self) -> Option<u64> {
+        match self {
+            Value::Unsigned(unsigned) => Some(*unsigned),
+            _ => None,
+        }
+    }
+
+    /// Return the value of a negative integer, or `None` for any other variant.
+    pub fn as_negative(&self) -> Option<i128> {
+        match self {
+            Value::Negative(negative) => Some(*negative),
+            _ => None,
+        }
+    }
+
+    /// Return the value of an integer, including unsigned (tag 2) and negative (tag 3) bignums, or
+    /// `None` for any other variant or for bignums outside the range of `u128`.
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Value::Unsigned(unsigned) => Some(*unsigned as u128),
+            _ => match self.as_bignum()? {
+                (false, magnitude) => magnitude,
+                (true, _) => None,
+            },
+        }
+    }
+
+    /// Return the value of an integer, including unsigned (tag 2) and negative (tag 3) bignums, or
+    /// `None` for any other variant or for bignums outside the range of `i128`.
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Value::Unsigned(unsigned) => Some(*unsigned as i128),
+            Value::Negative(negative) => Some(*negative),
+            _ => match self.as_bignum()? {
+                (false, magnitude) => magnitude.and_then(|n| i128::try_from(n).ok()),
+                // Negative bignums hold -1 - n.
+                (true, magnitude) => magnitude
+                    .and_then(|n| i128::try_from(n).ok())
+                    .map(|n| -1 - n),
+            },
+        }
+    }
+
+    /// Return the value of an integer like [`Value::as_i128`], clamping values outside the range
+    /// of `i128` to its bounds instead of failing.  Returns `None` for any other variant.
+    pub fn as_i128_saturating(&self) -> Option<i128> {
+        match self.as_bignum() {
+            Some((false, _)) => Some(self.as_i128().unwrap_or(i128::MAX)),
+            Some((true, _)) => Some(self.as_i128().unwrap_or(i128::MIN)),
+            None => self.as_i128(),
+        }
+    }
+
+    /// Return the value of an integer like [`Value::as_i128`], clamping values outside the range
+    /// of `i64` to its bounds instead of failing.  Returns `None` for any other variant.
+    pub fn as_i64_saturating(&self) -> Option<i64> {
+        self.as_i128_saturating()
+            .map(|n| n.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+
+    /// Return the value of an integer like [`Value::as_i128`], clamping values outside the range
+    /// of `u64` to its bounds, so that all negative integers become 0, instead of failing.  Returns
+    /// `None` for any other variant.
+    pub fn as_u64_saturating(&self) -> Option<u64> {
+        self.as_i128_saturating()
+            .map(|n| n.clamp(0, u64::MAX as i128) as u64)
+    }
+
+    /// Return whether a bignum is negative along with its magnitude, which is `None` if it does not
+    /// fit in 128 bits, or `None` for any other variant.
+    fn as_bignum(&self) -> Option<(bool, Option<u128>)> {
+        let (tag, inner_value) = self.as_tag()?;
+        let negative = match tag {
+            Constants::TAG_UNSIGNED_BIGNUM => false,
+            Constants::TAG_NEGATIVE_BIGNUM => true,
+            _ => return None,
+        };
+        let bytes = inner_value.as_bytes()?;
+        let significant_bytes = &bytes[bytes.iter().take_while(|b| **b == 0).count()..];
+        let magnitude = if significant_bytes.len() <= 16 {
+            Some(
+                significant_bytes
+                    .iter()
+                    .fold(0u128, |n, byte| (n << 8) | *byte as u128),
+            )
+        } else {
+            None
+        };
+        Some((negative, magnitude))
+    }
+
+    /// Return the contents of a byte string, or `None` for any other variant.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::ByteString(byte_string) => Some(byte_string),
+            _ => None,
+        }
+    }
+
+    /// Return the contents of a text string, or `None` for any other variant.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::TextString(text_string) => Some(text_string),
+            _ => None,
+        }
+    }
+
+    /// Return the elements of an array, or `None` for any other variant.
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Return the key-value pairs of a map, or `None` for any other variant.
+    pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Return the tag number and inner value of a tagged value, or `None` for any other variant.
+    pub fn as_tag(&self) -> Option<(u64, &Value)> {
+        match self {
+            Value::Tag(tag, inner_value) => Some((*tag, inner_value)),
+            _ => None,
+        }
+    }
+
+    /// Return the value of a boolean, or `None` for any other variant.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Simple(SimpleValue::FalseValue) => Some(false),
+            Value::Simple(SimpleValue::TrueValue) => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Return the value of a float, or `None` for any other variant.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    /// Consume a byte string and return its contents, or `None` for any other variant.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            Value::ByteString(byte_string) => Some(byte_string),
+            _ => None,
+        }
+    }
+
+    /// Consume a text string and return its contents, or `None` for any other variant.
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            Value::TextString(text_string) => Some(text_string),
+            _ => None,
+        }
+    }
+
+    /// Consume an array and return its elements, or `None` for any other variant.
+    pub fn into_array(self) -> Option<Vec<Value>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Consume a map and return its key-value pairs, or `None` for any other variant.
+    pub fn into_map(self) -> Option<Vec<(Value, Value)>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Return the value associated with the given key in a map, or `None` if `self` is not a map
+    /// or the key is absent.
+    ///
+    /// As maps are stored as a vector of pairs, the lookup is linear in the size of the map.
+    pub fn get<K: Into<Value>>(&self, key: K) -> Option<&Value> {
+        let key = key.into();
+        self.as_map()?
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Return a mutable reference to the value associated with the given key in a map, or `None`
+    /// if `self` is not a map or the key is absent.
+    ///
+    /// As maps are stored as a vector of pairs, the lookup is linear in the size of the map.
+    pub fn get_mut<K: Into<Value>>(&mut self, key: K) -> Option<&mut Value> {
+        let key = key.into();
+        match self {
+            Value::Map(map) => map.iter_mut().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Return the value found by following the given keys through nested maps, or `None` if any
+    /// step fails.  An empty path returns `self`.
+    pub fn get_path<K: Clone + Into<Value>>(&self, path: &[K]) -> Option<&Value> {
+        path.iter()
+            .try_fold(self, |value, key| value.get(key.clone()))
+    }
+
+    /// Return the value found by following a JSON Pointer (RFC 6901) such as `"/users/0/name"`,
+    /// or `None` if any step fails.  Each `/`-prefixed segment, with `~1` and `~0` unescaped to
+    /// `/` and `~`, indexes an array if it is a decimal integer without leading zeros, or selects
+    /// the entry of a map with that text string key.  Entries with other keys cannot be
+    /// addressed, and tagged values are not looked through.  An empty path returns `self`.
+    pub fn pointer(&self, path: &str) -> Option<&Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.strip_prefix('/')?
+            .split('/')
+            .try_fold(self, |value, segment| match value {
+                Value::Array(array) => {
+                    if segment.len() > 1 && segment.starts_with('0')
+                        || !segment.bytes().all(|b| b.is_ascii_digit())
+                    {
+                        return None;
+                    }
+                    array.get(segment.parse::<usize>().ok()?)
+                }
+                Value::Map(map) => {
+                    let key = if segment.contains('~') {
+                        Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+                    } else {
+                        Cow::Borrowed(segment)
+                    };
+                    map.iter()
+                        .find(|(k, _)| k.as_text() == Some(&*key))
+                        .map(|(_, v)| v)
+                }
+                _ => None,
+            })
+    }
+
+    /// Build the single-entry map `{name: payload}` representing an enum variant, as derived
+    /// encodings of externally tagged enums do.
+    pub fn enum_variant<P: Into<Value>>(name: &str, payload: P) -> Value {
+        Value::Map(vec![(Value::TextString(name.into()), payload.into())])
+    }
+
+    /// Return the name and payload of an enum variant represented as a single-entry map with a
+    /// text string key, as built by [`Value::enum_variant`], or `None` if `self` is not such a map.
+    pub fn as_enum_variant(&self) -> Option<(&str, &Value)> {
+        match self.as_map()? {
+            [(Value::TextString(name), payload)] => Some((name, payload)),
+            _ => None,
+        }
+    }
+
+    /// Compare with `other` as `==` does, except that floats only need to be within `epsilon` of
+    /// each other, for tests where floats went through narrowing or arithmetic.  Containers are
+    /// compared element by element, map entries in the order they are stored in, and nested
+    /// values are visited without recursion.
+    ///
+    /// For convenience, NaN is considered equal to NaN whatever its payload, unlike with `==`.
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        let mut pending = vec![(self, other)];
+        while let Some(pair) = pending.pop() {
+            match pair {
+                (Value::Float(a), Value::Float(b)) => {
+                    let close = a == b || (a - b <= epsilon && b - a <= epsilon);
+                    if !(close || a.is_nan() && b.is_nan()) {
+                        return false;
+                    }
+                }
+                (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+                    pending.extend(a.iter().zip(b))
+                }
+                (Value::Map(a), Value::Map(b)) if a.len() == b.len() => {
+                    for ((ka, va), (kb, vb)) in a.iter().zip(b) {
+                        pending.push((ka, kb));
+                        pending.push((va, vb));
+                    }
+                }
+                (Value::Tag(ta, a), Value::Tag(tb, b)) if ta == tb => pending.push((a, b)),
+                (Value::Array(_), _)
+                | (Value::Map(_), _)
+                | (Value::Tag(_, _), _)
+                | (Value::Float(_), _) => return false,
+                (a, b) => {
+                    if a != b {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Replace the [`Value`] with the given one, returning the previous value.
+    pub fn replace(&mut self, value: Value) -> Value {
+        core::mem::replace(self, value)
+    }
+
+    /// Take the [`Value`] out, leaving null in its place.
+    pub fn take(&mut self) -> Value {
+        self.replace(Value::Simple(SimpleValue::NullValue))
+    }
+
+    /// Encode the [`Value`] and wrap the encoding in a byte string, as for CDDL's `bstr .cbor`
+    /// payloads in COSE and CWT.  The encoding is canonical like that of
+    /// [`write`](crate::writer::write), with map entries sorted whatever their order in the
+    /// value, so the wrapped bytes are fit for signing.
+    pub fn into_embedded_cbor(self) -> Result<Value, EncoderError> {
+        let mut encoded_cbor = Vec::new();
+        crate::writer::write(self, &mut encoded_cbor)?;
+        Ok(Value::ByteString(encoded_cbor))
+    }
+
+    /// Encode the [`Value`] and wrap the encoding in a byte string tagged as encoded CBOR (tag 24),
+    /// as in RFC 8949 §3.4.5.1.
+    pub fn tagged_embedded_cbor(self) -> Result<Value, EncoderError> {
+        Ok(Value::Tag(
+            Constants::TAG_ENCODED_CBOR,
+            Box::new(self.into_embedded_cbor()?),
+        ))
+    }
+
+    /// Wrap the [`Value`] in a self-described CBOR tag (tag 55799), as in RFC 8949 §3.4.6, so that
+    /// its encoding starts with the magic bytes `0xD9D9F7` identifying CBOR data.
+    pub fn self_describing(self) -> Value {
+        Value::Tag(Constants::TAG_SELF_DESCRIBED_CBOR, Box::new(self))
+    }
+
+    /// Decode the [`Value`] embedded in a byte string, which may be tagged as encoded CBOR (tag
+    /// 24).  Other values fail with [`DecoderError::UnsupportedMajorType`].
+    pub fn from_embedded_cbor(&self) -> Result<Value, DecoderError> {
+        match self {
+            Value::ByteString(encoded_cbor) => crate::reader::read(encoded_cbor),
+            Value::Tag(Constants::TAG_ENCODED_CBOR, inner_value) => match &**inner_value {
+                Value::ByteString(encoded_cbor) => crate::reader::read(encoded_cbor),
+                _ => Err(DecoderError::InvalidTaggedValue),
+            },
+            _ => Err(DecoderError::UnsupportedMajorType),
+        }
+    }
+}
+
+impl Value {
+    /// Create a duration (tag 1002) as in RFC 9581, i.e. a map holding the whole seconds under key
+    /// 1 and, unless zero, the fractional part in nanoseconds under key -9.
+    pub fn from_duration(duration: Duration) -> Value {
+        let mut map = vec![(Value::Unsigned(1), Value::Unsigned(duration.as_secs()))];
+        if duration.subsec_nanos() != 0 {
+            map.push((
+                Value::Negative(-9),
+                Value::Unsigned(duration.subsec_nanos().into()),
+            ));
+        }
+        Value::Tag(Constants::TAG_DURATION, Box::new(Value::Map(map)))
+    }
+
+    /// Return the duration (tag 1002) held by the [`Value`], as created by
+    /// [`Value::from_duration`].  The whole seconds must be an unsigned integer under key 1, and the
+    /// fractional part, if any, an integer of milliseconds, microseconds or nanoseconds under key
+    /// -3, -6 or -9 respectively.  Other content, such as negative or floating-point seconds, is
+    /// not supported.
+    pub fn as_duration(&self) -> Option<Duration> {
+        let map = match self {
+            Value::Tag(Constants::TAG_DURATION, inner_value) => inner_value.as_map()?,
+            _ => return None,
+        };
+        let (seconds, fraction) = match map {
+            [(Value::Unsigned(1), seconds)] => (seconds, None),
+            [(Value::Unsigned(1), seconds), (Value::Negative(scale), fraction)] => {
+                (seconds, Some((*scale, fraction)))
+            }
+            _ => return None,
+        };
+        let nanos = match fraction {
+            None => 0,
+            Some((scale, fraction)) => {
+                let (units_per_second, nanos_per_unit) = match scale {
+                    -3 => (1_000, 1_000_000),
+                    -6 => (1_000_000, 1_000),
+                    -9 => (1_000_000_000, 1),
+                    _ => return None,
+                };
+                match fraction.as_unsigned()? {
+                    units if units < units_per_second => units as u32 * nanos_per_unit,
+                    _ => return None,
+                }
+            }
+        };
+        Some(Duration::new(seconds.as_unsigned()?, nanos))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Value {
+    /// Create an IPv4 (tag 52) or IPv6 (tag 54) address as in RFC 9164, i.e. a byte string of the
+    /// address in network byte order.
+    pub fn from_ip(addr: IpAddr) -> Value {
+        match addr {
+            IpAddr::V4(addr) => Value::Tag(
+                Constants::TAG_IPV4,
+                Box::new(Value::ByteString(addr.octets().to_vec())),
+            ),
+            IpAddr::V6(addr) => Value::Tag(
+                Constants::TAG_IPV6,
+                Box::new(Value::ByteString(addr.octets().to_vec())),
+            ),
+        }
+    }
+
+    /// Return the IPv4 (tag 52) or IPv6 (tag 54) address held by the [`Value`], as created by
+    /// [`Value::from_ip`].  The prefix and interface formats of RFC 9164 are not addresses, and
+    /// return `None` like any other value.
+    pub fn as_ip(&self) -> Option<IpAddr> {
+        match self {
+            Value::Tag(Constants::TAG_IPV4, inner_value) => {
+                let octets = <[u8; 4]>::try_from(inner_value.as_bytes()?).ok()?;
+                Some(IpAddr::from(octets))
+            }
+            Value::Tag(Constants::TAG_IPV6, inner_value) => {
+                let octets = <[u8; 16]>::try_from(inner_value.as_bytes()?).ok()?;
+                Some(IpAddr::from(octets))
+            }
+            _ => None,
         }
     }
 }
 
+/// Return the big-endian representation of a bignum without leading zero bytes.
+fn bignum_bytes(n: u128) -> Vec<u8> {
+    n.to_be_bytes()[n.leading_zeros() as usize / 8..].to_vec()
+}
+
+/// Return the size of an item header carrying the given argument.
+fn header_len(size: u64) -> usize {
+    match size {
+        0..=23 => 1,
+        24..=0xFF => 2,
+        0x100..=0xFFFF => 3,
+        0x10000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
+/// Values are ordered like their canonical encodings in bytewise lexicographic order, which is the
+/// order in which the writer sorts map keys by default.  Values are compared directly rather than
+/// by encoding them.  Map entries are compared in the order they are stored in, so this holds for
+/// maps whose entries are in canonical order, as for decoded maps or after
+/// [`Value::canonicalize`]; equality is likewise sensitive to the order of entries.
 impl Ord for Value {
     fn cmp(&self, other: &Value) -> Ordering {
         use super::values::Value::{
-            Array, ByteString, Map, Negative, Simple, Tag, TextString, Unsigned,
+            Array, ByteString, Float, Map, Negative, Simple, Tag, TextString, Unsigned,
         };
         let self_type_value = self.type_label();
         let other_type_value = other.type_label();
@@ -150,6 +1215,13 @@ impl Ord for Value {
             }
             (Tag(t1, v1), Tag(t2, v2)) => t1.cmp(t2).then(v1.cmp(v2)),
             (Simple(s1), Simple(s2)) => s1.cmp(s2),
+            // Simple values always have a shorter encoding than floats.
+            (Simple(_), Float(_)) => Ordering::Less,
+            (Float(_), Simple(_)) => Ordering::Greater,
+            (Float(f1), Float(f2)) => {
+                // Floats are ordered by their canonical (shortest) encoding.
+                shortest_float_bits(*f1).cmp(&shortest_float_bits(*f2))
+            }
             (_, _) => {
                 // The case of different major types is caught above.
                 unreachable!();
@@ -172,17 +1244,82 @@ impl PartialEq for Value {
     }
 }
 
+/// Hashing is consistent with equality, so floats are hashed by their canonical encoding.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_label().hash(state);
+        match self {
+            Value::Unsigned(unsigned) => unsigned.hash(state),
+            Value::Negative(negative) => negative.hash(state),
+            Value::ByteString(byte_string) => byte_string.hash(state),
+            Value::TextString(text_string) => text_string.hash(state),
+            Value::Array(array) => array.hash(state),
+            Value::Map(map) => map.hash(state),
+            Value::Tag(tag, inner_value) => {
+                tag.hash(state);
+                inner_value.hash(state);
+            }
+            Value::Simple(simple_value) => simple_value.hash(state),
+            Value::Float(float) => shortest_float_bits(*float).hash(state),
+        }
+    }
+}
+
 impl SimpleValue {
-    /// Create a simple value from its encoded value.
+    /// Create a simple value from its encoded value, returning `None` for the reserved values
+    /// 24..=31 and for values that do not fit in a byte.
     pub fn from_integer(int: u64) -> Option<SimpleValue> {
         match int {
             20 => Some(SimpleValue::FalseValue),
             21 => Some(SimpleValue::TrueValue),
             22 => Some(SimpleValue::NullValue),
             23 => Some(SimpleValue::Undefined),
+            0..=19 | 32..=255 => Some(SimpleValue::Unassigned(int as u8)),
             _ => None,
         }
     }
+
+    /// Return the encoded value of the simple value.
+    pub fn to_integer(&self) -> u8 {
+        match self {
+            SimpleValue::FalseValue => 20,
+            SimpleValue::TrueValue => 21,
+            SimpleValue::NullValue => 22,
+            SimpleValue::Undefined => 23,
+            SimpleValue::Unassigned(int) => *int,
+        }
+    }
+}
+
+/// Simple values are ordered by their encoded value, which is the order of their encodings.
+impl Ord for SimpleValue {
+    fn cmp(&self, other: &SimpleValue) -> Ordering {
+        self.to_integer().cmp(&other.to_integer())
+    }
+}
+
+impl PartialOrd for SimpleValue {
+    fn partial_cmp(&self, other: &SimpleValue) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        ValueRef::Value(value)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ValueRef<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        ValueRef::ByteString(bytes)
+    }
+}
+
+impl<'a> From<&'a str> for ValueRef<'a> {
+    fn from(text: &'a str) -> Self {
+        ValueRef::TextString(text)
+    }
 }
 
 impl From<u64> for Value {
@@ -197,54 +1334,307 @@ impl From<i64> for Value {
     }
 }
 
+/// Integers that do not fit in 64 bits become unsigned bignums, see [`Value::from_u128`].
+impl From<u128> for Value {
+    fn from(unsigned: u128) -> Self {
+        Value::from_u128(unsigned)
+    }
+}
+
+/// Integers that do not fit in 64 bits become bignums, see [`Value::from_i128`].
+impl From<i128> for Value {
+    fn from(i: i128) -> Self {
+        Value::from_i128(i)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(unsigned: u32) -> Self {
+        Value::Unsigned(unsigned as u64)
+    }
+}
+
+impl From<u16> for Value {
+    fn from(unsigned: u16) -> Self {
+        Value::Unsigned(unsigned as u64)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(unsigned: u8) -> Self {
+        Value::Unsigned(unsigned as u64)
+    }
+}
+
 impl From<i32> for Value {
     fn from(i: i32) -> Self {
         Value::integer(i as i64)
     }
 }
 
+impl From<i16> for Value {
+    fn from(i: i16) -> Self {
+        Value::integer(i as i64)
+    }
+}
+
+impl From<i8> for Value {
+    fn from(i: i8) -> Self {
+        Value::integer(i as i64)
+    }
+}
+
 impl From<Vec<u8>> for Value {
     fn from(bytes: Vec<u8>) -> Self {
         Value::ByteString(bytes)
     }
 }
 
-impl From<&[u8]> for Value {
-    fn from(bytes: &[u8]) -> Self {
-        Value::ByteString(bytes.to_vec())
-    }
-}
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Value::ByteString(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Value {
+    fn from(bytes: [u8; N]) -> Self {
+        Value::ByteString(bytes.to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::TextString(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::TextString(text.to_string())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(array: Vec<Value>) -> Self {
+        Value::Array(array)
+    }
+}
+
+impl From<Vec<(Value, Value)>> for Value {
+    fn from(map: Vec<(Value, Value)>) -> Self {
+        Value::Map(map)
+    }
+}
+
+/// Entries are kept in the order of the source map, which the writer sorts canonically anyway.
+impl<K: Into<Value>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Value::Map(map.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// Entries are sorted by key, so that the resulting [`Value`] does not depend on the iteration
+/// order of the source map.
+#[cfg(feature = "std")]
+impl<K: Into<Value>, V: Into<Value>, S> From<std::collections::HashMap<K, V, S>> for Value {
+    fn from(map: std::collections::HashMap<K, V, S>) -> Self {
+        let mut entries: Vec<(Value, Value)> =
+            map.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Value::Map(entries)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<S> From<indexmap::IndexMap<Value, Value, S>> for Value {
+    fn from(map: indexmap::IndexMap<Value, Value, S>) -> Self {
+        Value::Map(map.into_iter().collect())
+    }
+}
+
+/// Index the entries of a map for constant-time lookup, keeping them in order.  Converting back
+/// with `From` keeps that order, which the writer sorts canonically anyway.
+#[cfg(feature = "indexmap")]
+impl<S: core::hash::BuildHasher + Default> TryFrom<Value> for indexmap::IndexMap<Value, Value, S> {
+    type Error = ValueConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let map = value
+            .into_map()
+            .ok_or(ValueConversionError::UnexpectedType)?;
+        let len = map.len();
+        let index_map: Self = map.into_iter().collect();
+        if index_map.len() != len {
+            return Err(ValueConversionError::DuplicateMapKey);
+        }
+        Ok(index_map)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::bool_value(b)
+    }
+}
+
+/// Possible errors from converting a [`Value`] into a Rust type.
+#[derive(Debug, PartialEq)]
+pub enum ValueConversionError {
+    /// The value is not of the variant the target type is converted from.
+    UnexpectedType,
+    /// The value is an integer outside the range of the target type.
+    OutOfRange,
+    /// The value is a map with duplicate keys, which the target type cannot hold.
+    DuplicateMapKey,
+    /// The value is an array or byte string whose length differs from that of the target type.
+    LengthMismatch,
+}
+
+impl fmt::Display for ValueConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueConversionError::UnexpectedType => f.write_str("unexpected value type"),
+            ValueConversionError::OutOfRange => f.write_str("integer out of range"),
+            ValueConversionError::DuplicateMapKey => f.write_str("duplicate map key"),
+            ValueConversionError::LengthMismatch => f.write_str("length mismatch"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValueConversionError {}
+
+/// Converting a [`Value`] into itself never fails, which lets tuples hold values as is.
+impl From<core::convert::Infallible> for ValueConversionError {
+    fn from(infallible: core::convert::Infallible) -> Self {
+        match infallible {}
+    }
+}
+
+macro_rules! impl_try_from_value_for_int {
+    ($($name:ty),*) => {
+        $(
+            impl TryFrom<Value> for $name {
+                type Error = ValueConversionError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::Unsigned(unsigned) => {
+                            <$name>::try_from(unsigned).map_err(|_| ValueConversionError::OutOfRange)
+                        }
+                        Value::Negative(negative) => {
+                            <$name>::try_from(negative).map_err(|_| ValueConversionError::OutOfRange)
+                        }
+                        _ => Err(ValueConversionError::UnexpectedType),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+macro_rules! impl_try_from_value_for_int128 {
+    ($($name:ty => $as_int:ident),*) => {
+        $(
+            /// Bignums (tags 2 and 3) are accepted as well, see [`Value::as_u128`] and
+            /// [`Value::as_i128`].
+            impl TryFrom<Value> for $name {
+                type Error = ValueConversionError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    match value.$as_int() {
+                        Some(int) => Ok(int),
+                        None if value.as_i128_saturating().is_some() => {
+                            Err(ValueConversionError::OutOfRange)
+                        }
+                        None => Err(ValueConversionError::UnexpectedType),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value_for_int128!(u128 => as_u128, i128 => as_i128);
 
-impl From<String> for Value {
-    fn from(text: String) -> Self {
-        Value::TextString(text)
+impl TryFrom<Value> for String {
+    type Error = ValueConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .into_text()
+            .ok_or(ValueConversionError::UnexpectedType)
     }
 }
 
-impl From<&str> for Value {
-    fn from(text: &str) -> Self {
-        Value::TextString(text.to_string())
+impl TryFrom<Value> for Vec<u8> {
+    type Error = ValueConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .into_bytes()
+            .ok_or(ValueConversionError::UnexpectedType)
     }
 }
 
-impl From<Vec<Value>> for Value {
-    fn from(array: Vec<Value>) -> Self {
-        Value::Array(array)
+/// Byte strings of any other length fail with [`ValueConversionError::LengthMismatch`].
+impl<const N: usize> TryFrom<Value> for [u8; N] {
+    type Error = ValueConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let bytes = value
+            .into_bytes()
+            .ok_or(ValueConversionError::UnexpectedType)?;
+        <[u8; N]>::try_from(bytes).map_err(|_| ValueConversionError::LengthMismatch)
     }
 }
 
-impl From<Vec<(Value, Value)>> for Value {
-    fn from(map: Vec<(Value, Value)>) -> Self {
-        Value::Map(map)
+impl TryFrom<Value> for bool {
+    type Error = ValueConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_bool().ok_or(ValueConversionError::UnexpectedType)
     }
 }
 
-impl From<bool> for Value {
-    fn from(b: bool) -> Self {
-        Value::bool_value(b)
-    }
+/// Convert tuples to and from arrays of the same length, as for structures represented as definite
+/// arrays.
+macro_rules! impl_conversions_for_tuple {
+    ($len:expr; $($name:ident $element:ident),+) => {
+        impl<$($name: Into<Value>),+> From<($($name,)+)> for Value {
+            fn from(($($element,)+): ($($name,)+)) -> Self {
+                Value::Array(vec![$($element.into()),+])
+            }
+        }
+
+        impl<$($name),+> TryFrom<Value> for ($($name,)+)
+        where
+            $($name: TryFrom<Value>, ValueConversionError: From<<$name as TryFrom<Value>>::Error>,)+
+        {
+            type Error = ValueConversionError;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                let array = value
+                    .into_array()
+                    .ok_or(ValueConversionError::UnexpectedType)?;
+                if array.len() != $len {
+                    return Err(ValueConversionError::LengthMismatch);
+                }
+                let mut elements = array.into_iter();
+                Ok(($(<$name>::try_from(elements.next().unwrap())?,)+))
+            }
+        }
+    };
 }
 
+impl_conversions_for_tuple!(2; A a, B b);
+impl_conversions_for_tuple!(3; A a, B b, C c);
+impl_conversions_for_tuple!(4; A a, B b, C c, D d);
+impl_conversions_for_tuple!(5; A a, B b, C c, D d, E e);
+impl_conversions_for_tuple!(6; A a, B b, C c, D d, E e, F f);
+
 /// Trait that indicates that a type can be converted to a CBOR [`Value`].
 pub trait IntoCborValue {
     /// Convert `self` into a CBOR [`Value`], consuming it along the way.
@@ -287,9 +1677,1148 @@ where
 #[cfg(test)]
 mod test {
     use alloc::vec;
+    use core::hash::{BuildHasher, BuildHasherDefault};
 
     use super::*;
-    use crate::{cbor_array, cbor_bool, cbor_bytes, cbor_int, cbor_map, cbor_tagged, cbor_text};
+    use crate::{
+        cbor_array, cbor_bool, cbor_bytes, cbor_int, cbor_map, cbor_null, cbor_simple, cbor_tagged,
+        cbor_text,
+    };
+
+    #[test]
+    fn test_accessors() {
+        assert_eq!(cbor_int!(5).as_unsigned(), Some(5));
+        assert_eq!(cbor_int!(-5).as_unsigned(), None);
+        assert_eq!(cbor_int!(-5).as_negative(), Some(-5));
+        assert_eq!(cbor_bytes!(vec![0x01]).as_bytes(), Some(&[0x01][..]));
+        assert_eq!(cbor_text!("a").as_bytes(), None);
+        assert_eq!(cbor_text!("a").as_text(), Some("a"));
+        assert_eq!(
+            cbor_array![1, 2].as_array(),
+            Some(&[cbor_int!(1), cbor_int!(2)][..])
+        );
+        assert_eq!(
+            cbor_map! {1 => "a"}.as_map(),
+            Some(&[(cbor_int!(1), cbor_text!("a"))][..])
+        );
+        assert_eq!(cbor_array![].as_map(), None);
+        assert_eq!(
+            cbor_tagged!(1, cbor_int!(2)).as_tag(),
+            Some((1, &cbor_int!(2)))
+        );
+        assert_eq!(cbor_bool!(true).as_bool(), Some(true));
+        assert_eq!(cbor_int!(0).as_bool(), None);
+        assert_eq!(Value::Float(1.5).as_float(), Some(1.5));
+
+        assert_eq!(cbor_bytes!(vec![0x01]).into_bytes(), Some(vec![0x01]));
+        assert_eq!(cbor_text!("a").into_text(), Some(String::from("a")));
+        assert_eq!(cbor_int!(1).into_text(), None);
+        assert_eq!(cbor_array![1].into_array(), Some(vec![cbor_int!(1)]));
+        assert_eq!(
+            cbor_map! {1 => "a"}.into_map(),
+            Some(vec![(cbor_int!(1), cbor_text!("a"))])
+        );
+    }
+
+    #[test]
+    fn test_get() {
+        let mut value = cbor_map! {
+            1 => "a",
+            "b" => cbor_map! {"c" => cbor_map! {"d" => 2}},
+        };
+        assert_eq!(value.get(1), Some(&cbor_text!("a")));
+        assert_eq!(value.get("a"), None);
+        assert_eq!(cbor_array![1].get(0), None);
+        assert_eq!(value.get_path(&["b", "c", "d"]), Some(&cbor_int!(2)));
+        assert_eq!(value.get_path(&["b", "x"]), None);
+        assert_eq!(value.get_path::<&str>(&[]), Some(&value));
+
+        *value.get_mut(1).unwrap() = cbor_int!(3);
+        assert_eq!(value.get(1), Some(&cbor_int!(3)));
+        assert_eq!(value.get_mut(2), None);
+    }
+
+    #[test]
+    fn test_pointer() {
+        let value = cbor_map! {
+            "users" => cbor_array![cbor_map! {"name" => "a"}, cbor_map! {"name" => "b"}],
+            "a/b" => 1,
+            "m~n" => 2,
+            "0" => 3,
+            "" => 4,
+            1 => 5,
+        };
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/users/0/name"), Some(&cbor_text!("a")));
+        assert_eq!(value.pointer("/users/1/name"), Some(&cbor_text!("b")));
+        assert_eq!(value.pointer("/users/1"), Some(&cbor_map! {"name" => "b"}));
+        assert_eq!(value.pointer("/a~1b"), Some(&cbor_int!(1)));
+        assert_eq!(value.pointer("/m~0n"), Some(&cbor_int!(2)));
+        // Integer segments select text keys of maps.
+        assert_eq!(value.pointer("/0"), Some(&cbor_int!(3)));
+        assert_eq!(value.pointer("/"), Some(&cbor_int!(4)));
+
+        // Missing keys, out-of-bounds and malformed indices, and non-text keys.
+        assert_eq!(value.pointer("/missing"), None);
+        assert_eq!(value.pointer("/users/0/missing"), None);
+        assert_eq!(value.pointer("/users/2"), None);
+        assert_eq!(value.pointer("/users/00"), None);
+        assert_eq!(value.pointer("/users/-1"), None);
+        assert_eq!(value.pointer("/users/+1"), None);
+        assert_eq!(value.pointer("/users/name"), None);
+        assert_eq!(value.pointer("/users/99999999999999999999999"), None);
+        assert_eq!(value.pointer("/1"), None);
+        assert_eq!(value.pointer("/a~1b/c"), None);
+        assert_eq!(value.pointer("users"), None);
+    }
+
+    #[test]
+    fn test_enum_variant() {
+        let value = Value::enum_variant("Transfer", cbor_map! {"amount" => 10});
+        assert_eq!(value, cbor_map! {"Transfer" => cbor_map! {"amount" => 10}});
+        assert_eq!(
+            value.as_enum_variant(),
+            Some(("Transfer", &cbor_map! {"amount" => 10}))
+        );
+        assert_eq!(
+            Value::enum_variant("Unit", cbor_null!()).as_enum_variant(),
+            Some(("Unit", &cbor_null!()))
+        );
+
+        assert_eq!(cbor_map! {}.as_enum_variant(), None);
+        assert_eq!(cbor_map! {"A" => 1, "B" => 2}.as_enum_variant(), None);
+        assert_eq!(cbor_map! {1 => 2}.as_enum_variant(), None);
+        assert_eq!(cbor_array!["A", 1].as_enum_variant(), None);
+        assert_eq!(cbor_text!("A").as_enum_variant(), None);
+    }
+
+    #[test]
+    fn test_replace_take() {
+        let mut value = cbor_map! {"a" => cbor_map! {"b" => 1}, "c" => cbor_array![2]};
+        let old = value
+            .get_mut("a")
+            .unwrap()
+            .get_mut("b")
+            .unwrap()
+            .replace(cbor_text!("x"));
+        assert_eq!(old, cbor_int!(1));
+        assert_eq!(value.get_mut("c").unwrap().take(), cbor_array![2]);
+        assert_eq!(
+            value,
+            cbor_map! {"a" => cbor_map! {"b" => "x"}, "c" => cbor_null!()}
+        );
+    }
+
+    #[test]
+    fn test_embedded_cbor() {
+        let value = cbor_map! {1 => cbor_array![-7], 4 => cbor_bytes!(vec![0x01])};
+        let embedded = value.clone().into_embedded_cbor().unwrap();
+        assert_eq!(
+            embedded,
+            cbor_bytes!(vec![0xa2, 0x01, 0x81, 0x26, 0x04, 0x41, 0x01])
+        );
+        assert_eq!(embedded.from_embedded_cbor(), Ok(value.clone()));
+        let tagged = value.clone().tagged_embedded_cbor().unwrap();
+        assert_eq!(tagged, cbor_tagged!(24, embedded));
+        assert_eq!(tagged.from_embedded_cbor(), Ok(value));
+
+        // Signed payloads rely on the wrapped bytes being canonical.
+        let unsorted = cbor_map! {
+            "b" => cbor_map! {-1 => 0, 10 => 1, 1 => 2},
+            3 => cbor_array![cbor_map! {"bb" => 0, "a" => 1}],
+        };
+        let mut canonical_cbor = Vec::new();
+        crate::writer::write(unsorted.clone(), &mut canonical_cbor).unwrap();
+        assert!(crate::reader::is_canonical(&canonical_cbor));
+        assert_eq!(
+            unsorted.into_embedded_cbor(),
+            Ok(cbor_bytes!(canonical_cbor))
+        );
+
+        assert_eq!(
+            cbor_map! {0 => 0, 0 => 1}.into_embedded_cbor(),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+        assert_eq!(
+            cbor_bytes!(vec![0x18, 0x01]).from_embedded_cbor(),
+            Err(DecoderError::NonMinimalCborEncoding)
+        );
+        assert_eq!(
+            cbor_bytes!(vec![0x01, 0x02]).from_embedded_cbor(),
+            Err(DecoderError::ExtraneousData)
+        );
+        assert_eq!(
+            cbor_text!("a").from_embedded_cbor(),
+            Err(DecoderError::UnsupportedMajorType)
+        );
+        assert_eq!(
+            cbor_tagged!(24, cbor_text!("a")).from_embedded_cbor(),
+            Err(DecoderError::InvalidTaggedValue)
+        );
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(u8::try_from(cbor_int!(255)), Ok(255));
+        assert_eq!(
+            u8::try_from(cbor_int!(300)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            u32::try_from(cbor_int!(-1)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(i8::try_from(cbor_int!(-128)), Ok(-128));
+        assert_eq!(
+            i8::try_from(cbor_int!(-129)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(i64::try_from(cbor_int!(i64::MIN)), Ok(i64::MIN));
+        assert_eq!(
+            i64::try_from(Value::Negative(i64::MIN as i128 - 1)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            i64::try_from(Value::Unsigned(u64::MAX)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(u64::try_from(Value::Unsigned(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(
+            u64::try_from(cbor_text!("1")),
+            Err(ValueConversionError::UnexpectedType)
+        );
+
+        assert_eq!(String::try_from(cbor_text!("a")), Ok(String::from("a")));
+        assert_eq!(
+            String::try_from(cbor_bytes!(vec![0x61])),
+            Err(ValueConversionError::UnexpectedType)
+        );
+        assert_eq!(Vec::<u8>::try_from(cbor_bytes!(vec![0x61])), Ok(vec![0x61]));
+        let key = [0xA5u8; 32];
+        assert_eq!(Value::from(key), cbor_bytes!(vec![0xA5; 32]));
+        assert_eq!(<[u8; 32]>::try_from(Value::from(key)), Ok(key));
+        assert_eq!(<[u8; 0]>::try_from(cbor_bytes!(vec![])), Ok([]));
+        assert_eq!(
+            <[u8; 32]>::try_from(cbor_bytes!(vec![0xA5; 31])),
+            Err(ValueConversionError::LengthMismatch)
+        );
+        assert_eq!(
+            <[u8; 32]>::try_from(cbor_bytes!(vec![0xA5; 33])),
+            Err(ValueConversionError::LengthMismatch)
+        );
+        assert_eq!(
+            <[u8; 1]>::try_from(cbor_text!("a")),
+            Err(ValueConversionError::UnexpectedType)
+        );
+        assert_eq!(bool::try_from(cbor_bool!(false)), Ok(false));
+        assert_eq!(
+            bool::try_from(cbor_int!(1)),
+            Err(ValueConversionError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_tuple_conversions() {
+        let value = Value::from((1u8, "a", vec![0x01u8]));
+        assert_eq!(value, cbor_array![1, "a", cbor_bytes!(vec![0x01])]);
+        assert_eq!(
+            <(u8, String, Vec<u8>)>::try_from(value),
+            Ok((1, String::from("a"), vec![0x01]))
+        );
+        assert_eq!(
+            <(i64, Value)>::try_from(cbor_array![-1, cbor_map! {}]),
+            Ok((-1, cbor_map! {}))
+        );
+        assert_eq!(
+            Value::from((1u8, 2u8, 3u8, 4u8, 5u8, true)),
+            cbor_array![1, 2, 3, 4, 5, true]
+        );
+
+        assert_eq!(
+            <(u8, u8)>::try_from(cbor_array![1, 2, 3]),
+            Err(ValueConversionError::LengthMismatch)
+        );
+        assert_eq!(
+            <(u8, u8, u8)>::try_from(cbor_array![1, 2]),
+            Err(ValueConversionError::LengthMismatch)
+        );
+        assert_eq!(
+            <(u8, bool)>::try_from(cbor_array![1, 2]),
+            Err(ValueConversionError::UnexpectedType)
+        );
+        assert_eq!(
+            <(u8, u8)>::try_from(cbor_array![1, 256]),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            <(u8, u8)>::try_from(cbor_map! {1 => 2}),
+            Err(ValueConversionError::UnexpectedType)
+        );
+    }
+
+    // A simple deterministic hasher, as there is no default one without std.
+    #[derive(Default)]
+    struct FnvHasher(u64);
+
+    impl Hasher for FnvHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        use alloc::collections::BTreeSet;
+
+        let hash = |value: &Value| BuildHasherDefault::<FnvHasher>::default().hash_one(value);
+
+        let equal_pairs = vec![
+            (Value::Float(f64::NAN), Value::Float(-f64::NAN)),
+            (Value::Float(1.0), Value::Float(1.0)),
+            (
+                cbor_map! {1 => cbor_array!["a"]},
+                cbor_map! {1 => cbor_array!["a"]},
+            ),
+        ];
+        for (a, b) in equal_pairs {
+            assert_eq!(a, b);
+            assert_eq!(hash(&a), hash(&b));
+        }
+        let values = vec![
+            cbor_int!(0),
+            cbor_int!(-1),
+            cbor_bytes!(vec![]),
+            cbor_text!(""),
+            cbor_array![],
+            cbor_map! {},
+            cbor_tagged!(0, cbor_int!(0)),
+            cbor_bool!(false),
+            Value::Float(0.0),
+            Value::Float(-0.0),
+        ];
+        let hashes: BTreeSet<_> = values.iter().map(hash).collect();
+        assert_eq!(hashes.len(), values.len());
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_index_map() {
+        type IndexMap = indexmap::IndexMap<Value, Value, BuildHasherDefault<FnvHasher>>;
+        let value = cbor_map! {"b" => 1, "a" => cbor_array![2]};
+        let index_map = IndexMap::try_from(value.clone()).unwrap();
+        assert_eq!(index_map.get(&cbor_text!("a")), Some(&cbor_array![2]));
+        assert_eq!(
+            index_map.get_index(0),
+            Some((&cbor_text!("b"), &cbor_int!(1)))
+        );
+        assert_eq!(Value::from(index_map), value);
+
+        assert_eq!(
+            IndexMap::try_from(cbor_map! {1 => 0, 1 => 1}),
+            Err(ValueConversionError::DuplicateMapKey)
+        );
+        assert_eq!(
+            IndexMap::try_from(cbor_array![]),
+            Err(ValueConversionError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_contains_float() {
+        let values = vec![
+            Value::Float(0.0),
+            cbor_array![1, cbor_array![Value::Float(f64::NAN)]],
+            cbor_map! {"a" => 1, "b" => cbor_map! {"c" => Value::Float(1.5)}},
+            cbor_map! {Value::Float(1.5) => 1},
+            cbor_tagged!(1, Value::Float(1.0e9)),
+        ];
+        for value in values {
+            assert!(value.contains_float(), "{:?}", value);
+        }
+
+        let values = vec![
+            cbor_int!(1),
+            cbor_text!("1.5"),
+            cbor_array![1, cbor_array![-2, cbor_null!()]],
+            cbor_map! {"a" => cbor_tagged!(1, cbor_int!(1)), 2 => cbor_bytes!(vec![0xf9])},
+        ];
+        for value in values {
+            assert!(!value.contains_float(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let value_size = core::mem::size_of::<Value>();
+        assert_eq!(cbor_int!(1).heap_size(), 0);
+        assert_eq!(cbor_bytes!(vec![0; 10]).heap_size(), 10);
+        assert_eq!(cbor_text!("abc").heap_size(), 3);
+        assert_eq!(cbor_array![].heap_size(), 0);
+        assert_eq!(cbor_array![1, "ab"].heap_size(), 2 * value_size + 2);
+        assert_eq!(
+            Value::Map(vec![
+                (cbor_int!(1), cbor_text!("ab")),
+                (cbor_text!("c"), cbor_array![])
+            ])
+            .heap_size(),
+            2 * 2 * value_size + 3
+        );
+        assert_eq!(
+            cbor_tagged!(1, cbor_bytes!(vec![0; 4])).heap_size(),
+            value_size + 4
+        );
+
+        // Spare capacity is counted.
+        let mut byte_string = Vec::with_capacity(100);
+        byte_string.push(0);
+        assert_eq!(Value::ByteString(byte_string).heap_size(), 100);
+        let mut array = Vec::with_capacity(8);
+        array.push(cbor_int!(1));
+        assert_eq!(Value::Array(array).heap_size(), 8 * value_size);
+
+        let mut nested = cbor_int!(0);
+        for _ in 0..100_000 {
+            nested = cbor_tagged!(1, nested);
+        }
+        assert_eq!(nested.heap_size(), 100_000 * value_size);
+        while let Value::Tag(_, inner_value) = nested {
+            nested = *inner_value;
+        }
+    }
+
+    #[test]
+    fn test_self_describing() {
+        assert_eq!(
+            cbor_int!(1).self_describing(),
+            cbor_tagged!(55799, cbor_int!(1))
+        );
+        assert_eq!(cbor_int!(1).self_describing().strip_tags(), cbor_int!(1));
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        let value = cbor_tagged!(
+            1,
+            cbor_tagged!(
+                2,
+                cbor_array![
+                    cbor_tagged!(3, cbor_int!(4)),
+                    cbor_map! {cbor_tagged!(5, cbor_text!("a")) => cbor_tagged!(6, cbor_array![7])},
+                    8,
+                ]
+            )
+        );
+        assert_eq!(
+            value.strip_tags(),
+            cbor_array![4, cbor_map! {"a" => cbor_array![7]}, 8]
+        );
+        assert_eq!(cbor_map! {1 => 2}.strip_tags(), cbor_map! {1 => 2});
+
+        let mut nested = cbor_int!(0);
+        for i in 0..100_000 {
+            nested = if i % 2 == 0 {
+                cbor_tagged!(i, nested)
+            } else {
+                cbor_array![nested]
+            };
+        }
+        let mut stripped = nested.strip_tags();
+        for _ in 0..50_000 {
+            stripped = match stripped {
+                Value::Array(mut array) => array.pop().unwrap(),
+                _ => panic!("expected an array"),
+            };
+        }
+        assert_eq!(stripped, cbor_int!(0));
+    }
+
+    #[test]
+    fn test_integer_accessors() {
+        let bignum = |tag, bytes: Vec<u8>| cbor_tagged!(tag, cbor_bytes!(bytes));
+        let huge = vec![0x01; 17];
+        // (value, as_u128, as_i128, as_u64_saturating, as_i64_saturating)
+        let cases = vec![
+            (cbor_int!(5), Some(5), Some(5), Some(5), Some(5)),
+            (
+                Value::Unsigned(u64::MAX),
+                Some(u64::MAX as u128),
+                Some(u64::MAX as i128),
+                Some(u64::MAX),
+                Some(i64::MAX),
+            ),
+            (cbor_int!(-5), None, Some(-5), Some(0), Some(-5)),
+            (
+                Value::Negative(-(1i128 << 64)),
+                None,
+                Some(-(1i128 << 64)),
+                Some(0),
+                Some(i64::MIN),
+            ),
+            (
+                Value::from_u128(u128::MAX),
+                Some(u128::MAX),
+                None,
+                Some(u64::MAX),
+                Some(i64::MAX),
+            ),
+            (
+                Value::from_i128(i128::MIN),
+                None,
+                Some(i128::MIN),
+                Some(0),
+                Some(i64::MIN),
+            ),
+            // Bignums with leading zeros or small values are still recognized.
+            (
+                bignum(2, vec![0x00, 0x00, 0x07]),
+                Some(7),
+                Some(7),
+                Some(7),
+                Some(7),
+            ),
+            (bignum(3, vec![0x07]), None, Some(-8), Some(0), Some(-8)),
+            (
+                bignum(2, huge.clone()),
+                None,
+                None,
+                Some(u64::MAX),
+                Some(i64::MAX),
+            ),
+            (bignum(3, huge), None, None, Some(0), Some(i64::MIN)),
+        ];
+        for (value, u128_value, i128_value, u64_saturating, i64_saturating) in cases {
+            assert_eq!(value.as_u128(), u128_value, "{:?}", value);
+            assert_eq!(value.as_i128(), i128_value, "{:?}", value);
+            assert_eq!(value.as_u64_saturating(), u64_saturating, "{:?}", value);
+            assert_eq!(value.as_i64_saturating(), i64_saturating, "{:?}", value);
+        }
+        assert_eq!(
+            bignum(2, vec![0x01; 17]).as_i128_saturating(),
+            Some(i128::MAX)
+        );
+        assert_eq!(
+            Value::from_u128(u128::MAX).as_i128_saturating(),
+            Some(i128::MAX)
+        );
+
+        let non_integers = vec![
+            cbor_text!("1"),
+            Value::Float(1.0),
+            cbor_tagged!(2, cbor_int!(1)),
+            cbor_tagged!(1, cbor_int!(1)),
+        ];
+        for value in non_integers {
+            assert_eq!(value.as_u128(), None);
+            assert_eq!(value.as_i128(), None);
+            assert_eq!(value.as_i128_saturating(), None);
+            assert_eq!(value.as_u64_saturating(), None);
+            assert_eq!(value.as_i64_saturating(), None);
+        }
+    }
+
+    #[test]
+    fn test_unwrap_singleton() {
+        assert_eq!(cbor_array![1].unwrap_singleton(), cbor_int!(1));
+        assert_eq!(
+            cbor_array![cbor_array![1]].unwrap_singleton(),
+            cbor_array![1]
+        );
+        assert_eq!(cbor_array![1, 2].unwrap_singleton(), cbor_array![1, 2]);
+        assert_eq!(cbor_array![].unwrap_singleton(), cbor_array![]);
+        assert_eq!(cbor_map! {1 => 2}.unwrap_singleton(), cbor_map! {1 => 2});
+        assert_eq!(cbor_int!(1).unwrap_singleton(), cbor_int!(1));
+    }
+
+    #[test]
+    fn test_unwrap_singletons() {
+        let value = cbor_array![
+            cbor_array![cbor_array![1]],
+            cbor_map! {cbor_array!["a"] => cbor_array![cbor_array![2, 3]]},
+            cbor_tagged!(4, cbor_array![cbor_array![]]),
+        ];
+        assert_eq!(
+            value.unwrap_singletons(),
+            cbor_array![
+                1,
+                cbor_map! {"a" => cbor_array![2, 3]},
+                cbor_tagged!(4, cbor_array![]),
+            ]
+        );
+        assert_eq!(cbor_array![1, 2].unwrap_singletons(), cbor_array![1, 2]);
+
+        let mut nested = cbor_int!(0);
+        for _ in 0..100_000 {
+            nested = cbor_array![nested];
+        }
+        assert_eq!(nested.unwrap_singletons(), cbor_int!(0));
+    }
+
+    #[test]
+    fn test_fits_default_nesting() {
+        let mut value = cbor_int!(0);
+        for _ in 0..MAX_NESTING_DEPTH {
+            value = cbor_array![value];
+        }
+        assert!(value.fits_default_nesting());
+        let mut encoded_cbor = Vec::new();
+        assert!(crate::writer::write(value.clone(), &mut encoded_cbor).is_ok());
+
+        let value = cbor_tagged!(1, value);
+        assert!(!value.fits_default_nesting());
+        assert!(crate::writer::write(value, &mut encoded_cbor).is_err());
+    }
+
+    #[test]
+    fn test_retain() {
+        let is_public = |k: &Value, _: &Value| k != &cbor_text!("secret");
+        let mut value = cbor_map! {
+            "secret" => 1,
+            "public" => cbor_map! {"secret" => 2, "other" => 3},
+        };
+        value.retain(is_public);
+        assert_eq!(
+            value,
+            cbor_map! {"public" => cbor_map! {"secret" => 2, "other" => 3}}
+        );
+
+        let mut value = cbor_array![cbor_map! {"secret" => 1}];
+        value.retain(is_public);
+        assert_eq!(value, cbor_array![cbor_map! {"secret" => 1}]);
+
+        let mut value = cbor_map! {1 => "a", 2 => "b", 3 => "c"};
+        value.retain(|_, v| v != &cbor_text!("b"));
+        assert_eq!(value, cbor_map! {1 => "a", 3 => "c"});
+    }
+
+    #[test]
+    fn test_retain_recursive() {
+        let mut value = cbor_map! {
+            "secret" => 1,
+            "public" => cbor_map! {"secret" => 2, "other" => 3},
+            "list" => cbor_array![cbor_tagged!(7, cbor_map! {"secret" => 4}), 5],
+            cbor_map! {"secret" => 6} => 7,
+        };
+        value.retain_recursive(|k, _| k != &cbor_text!("secret"));
+        assert_eq!(
+            value,
+            cbor_map! {
+                "public" => cbor_map! {"other" => 3},
+                "list" => cbor_array![cbor_tagged!(7, cbor_map! {}), 5],
+                cbor_map! {"secret" => 6} => 7,
+            }
+        );
+
+        let mut value = cbor_text!("secret");
+        value.retain_recursive(|_, _| false);
+        assert_eq!(value, cbor_text!("secret"));
+    }
+
+    #[test]
+    fn test_truncate_strings() {
+        let mut value = cbor_map! {
+            "short" => "abc",
+            "long" => "abcdef",
+            "bytes" => cbor_bytes!(vec![0x01; 8]),
+            "list" => cbor_array![cbor_tagged!(7, cbor_text!("uvwxyz")), 5],
+            "keys longer" => cbor_bytes!(vec![0x02; 4]),
+        };
+        value.truncate_strings(4);
+        assert_eq!(
+            value,
+            cbor_map! {
+                "shor…" => "abc",
+                "long" => "abcd…",
+                "byte…" => cbor_bytes!(vec![0x01; 4]),
+                "list" => cbor_array![cbor_tagged!(7, cbor_text!("uvwx…")), 5],
+                "keys…" => cbor_bytes!(vec![0x02; 4]),
+            }
+        );
+
+        // Text strings are cut at character boundaries.
+        let mut value = cbor_text!("aé€𝄞");
+        value.truncate_strings(10);
+        assert_eq!(value, cbor_text!("aé€𝄞"));
+        for (max_len, expected) in [
+            (0, "…"),
+            (1, "a…"),
+            (2, "a…"),
+            (3, "aé…"),
+            (5, "aé…"),
+            (6, "aé€…"),
+            (9, "aé€…"),
+        ] {
+            let mut value = cbor_text!("aé€𝄞");
+            value.truncate_strings(max_len);
+            assert_eq!(value, cbor_text!(expected));
+        }
+    }
+
+    #[test]
+    fn test_sort_array_elements() {
+        let mut value = cbor_array![3, "a", -1, cbor_array![2, 1], 0, cbor_bytes!(vec![0x01])];
+        value.sort_array_elements();
+        let expected = cbor_array![0, 3, -1, cbor_bytes!(vec![0x01]), "a", cbor_array![2, 1]];
+        assert_eq!(value, expected);
+        value.sort_array_elements();
+        assert_eq!(value, expected);
+
+        let mut value = cbor_map! {2 => 1, 1 => 2};
+        value.sort_array_elements();
+        assert_eq!(value, cbor_map! {2 => 1, 1 => 2});
+    }
+
+    #[test]
+    fn test_sort_all_arrays() {
+        let mut value = cbor_map! {
+            "set" => cbor_array![cbor_array![3, 1], cbor_array![2, 0]],
+            "tagged" => cbor_tagged!(258, cbor_array!["b", "a"]),
+            "empty" => cbor_array![],
+            cbor_array![2, 1] => cbor_map! {"nested" => cbor_array![1, 0]},
+        };
+        value.sort_all_arrays();
+        // Inner arrays are sorted first, so that [0, 2] sorts before [1, 3].
+        let expected = cbor_map! {
+            "set" => cbor_array![cbor_array![0, 2], cbor_array![1, 3]],
+            "tagged" => cbor_tagged!(258, cbor_array!["a", "b"]),
+            "empty" => cbor_array![],
+            cbor_array![2, 1] => cbor_map! {"nested" => cbor_array![0, 1]},
+        };
+        assert_eq!(value, expected);
+        value.sort_all_arrays();
+        assert_eq!(value, expected);
+
+        let mut value = cbor_int!(1);
+        value.sort_all_arrays();
+        assert_eq!(value, cbor_int!(1));
+    }
+
+    #[test]
+    fn test_sort_all_arrays_deeply_nested() {
+        let mut value = cbor_int!(0);
+        for i in 0..100_000 {
+            value = cbor_array![cbor_int!(i), value];
+        }
+        value.sort_all_arrays();
+        let mut depth = 0;
+        while let Value::Array(mut array) = value {
+            // Integers sort before arrays.
+            value = array.pop().unwrap();
+            assert_eq!(array, vec![cbor_int!(99_999 - depth)]);
+            depth += 1;
+        }
+        assert_eq!(depth, 100_000);
+        assert_eq!(value, cbor_int!(0));
+    }
+
+    #[test]
+    fn test_negative() {
+        assert_eq!(Value::negative(-1), Value::Negative(-1));
+        assert_eq!(Value::negative(i64::MIN), Value::Negative(i64::MIN as i128));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_negative_zero() {
+        Value::negative(0);
+    }
+
+    #[test]
+    fn test_type_name() {
+        let cases = vec![
+            (cbor_int!(1), "unsigned"),
+            (cbor_int!(-1), "negative"),
+            (cbor_bytes!(vec![]), "bytes"),
+            (cbor_text!(""), "text"),
+            (cbor_array![], "array"),
+            (cbor_map! {}, "map"),
+            (cbor_tagged!(1, cbor_int!(0)), "tag"),
+            (cbor_null!(), "simple"),
+            (Value::Float(0.0), "float"),
+        ];
+        for (value, name) in cases {
+            assert_eq!(value.type_name(), name);
+        }
+    }
+
+    #[test]
+    fn test_duration() {
+        let cases = vec![
+            (Duration::new(0, 0), cbor_tagged!(1002, cbor_map! {1 => 0})),
+            (
+                Duration::new(90, 500_000_000),
+                cbor_tagged!(1002, cbor_map! {1 => 90, -9 => 500_000_000}),
+            ),
+            (
+                Duration::new(u64::MAX, 999_999_999),
+                cbor_tagged!(1002, cbor_map! {1 => u64::MAX, -9 => 999_999_999}),
+            ),
+        ];
+        for (duration, value) in cases {
+            assert_eq!(Value::from_duration(duration), value);
+            assert_eq!(value.as_duration(), Some(duration));
+        }
+
+        let duration = Duration::from_millis(1_500);
+        let value = cbor_tagged!(1002, cbor_map! {1 => 1, -3 => 500});
+        assert_eq!(value.as_duration(), Some(duration));
+        let value = cbor_tagged!(1002, cbor_map! {1 => 1, -6 => 500_000});
+        assert_eq!(value.as_duration(), Some(duration));
+
+        let invalid_values = vec![
+            cbor_map! {1 => 1},
+            cbor_tagged!(1001, cbor_map! {1 => 1}),
+            cbor_tagged!(1002, cbor_int!(1)),
+            cbor_tagged!(1002, cbor_map! {}),
+            cbor_tagged!(1002, cbor_map! {1 => -1}),
+            cbor_tagged!(1002, cbor_map! {1 => Value::Float(1.5)}),
+            cbor_tagged!(1002, cbor_map! {1 => 1, -3 => 1_000}),
+            cbor_tagged!(1002, cbor_map! {1 => 1, -4 => 1}),
+            cbor_tagged!(1002, cbor_map! {1 => 1, -9 => 1, -3 => 1}),
+            cbor_tagged!(1002, cbor_map! {-9 => 1}),
+        ];
+        for value in invalid_values {
+            assert_eq!(value.as_duration(), None);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_ip() {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let value = cbor_tagged!(52, cbor_bytes!(vec![0xC0, 0x00, 0x02, 0x01]));
+        assert_eq!(Value::from_ip(addr), value);
+        assert_eq!(value.as_ip(), Some(addr));
+
+        let addr = IpAddr::V6(Ipv6Addr::new(
+            0x2001, 0xdb8, 0x1234, 0xdeed, 0xbeef, 0xcafe, 0xface, 0xfeed,
+        ));
+        let value = cbor_tagged!(
+            54,
+            cbor_bytes!(vec![
+                0x20, 0x01, 0x0D, 0xB8, 0x12, 0x34, 0xDE, 0xED, 0xBE, 0xEF, 0xCA, 0xFE, 0xFA, 0xCE,
+                0xFE, 0xED
+            ])
+        );
+        assert_eq!(Value::from_ip(addr), value);
+        assert_eq!(value.as_ip(), Some(addr));
+
+        let invalid_values = vec![
+            cbor_bytes!(vec![0xC0, 0x00, 0x02, 0x01]),
+            cbor_tagged!(52, cbor_bytes!(vec![0xC0, 0x00, 0x02])),
+            cbor_tagged!(54, cbor_bytes!(vec![0xC0, 0x00, 0x02, 0x01])),
+            // Prefix format.
+            cbor_tagged!(52, cbor_array![24, cbor_bytes!(vec![0xC0, 0x00, 0x02])]),
+            cbor_tagged!(53, cbor_bytes!(vec![0xC0, 0x00, 0x02, 0x01])),
+        ];
+        for value in invalid_values {
+            assert_eq!(value.as_ip(), None);
+        }
+    }
+
+    #[test]
+    fn test_from_btree_map() {
+        let map: BTreeMap<&str, u64> = vec![("bb", 1), ("a", 2), ("c", 3)].into_iter().collect();
+        let value = Value::from(map);
+        assert_eq!(value, cbor_map! {"a" => 2, "bb" => 1, "c" => 3});
+        let mut encoded_cbor = Vec::new();
+        crate::writer::write(value, &mut encoded_cbor).unwrap();
+        // Keys are sorted canonically when written, with shorter keys first.
+        let expected_cbor = [
+            0xA3, 0x61, 0x61, 0x02, 0x61, 0x63, 0x03, 0x62, 0x62, 0x62, 0x01,
+        ];
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        let map: BTreeMap<Value, Value> =
+            vec![(cbor_int!(1), cbor_text!("a"))].into_iter().collect();
+        assert_eq!(Value::from(map), cbor_map! {1 => "a"});
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_hash_map() {
+        use std::collections::HashMap;
+
+        let entries = vec![("bb", -1i64), ("a", 2), ("c", 3)];
+        let mut forward = HashMap::new();
+        for (k, v) in entries.clone() {
+            forward.insert(k, v);
+        }
+        let mut backward = HashMap::with_capacity(100);
+        for (k, v) in entries.into_iter().rev() {
+            backward.insert(k, v);
+        }
+        let forward = Value::from(forward);
+        let backward = Value::from(backward);
+        assert_eq!(forward, cbor_map! {"a" => 2, "c" => 3, "bb" => -1});
+        assert_eq!(forward, backward);
+        let mut forward_cbor = Vec::new();
+        crate::writer::write(forward, &mut forward_cbor).unwrap();
+        let mut backward_cbor = Vec::new();
+        crate::writer::write(backward, &mut backward_cbor).unwrap();
+        assert_eq!(forward_cbor, backward_cbor);
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(Value::from(0u64), Value::Unsigned(0));
+        assert_eq!(Value::from(0i64), Value::Unsigned(0));
+        assert_eq!(Value::from(-1i64), Value::Negative(-1));
+        assert_eq!(Value::from(1i32), Value::Unsigned(1));
+        assert_eq!(Value::from(-1i8), Value::Negative(-1));
+        assert_eq!(Value::from(i16::MIN), Value::Negative(i16::MIN as i128));
+        assert_eq!(Value::from(u8::MAX), Value::Unsigned(255));
+        assert_eq!(Value::from(u32::MAX), Value::Unsigned(u32::MAX as u64));
+        assert_eq!(Value::from("a"), cbor_text!("a"));
+        assert_eq!(Value::from(String::from("a")), cbor_text!("a"));
+        assert_eq!(Value::from(vec![0x01u8]), cbor_bytes!(vec![0x01]));
+        assert_eq!(Value::from(true), cbor_bool!(true));
+        assert_eq!(Value::from(vec![cbor_int!(1)]), cbor_array![1]);
+        let value: Value = 5u16.into();
+        assert_eq!(value, cbor_int!(5));
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        assert_eq!(cbor_int!(1).nesting_depth(), 0);
+        assert_eq!(cbor_text!("a").nesting_depth(), 0);
+        assert_eq!(cbor_array![].nesting_depth(), 1);
+        assert_eq!(cbor_array![cbor_map! {}].nesting_depth(), 2);
+        assert_eq!(
+            cbor_map! {1 => 2, cbor_array![cbor_array![]] => 3}.nesting_depth(),
+            3
+        );
+        assert_eq!(cbor_tagged!(1, cbor_array![1]).nesting_depth(), 2);
+
+        let mut deep = cbor_int!(0);
+        for _ in 0..100_000 {
+            deep = cbor_array![deep];
+        }
+        assert_eq!(deep.nesting_depth(), 100_000);
+        // Avoid a recursive drop of the deeply nested value.
+        while let Value::Array(mut array) = deep {
+            deep = array.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut value = cbor_array![cbor_map! {
+            "b" => cbor_tagged!(1, cbor_map! {2 => 0, 1 => 1}),
+            cbor_map! {"y" => 0, "x" => 1} => 2,
+            1000 => 3,
+            "a" => 4,
+        }];
+        let mut original_cbor = Vec::new();
+        crate::writer::write(value.clone(), &mut original_cbor).unwrap();
+
+        assert_eq!(value.canonicalize(), Ok(()));
+        assert_eq!(
+            value,
+            cbor_array![cbor_map! {
+                1000 => 3,
+                "a" => 4,
+                "b" => cbor_tagged!(1, cbor_map! {1 => 1, 2 => 0}),
+                cbor_map! {"x" => 1, "y" => 0} => 2,
+            }]
+        );
+        let mut canonical_cbor = Vec::new();
+        let options = crate::writer::WriterOptions {
+            preserve_map_order: true,
+            ..Default::default()
+        };
+        crate::writer::write_with_options(value, &mut canonical_cbor, None, &options).unwrap();
+        assert_eq!(canonical_cbor, original_cbor);
+
+        let mut duplicate = cbor_array![cbor_map! {1 => 1, 2 => 2, 1 => 3}];
+        assert_eq!(
+            duplicate.canonicalize(),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x01] })
+        );
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let value = cbor_map! {
+            "a" => cbor_array![Value::Float(1.0), Value::Float(f64::NAN)],
+            "b" => cbor_tagged!(1, Value::Float(-2.5)),
+            Value::Float(0.1) => 3,
+        };
+        let close = cbor_map! {
+            "a" => cbor_array![Value::Float(1.0 + 1e-10), Value::Float(-f64::NAN)],
+            "b" => cbor_tagged!(1, Value::Float(-2.5 - 1e-10)),
+            Value::Float(0.1 + 1e-10) => 3,
+        };
+        assert!(value.approx_eq(&value, 0.0));
+        assert!(value.approx_eq(&close, 1e-9));
+        assert!(close.approx_eq(&value, 1e-9));
+        assert!(!value.approx_eq(&close, 1e-11));
+        assert!(Value::Float(f64::INFINITY).approx_eq(&Value::Float(f64::INFINITY), 0.0));
+        assert!(!Value::Float(f64::INFINITY).approx_eq(&Value::Float(f64::MAX), 1.0));
+        assert!(!Value::Float(1.0).approx_eq(&Value::Float(f64::NAN), f64::INFINITY));
+        assert!(Value::Float(1.0).approx_eq(&Value::Float(1e300), f64::INFINITY));
+
+        // Other variants are compared exactly.
+        let differing = vec![
+            (cbor_int!(1), cbor_int!(2)),
+            (cbor_int!(1), Value::Float(1.0)),
+            (Value::Float(1.0), cbor_int!(1)),
+            (cbor_text!("a"), cbor_bytes!(vec![b'a'])),
+            (cbor_array![1], cbor_array![1, 1]),
+            (cbor_map! {1 => 1}, cbor_map! {2 => 1}),
+            (cbor_map! {1 => 1, 2 => 2}, cbor_map! {2 => 2, 1 => 1}),
+            (cbor_tagged!(1, cbor_int!(1)), cbor_tagged!(2, cbor_int!(1))),
+            (cbor_null!(), cbor_bool!(false)),
+        ];
+        for (a, b) in differing {
+            assert!(!a.approx_eq(&b, 1.0), "{:?} {:?}", a, b);
+        }
+        assert!(cbor_map! {1 => "a"}.approx_eq(&cbor_map! {1 => "a"}, 0.0));
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = cbor_map! {
+            "a" => 1,
+            "b" => cbor_map! {"x" => 1, "y" => 2},
+            "c" => cbor_array![1],
+        };
+        let overrides = cbor_map! {
+            "b" => cbor_map! {"y" => 3, "z" => 4},
+            "c" => cbor_array![2],
+            "d" => 5,
+        };
+
+        let mut value = defaults.clone();
+        assert_eq!(value.merge(overrides.clone(), false), Ok(()));
+        assert_eq!(
+            value,
+            cbor_map! {
+                "a" => 1,
+                "b" => cbor_map! {"y" => 3, "z" => 4},
+                "c" => cbor_array![2],
+                "d" => 5,
+            }
+        );
+
+        let mut value = defaults.clone();
+        assert_eq!(value.merge(overrides, true), Ok(()));
+        assert_eq!(
+            value,
+            cbor_map! {
+                "a" => 1,
+                "b" => cbor_map! {"x" => 1, "y" => 3, "z" => 4},
+                "c" => cbor_array![2],
+                "d" => 5,
+            }
+        );
+
+        let mut value = defaults.clone();
+        assert_eq!(value.merge(cbor_int!(1), true), Ok(()));
+        assert_eq!(value, cbor_int!(1));
+        let mut value = cbor_array![];
+        assert_eq!(value.merge(defaults.clone(), true), Ok(()));
+        assert_eq!(value, defaults);
+
+        let mut value = defaults.clone();
+        assert_eq!(
+            value.merge(cbor_map! {"a" => 2, "a" => 3}, false),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0x61],
+            })
+        );
+        let mut value = defaults;
+        assert_eq!(
+            value.merge(cbor_map! {"e" => 2, "e" => 3}, false),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0x65],
+            })
+        );
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let cases = vec![
+            (cbor_int!(1), cbor_int!(1), true),
+            (cbor_int!(1), cbor_int!(2), false),
+            (
+                cbor_map! {"b" => 1, "a" => 2},
+                cbor_map! {"a" => 2, "b" => 1},
+                true,
+            ),
+            (
+                cbor_map! {"b" => 1, "a" => 2},
+                cbor_map! {"a" => 1, "b" => 2},
+                false,
+            ),
+            (cbor_map! {"a" => 1}, cbor_map! {"a" => 1, "b" => 2}, false),
+            (
+                cbor_array![cbor_tagged!(
+                    1,
+                    cbor_map! {1 => cbor_map! {3 => 0, 2 => 0}, 0 => 0}
+                )],
+                cbor_array![cbor_tagged!(
+                    1,
+                    cbor_map! {0 => 0, 1 => cbor_map! {2 => 0, 3 => 0}}
+                )],
+                true,
+            ),
+            (
+                cbor_map! {cbor_map! {2 => 0, 1 => 0} => 0},
+                cbor_map! {cbor_map! {1 => 0, 2 => 0} => 0},
+                true,
+            ),
+            (cbor_array![1, 2], cbor_array![2, 1], false),
+            (
+                cbor_tagged!(1, cbor_int!(0)),
+                cbor_tagged!(2, cbor_int!(0)),
+                false,
+            ),
+            // Duplicate entries are matched one to one.
+            (
+                cbor_map! {1 => 0, 1 => 0, 2 => 0},
+                cbor_map! {1 => 0, 2 => 0, 2 => 0},
+                false,
+            ),
+            (
+                cbor_map! {1 => 0, 2 => 0, 1 => 0},
+                cbor_map! {1 => 0, 1 => 0, 2 => 0},
+                true,
+            ),
+        ];
+        for (v1, v2, eq) in cases {
+            assert_eq!(v1.semantic_eq(&v2), eq);
+            assert_eq!(v2.semantic_eq(&v1), eq);
+        }
+    }
+
+    #[test]
+    fn test_major_type() {
+        let cases = vec![
+            (0x00, MajorType::Unsigned),
+            (0x37, MajorType::Negative),
+            (0x5F, MajorType::ByteString),
+            (0x60, MajorType::TextString),
+            (0x9F, MajorType::Array),
+            (0xA1, MajorType::Map),
+            (0xC1, MajorType::Tag),
+            (0xF9, MajorType::SimpleFloat),
+            (0xFF, MajorType::SimpleFloat),
+        ];
+        for (initial_byte, major_type) in cases {
+            assert_eq!(MajorType::from_initial_byte(initial_byte), major_type);
+            assert_eq!(major_type.to_label(), initial_byte >> 5);
+        }
+        assert_eq!(cbor_text!("a").type_label(), MajorType::TextString);
+        assert_eq!(Value::Float(1.0).type_label(), MajorType::SimpleFloat);
+    }
 
     #[test]
     fn test_value_ordering() {
@@ -338,6 +2867,9 @@ mod test {
         assert!(cbor_bool!(false) < cbor_bool!(true));
         assert!(cbor_bool!(true) < Value::Simple(SimpleValue::NullValue));
         assert!(Value::Simple(SimpleValue::NullValue) < Value::Simple(SimpleValue::Undefined));
+        assert!(cbor_simple!(19) < cbor_bool!(false));
+        assert!(Value::Simple(SimpleValue::Undefined) < cbor_simple!(32));
+        assert!(cbor_simple!(255) < Value::Float(0.0));
         assert!(cbor_tagged!(1, cbor_text!("s")) < cbor_tagged!(2, cbor_int!(0)));
         assert!(cbor_int!(1) < cbor_int!(-1));
         assert!(cbor_int!(1) < cbor_bytes!(vec![0x00]));
@@ -365,5 +2897,204 @@ mod test {
         assert!(cbor_array![] < cbor_tagged!(2, cbor_int!(0)));
         assert!(cbor_array![] < cbor_bool!(false));
         assert!(cbor_tagged!(1, cbor_text!("s")) < cbor_bool!(false));
+        assert!(Value::Simple(SimpleValue::Undefined) < Value::Float(0.0));
+        assert!(Value::Float(0.0) < Value::Float(-0.0));
+        assert!(Value::Float(1.0) < Value::Float(f64::NAN));
+        assert!(Value::Float(f64::NAN) < Value::Float(100000.0));
+        assert!(Value::Float(100000.0) < Value::Float(1.1));
+        assert_eq!(Value::Float(f64::NAN), Value::Float(-f64::NAN));
+    }
+
+    #[test]
+    fn test_value_ordering_matches_encoding() {
+        let mut values = vec![
+            cbor_int!(24),
+            cbor_int!(-1),
+            cbor_int!(0),
+            cbor_int!(1000),
+            cbor_int!(-25),
+            cbor_bytes!(vec![0xFF]),
+            cbor_bytes!(vec![0x00, 0x00]),
+            cbor_text!("b"),
+            cbor_text!("aa"),
+            cbor_text!(""),
+            cbor_array![1, 2],
+            cbor_array![cbor_int!(-1)],
+            cbor_map! {0 => 1},
+            cbor_tagged!(1, cbor_text!("s")),
+            cbor_bool!(true),
+            Value::Simple(SimpleValue::NullValue),
+            Value::Float(1.1),
+            Value::Float(-0.0),
+            Value::Float(100000.0),
+        ];
+        values.sort();
+        let encoded: Vec<_> = values
+            .iter()
+            .map(|v| crate::writer::write_with_capacity(v.clone(), 0).unwrap())
+            .collect();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+
+        // Sorting keys matches the order the writer emits map entries in.
+        let map = Value::Map(
+            values
+                .iter()
+                .rev()
+                .map(|k| (k.clone(), cbor_int!(0)))
+                .collect(),
+        );
+        let mut sorted = map.clone();
+        sorted.canonicalize().unwrap();
+        let keys: Vec<_> = sorted
+            .into_map()
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, values);
+        let options = crate::writer::WriterOptions {
+            preserve_map_order: true,
+            ..Default::default()
+        };
+        let mut encoded_sorted = Vec::new();
+        let sorted = Value::Map(values.into_iter().map(|k| (k, cbor_int!(0))).collect());
+        crate::writer::write_with_options(sorted, &mut encoded_sorted, None, &options).unwrap();
+        assert_eq!(
+            crate::writer::write_with_capacity(map, 0).unwrap(),
+            encoded_sorted
+        );
+    }
+
+    #[test]
+    fn test_from_u128() {
+        assert_eq!(Value::from_u128(0), Value::Unsigned(0));
+        assert_eq!(
+            Value::from_u128(u64::MAX as u128),
+            Value::Unsigned(u64::MAX)
+        );
+        assert_eq!(
+            Value::from_u128(u64::MAX as u128 + 1),
+            cbor_tagged!(2, cbor_bytes!(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0]))
+        );
+        assert_eq!(
+            Value::from_u128(u128::MAX),
+            cbor_tagged!(2, cbor_bytes!(vec![0xFF; 16]))
+        );
+    }
+
+    #[test]
+    fn test_from_i128() {
+        assert_eq!(Value::from_i128(1), Value::Unsigned(1));
+        assert_eq!(Value::from_i128(-1), Value::Negative(-1));
+        assert_eq!(
+            Value::from_i128(i64::MIN as i128 - 1),
+            Value::Negative(-9223372036854775809)
+        );
+        // The smallest integer that fits in a nint is -2^64.
+        assert_eq!(
+            Value::from_i128(-(u64::MAX as i128) - 1),
+            Value::Negative(-18446744073709551616)
+        );
+        assert_eq!(
+            Value::from_i128(-(u64::MAX as i128) - 2),
+            cbor_tagged!(3, cbor_bytes!(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0]))
+        );
+        assert_eq!(
+            Value::from_i128(i128::MIN),
+            cbor_tagged!(
+                3,
+                cbor_bytes!(vec![
+                    0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                    0xFF, 0xFF, 0xFF
+                ])
+            )
+        );
+        assert_eq!(
+            Value::from_i128(i128::MAX),
+            Value::from_u128(i128::MAX as u128)
+        );
+    }
+
+    #[test]
+    fn test_from_int128() {
+        let two_pow_64 = 1u128 << 64;
+        assert_eq!(Value::from(two_pow_64 - 1), Value::Unsigned(u64::MAX));
+        assert_eq!(
+            Value::from(two_pow_64),
+            cbor_tagged!(2, cbor_bytes!(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0]))
+        );
+        assert_eq!(
+            Value::from(two_pow_64 as i128 - 1),
+            Value::Unsigned(u64::MAX)
+        );
+        assert_eq!(Value::from(two_pow_64 as i128), Value::from(two_pow_64));
+        assert_eq!(
+            Value::from(-(two_pow_64 as i128)),
+            Value::Negative(-(two_pow_64 as i128))
+        );
+        assert_eq!(
+            Value::from(-(two_pow_64 as i128) - 1),
+            cbor_tagged!(3, cbor_bytes!(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0]))
+        );
+
+        for int in [0, 1, two_pow_64 - 1, two_pow_64, u128::MAX] {
+            assert_eq!(u128::try_from(Value::from(int)), Ok(int));
+        }
+        for int in [
+            0,
+            -1,
+            two_pow_64 as i128 - 1,
+            two_pow_64 as i128,
+            -(two_pow_64 as i128),
+            -(two_pow_64 as i128) - 1,
+            i128::MIN,
+            i128::MAX,
+        ] {
+            assert_eq!(i128::try_from(Value::from(int)), Ok(int));
+        }
+        assert_eq!(
+            u128::try_from(cbor_int!(-1)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            i128::try_from(Value::from(u128::MAX)),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            u128::try_from(cbor_tagged!(2, cbor_bytes!(vec![0x01; 17]))),
+            Err(ValueConversionError::OutOfRange)
+        );
+        assert_eq!(
+            u128::try_from(cbor_text!("1")),
+            Err(ValueConversionError::UnexpectedType)
+        );
+        assert_eq!(
+            i128::try_from(cbor_tagged!(4, cbor_bytes!(vec![0x01]))),
+            Err(ValueConversionError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_encoded_len_hint() {
+        let cases = vec![
+            cbor_int!(0),
+            cbor_int!(24),
+            cbor_int!(i64::MAX),
+            cbor_int!(-25),
+            cbor_int!(i64::MIN),
+            cbor_bytes!(vec![0x00; 300]),
+            cbor_text!("IETF"),
+            cbor_array![1, 2, cbor_array![3, 4]],
+            cbor_map! {"a" => 1, 1000 => cbor_map! {"b" => b"c"}},
+            cbor_tagged!(1000, cbor_text!("s")),
+            cbor_bool!(true),
+            Value::Float(1.5),
+        ];
+        for value in cases {
+            let mut encoded = Vec::new();
+            crate::writer::write(value.clone(), &mut encoded).unwrap();
+            assert_eq!(value.encoded_len_hint(), encoded.len());
+        }
+        assert!(Value::Float(1.1).encoded_len_hint() <= 9);
     }
 }
@@ -0,0 +1,254 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functionality for traversing CBOR values without copying them.
+
+use alloc::{vec, vec::Vec};
+
+use super::values::{SimpleValue, Value};
+
+/// Callbacks for the items of a [`Value`] traversed by [`Value::accept`], borrowing from the
+/// value.  Every method does nothing by default, so that visitors only override what they need.
+pub trait Visitor<'a> {
+    /// Visit an unsigned integer value (uint).
+    fn visit_unsigned(&mut self, _unsigned: u64) {}
+
+    /// Visit a negative integer value (nint).
+    fn visit_negative(&mut self, _negative: i128) {}
+
+    /// Visit a byte string (bstr).
+    fn visit_bytes(&mut self, _byte_string: &'a [u8]) {}
+
+    /// Visit a text string (tstr).
+    fn visit_text(&mut self, _text_string: &'a str) {}
+
+    /// Start visiting an array with the given number of elements, which are visited next.
+    fn visit_array_start(&mut self, _len: usize) {}
+
+    /// Finish visiting an array.
+    fn visit_array_end(&mut self) {}
+
+    /// Start visiting a map with the given number of entries, which are visited next.
+    fn visit_map_start(&mut self, _len: usize) {}
+
+    /// Visit an entry of a map, before its key and then its value are visited.
+    fn visit_map_entry(&mut self, _key: &'a Value, _value: &'a Value) {}
+
+    /// Finish visiting a map.
+    fn visit_map_end(&mut self) {}
+
+    /// Start visiting a tagged value, whose content is visited next.
+    fn visit_tag_start(&mut self, _tag: u64) {}
+
+    /// Finish visiting a tagged value.
+    fn visit_tag_end(&mut self, _tag: u64) {}
+
+    /// Visit a simple value.
+    fn visit_simple(&mut self, _simple_value: &'a SimpleValue) {}
+
+    /// Visit a floating-point value.
+    fn visit_float(&mut self, _float: f64) {}
+}
+
+/// Pending step of a traversal.
+enum Step<'a> {
+    Visit(&'a Value),
+    MapEntry(&'a Value, &'a Value),
+    ArrayEnd,
+    MapEnd,
+    TagEnd(u64),
+}
+
+impl Value {
+    /// Traverse the [`Value`] depth-first, calling the [`Visitor`] for each nested item in the
+    /// order they are stored, i.e. as they would be written with
+    /// [`WriterOptions::preserve_map_order`](crate::writer::WriterOptions) set.  Nested values are
+    /// visited without recursion, so that deeply nested values cannot overflow the stack.
+    pub fn accept<'a, V: Visitor<'a>>(&'a self, visitor: &mut V) {
+        let mut steps = vec![Step::Visit(self)];
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Visit(value) => visit(value, visitor, &mut steps),
+                Step::MapEntry(key, value) => {
+                    visitor.visit_map_entry(key, value);
+                    steps.push(Step::Visit(value));
+                    steps.push(Step::Visit(key));
+                }
+                Step::ArrayEnd => visitor.visit_array_end(),
+                Step::MapEnd => visitor.visit_map_end(),
+                Step::TagEnd(tag) => visitor.visit_tag_end(tag),
+            }
+        }
+    }
+}
+
+/// Visit a single item, scheduling any nested values on the step list.
+fn visit<'a, V: Visitor<'a>>(value: &'a Value, visitor: &mut V, steps: &mut Vec<Step<'a>>) {
+    match value {
+        Value::Unsigned(unsigned) => visitor.visit_unsigned(*unsigned),
+        Value::Negative(negative) => visitor.visit_negative(*negative),
+        Value::ByteString(byte_string) => visitor.visit_bytes(byte_string),
+        Value::TextString(text_string) => visitor.visit_text(text_string),
+        Value::Array(array) => {
+            visitor.visit_array_start(array.len());
+            steps.push(Step::ArrayEnd);
+            steps.extend(array.iter().rev().map(Step::Visit));
+        }
+        Value::Map(map) => {
+            visitor.visit_map_start(map.len());
+            steps.push(Step::MapEnd);
+            steps.extend(map.iter().rev().map(|(k, v)| Step::MapEntry(k, v)));
+        }
+        Value::Tag(tag, inner_value) => {
+            visitor.visit_tag_start(*tag);
+            steps.push(Step::TagEnd(*tag));
+            steps.push(Step::Visit(inner_value));
+        }
+        Value::Simple(simple_value) => visitor.visit_simple(simple_value),
+        Value::Float(float) => visitor.visit_float(*float),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{format, string::String};
+
+    use super::*;
+    use crate::{cbor_array, cbor_bytes, cbor_int, cbor_map, cbor_null, cbor_tagged, cbor_text};
+
+    /// Visitor recording every callback.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn visit_unsigned(&mut self, unsigned: u64) {
+            self.0.push(format!("uint {}", unsigned));
+        }
+        fn visit_negative(&mut self, negative: i128) {
+            self.0.push(format!("nint {}", negative));
+        }
+        fn visit_bytes(&mut self, byte_string: &'a [u8]) {
+            self.0.push(format!("bytes {:?}", byte_string));
+        }
+        fn visit_text(&mut self, text_string: &'a str) {
+            self.0.push(format!("text {}", text_string));
+        }
+        fn visit_array_start(&mut self, len: usize) {
+            self.0.push(format!("array {}", len));
+        }
+        fn visit_array_end(&mut self) {
+            self.0.push(String::from("array end"));
+        }
+        fn visit_map_start(&mut self, len: usize) {
+            self.0.push(format!("map {}", len));
+        }
+        fn visit_map_entry(&mut self, key: &'a Value, _value: &'a Value) {
+            self.0.push(format!("entry {:?}", key));
+        }
+        fn visit_map_end(&mut self) {
+            self.0.push(String::from("map end"));
+        }
+        fn visit_tag_start(&mut self, tag: u64) {
+            self.0.push(format!("tag {}", tag));
+        }
+        fn visit_tag_end(&mut self, tag: u64) {
+            self.0.push(format!("tag end {}", tag));
+        }
+        fn visit_simple(&mut self, simple_value: &'a SimpleValue) {
+            self.0.push(format!("simple {:?}", simple_value));
+        }
+        fn visit_float(&mut self, float: f64) {
+            self.0.push(format!("float {}", float));
+        }
+    }
+
+    #[test]
+    fn test_accept() {
+        let value = cbor_array![
+            1,
+            -2,
+            cbor_map! {"b" => cbor_bytes!(vec![0x03]), "a" => cbor_tagged!(4, cbor_null!())},
+            Value::Float(1.5),
+        ];
+        let mut recorder = Recorder::default();
+        value.accept(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            vec![
+                "array 4",
+                "uint 1",
+                "nint -2",
+                "map 2",
+                "entry TextString(\"b\")",
+                "text b",
+                "bytes [3]",
+                "entry TextString(\"a\")",
+                "text a",
+                "tag 4",
+                "simple NullValue",
+                "tag end 4",
+                "map end",
+                "float 1.5",
+                "array end",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accept_default_methods() {
+        /// Visitor collecting borrowed text strings only.
+        struct Texts<'a>(Vec<&'a str>);
+
+        impl<'a> Visitor<'a> for Texts<'a> {
+            fn visit_text(&mut self, text_string: &'a str) {
+                self.0.push(text_string);
+            }
+        }
+
+        let value = cbor_map! {
+            "a" => cbor_array![cbor_text!("b"), cbor_int!(1)],
+            2 => cbor_tagged!(3, cbor_text!("c")),
+        };
+        let mut texts = Texts(Vec::new());
+        value.accept(&mut texts);
+        assert_eq!(texts.0, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_accept_deeply_nested() {
+        /// Visitor counting all items.
+        struct Counter(usize);
+
+        impl<'a> Visitor<'a> for Counter {
+            fn visit_unsigned(&mut self, _unsigned: u64) {
+                self.0 += 1;
+            }
+            fn visit_array_start(&mut self, _len: usize) {
+                self.0 += 1;
+            }
+        }
+
+        let mut value = cbor_int!(0);
+        for _ in 0..100_000 {
+            value = cbor_array![value];
+        }
+        let mut counter = Counter(0);
+        value.accept(&mut counter);
+        assert_eq!(counter.0, 100_001);
+        // Unwrap the value level by level, as dropping it at once would overflow the stack.
+        while let Value::Array(mut array) = value {
+            value = array.pop().unwrap();
+        }
+    }
+}
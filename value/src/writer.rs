@@ -14,22 +14,96 @@
 
 //! Functionality for serializing CBOR values into bytes.
 
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{convert::TryFrom, fmt};
 
-use super::values::{Constants, Value};
+use super::{
+    scanner::Scanner,
+    values::{Constants, MajorType, SimpleValue, Value, ValueRef, MAX_NESTING_DEPTH},
+};
 
 /// Possible errors from a serialization operation.
 #[derive(Debug, PartialEq)]
 pub enum EncoderError {
     TooMuchNesting,
-    DuplicateMapKey,
+    /// A map has more than one entry with the given encoded key.
+    DuplicateMapKey {
+        key: Vec<u8>,
+    },
+    FrameTooLarge,
+    OutputTooLarge,
+    InvalidSimpleValue,
+    LengthMismatch,
+    OutOfOrderKey,
+}
+
+impl fmt::Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncoderError::TooMuchNesting => f.write_str("maximum nesting depth exceeded"),
+            EncoderError::DuplicateMapKey { key } => {
+                f.write_str("duplicate map key h'")?;
+                for byte in key {
+                    write!(f, "{:02x}", byte)?;
+                }
+                f.write_str("'")
+            }
+            EncoderError::FrameTooLarge => f.write_str("frame length exceeds 32 bits"),
+            EncoderError::OutputTooLarge => f.write_str("maximum output length exceeded"),
+            EncoderError::InvalidSimpleValue => {
+                f.write_str("reserved or non-canonical simple value")
+            }
+            EncoderError::LengthMismatch => f.write_str("number of items differs from length"),
+            EncoderError::OutOfOrderKey => f.write_str("map key out of order"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncoderError {}
+
+/// Possible errors from a serialization operation into an [`std::io::Write`] sink.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum WriteError {
+    /// The value could not be encoded.
+    Encoder(EncoderError),
+    /// The sink failed to accept the encoded data.
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Encoder(e) => e.fmt(f),
+            WriteError::Io(e) => write!(f, "write failed: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::Encoder(e) => Some(e),
+            WriteError::Io(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<EncoderError> for WriteError {
+    fn from(e: EncoderError) -> Self {
+        WriteError::Encoder(e)
+    }
 }
 
 /// Convert a [`Value`] to serialized CBOR data, consuming it along the way and appending to the provided vector.
 /// Maximum level of nesting supported is 127; more deeply nested structures will fail with
 /// [`EncoderError::TooMuchNesting`].
 pub fn write(value: Value, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
-    write_nested(value, encoded_cbor, Some(i8::MAX))
+    write_nested(value, encoded_cbor, Some(MAX_NESTING_DEPTH))
 }
 
 /// Convert a [`Value`] to serialized CBOR data, consuming it along the way and appending to the provided vector.  If
@@ -39,235 +113,1613 @@ pub fn write_nested(
     value: Value,
     encoded_cbor: &mut Vec<u8>,
     max_nest: Option<i8>,
+) -> Result<(), EncoderError> {
+    let result = write_nested_ref(&value, encoded_cbor, max_nest);
+    dismantle(value);
+    result
+}
+
+/// Convert a sequence of [`Value`]s to a CBOR sequence (RFC 8742), appending each item's serialized
+/// data to the provided vector without any framing.  An empty sequence produces no data.  Maximum
+/// level of nesting supported within each item is 127; more deeply nested structures will fail with
+/// [`EncoderError::TooMuchNesting`].
+pub fn write_sequence<I: IntoIterator<Item = Value>>(
+    items: I,
+    encoded_cbor: &mut Vec<u8>,
+) -> Result<(), EncoderError> {
+    for value in items {
+        write(value, encoded_cbor)?;
+    }
+    Ok(())
+}
+
+/// Convert a [`Value`] to a self-delimiting frame, appending to the provided vector the length of
+/// its serialized data as a 4-byte big-endian integer followed by the data itself.  Data longer
+/// than `u32::MAX` bytes fails with [`EncoderError::FrameTooLarge`], leaving the vector unchanged.
+/// Maximum level of nesting supported is 127; more deeply nested structures will fail with
+/// [`EncoderError::TooMuchNesting`].
+pub fn write_framed(value: Value, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
+    let start = encoded_cbor.len();
+    encoded_cbor.extend_from_slice(&[0; 4]);
+    let result = write(value, encoded_cbor).and_then(|()| {
+        u32::try_from(encoded_cbor.len() - start - 4).map_err(|_| EncoderError::FrameTooLarge)
+    });
+    match result {
+        Ok(len) => {
+            encoded_cbor[start..start + 4].copy_from_slice(&len.to_be_bytes());
+            Ok(())
+        }
+        Err(e) => {
+            encoded_cbor.truncate(start);
+            Err(e)
+        }
+    }
+}
+
+/// Convert a [`Value`] to self-described CBOR data, appending to the provided vector the head of
+/// the self-described CBOR tag (tag 55799), which starts the data with the magic bytes `0xD9D9F7`,
+/// followed by the serialized value.  The tag does not count towards the nesting limit of 127;
+/// more deeply nested structures will fail with [`EncoderError::TooMuchNesting`], leaving the
+/// vector unchanged.
+pub fn write_self_describing(value: Value, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
+    let start = encoded_cbor.len();
+    encoded_cbor.extend_from_slice(&Constants::SELF_DESCRIBED_CBOR_HEAD);
+    write(value, encoded_cbor).inspect_err(|_| encoded_cbor.truncate(start))
+}
+
+/// Return the error for a map key occurring more than once, carrying its encoding, or the error
+/// encoding it fails with.
+pub(crate) fn duplicate_key_error(key: &Value) -> EncoderError {
+    let mut encoded_key = Vec::new();
+    match write_nested_ref(key, &mut encoded_key, None) {
+        Ok(()) => EncoderError::DuplicateMapKey { key: encoded_key },
+        Err(e) => e,
+    }
+}
+
+/// Convert a [`Value`] to serialized CBOR data without consuming it, appending to the provided
+/// vector.  The output is identical to that of [`write`].  Maximum level of nesting supported is
+/// 127; more deeply nested structures will fail with [`EncoderError::TooMuchNesting`].
+pub fn write_ref(value: &Value, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
+    write_nested_ref(value, encoded_cbor, Some(MAX_NESTING_DEPTH))
+}
+
+/// Convert a [`Value`] to serialized CBOR data without consuming it, appending to the provided
+/// vector.  If `max_nest` is `Some(max)`, then nested structures are only supported up to the
+/// given limit (returning [`EncoderError::TooMuchNesting`] if the limit is hit).
+pub fn write_nested_ref(
+    value: &Value,
+    encoded_cbor: &mut Vec<u8>,
+    max_nest: Option<i8>,
 ) -> Result<(), EncoderError> {
     let mut writer = Writer::new(encoded_cbor);
-    writer.encode_cbor(value, max_nest)
+    writer.encode_cbor(Node::Value(value), max_nest)
 }
 
-struct Writer<'a> {
-    encoded_cbor: &'a mut Vec<u8>,
+/// Convert a [`ValueRef`] to serialized CBOR data, appending to the provided vector.  The output is
+/// identical to that of [`write`] for the equivalent [`Value`].  Maximum level of nesting
+/// supported is 127; more deeply nested structures will fail with
+/// [`EncoderError::TooMuchNesting`].
+pub fn write_value_ref(value: &ValueRef, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
+    let mut writer = Writer::new(encoded_cbor);
+    writer.encode_cbor(Node::Ref(value), Some(MAX_NESTING_DEPTH))
+}
+
+/// Convert a [`Value`] to serialized CBOR data using the given [`WriterOptions`], consuming it along
+/// the way and appending to the provided vector, returning the number of bytes written.  If
+/// `max_nest` is `Some(max)`, then nested structures are only supported up to the given limit
+/// (returning [`EncoderError::TooMuchNesting`] if the limit is hit).
+pub fn write_with_options(
+    value: Value,
+    encoded_cbor: &mut Vec<u8>,
+    max_nest: Option<i8>,
+    options: &WriterOptions,
+) -> Result<usize, EncoderError> {
+    let mut writer = Writer::with_options(encoded_cbor, options.clone());
+    let result = writer.encode_cbor(Node::Value(&value), max_nest);
+    dismantle(value);
+    result?;
+    Ok(writer.bytes_written)
+}
+
+/// Encoder of [`Value`]s for high-throughput encoding, which keeps the scratch buffers used to
+/// sort map keys by their encoding across calls, amortizing their allocations.
+///
+/// Each call to [`Encoder::encode`] produces the same output as [`write_with_options`].
+pub struct Encoder {
+    max_nest: Option<i8>,
+    options: WriterOptions,
+    spare_buffers: Vec<Vec<u8>>,
 }
 
-impl<'a> Writer<'a> {
-    pub fn new(encoded_cbor: &mut Vec<u8>) -> Writer {
-        Writer { encoded_cbor }
+impl Encoder {
+    /// Create an encoder with the default options, supporting up to 127 levels of nesting.
+    pub fn new() -> Encoder {
+        Encoder::with_options(Some(MAX_NESTING_DEPTH), WriterOptions::default())
     }
 
-    fn encode_cbor(
-        &mut self,
-        value: Value,
-        remaining_depth: Option<i8>,
-    ) -> Result<(), EncoderError> {
-        if remaining_depth.map_or(false, |d| d < 0) {
-            return Err(EncoderError::TooMuchNesting);
+    /// Create an encoder with the given options.  If `max_nest` is `Some(max)`, then nested
+    /// structures are only supported up to the given limit (returning
+    /// [`EncoderError::TooMuchNesting`] if the limit is hit).
+    pub fn with_options(max_nest: Option<i8>, options: WriterOptions) -> Encoder {
+        Encoder {
+            max_nest,
+            options,
+            spare_buffers: Vec::new(),
         }
-        let type_label = value.type_label();
-        match value {
-            Value::Unsigned(unsigned) => self.start_item(type_label, unsigned),
-            Value::Negative(negative) => self.start_item(type_label, -(negative + 1) as u64),
-            Value::ByteString(byte_string) => {
-                self.start_item(type_label, byte_string.len() as u64);
-                self.encoded_cbor.extend(byte_string);
-            }
-            Value::TextString(text_string) => {
-                self.start_item(type_label, text_string.len() as u64);
-                self.encoded_cbor.extend(text_string.into_bytes());
-            }
-            Value::Array(array) => {
-                self.start_item(type_label, array.len() as u64);
-                for el in array {
-                    self.encode_cbor(el, remaining_depth.map(|d| d - 1))?;
-                }
-            }
-            Value::Map(map) => {
-                // Canonical ordering requires sorting by encoded keys, so encode them first.
-                let mut map: Vec<_> = map.into_iter().map(|(k, v)| {
-                    let mut encoded_key = Vec::new();
-                    let mut key_writer = Writer::new(&mut encoded_key);
-                    key_writer.encode_cbor(k, remaining_depth.map(|d| d - 1))?;
-                    Ok((encoded_key, v))
-                }).collect::<Result<_, _>>()?;
-                map.sort_by(|a, b| a.0.cmp(&b.0));
-
-                let map_len = map.len();
-                map.dedup_by(|a, b| a.0.eq(&b.0));
-                if map_len != map.len() {
-                    return Err(EncoderError::DuplicateMapKey);
-                }
+    }
 
-                self.start_item(type_label, map_len as u64);
-                for (encoded_key, v) in map {
-                    self.encoded_cbor.extend(encoded_key);
-                    self.encode_cbor(v, remaining_depth.map(|d| d - 1))?;
-                }
-            }
-            Value::Tag(tag, inner_value) => {
-                self.start_item(type_label, tag);
-                self.encode_cbor(*inner_value, remaining_depth.map(|d| d - 1))?;
-            }
-            Value::Simple(simple_value) => self.start_item(type_label, simple_value as u64),
-        }
-        Ok(())
+    /// Convert a [`Value`] to serialized CBOR data without consuming it, appending to the
+    /// provided vector and returning the number of bytes written.
+    pub fn encode(
+        &mut self,
+        value: &Value,
+        encoded_cbor: &mut Vec<u8>,
+    ) -> Result<usize, EncoderError> {
+        let mut writer = Writer::with_options(encoded_cbor, self.options.clone());
+        writer.spare_buffers = core::mem::take(&mut self.spare_buffers);
+        let result = writer.encode_cbor(Node::Value(value), self.max_nest);
+        self.spare_buffers = core::mem::take(&mut writer.spare_buffers);
+        result?;
+        Ok(writer.bytes_written)
     }
+}
 
-    fn start_item(&mut self, type_label: u8, size: u64) {
-        let (mut first_byte, shift) = match size {
-            0..=23 => (size as u8, 0),
-            24..=0xFF => (Constants::ADDITIONAL_INFORMATION_1_BYTE, 1),
-            0x100..=0xFFFF => (Constants::ADDITIONAL_INFORMATION_2_BYTES, 2),
-            0x10000..=0xFFFF_FFFF => (Constants::ADDITIONAL_INFORMATION_4_BYTES, 4),
-            _ => (Constants::ADDITIONAL_INFORMATION_8_BYTES, 8),
-        };
-        first_byte |= type_label << Constants::MAJOR_TYPE_BIT_SHIFT;
-        self.encoded_cbor.push(first_byte);
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder::new()
+    }
+}
 
-        for i in (0..shift).rev() {
-            self.encoded_cbor.push((size >> (i * 8)) as u8);
-        }
+/// Convert a [`Value`] to serialized CBOR data, consuming it along the way and returning a new
+/// vector allocated with the given initial capacity. A suitable capacity can be obtained from
+/// [`Value::encoded_len_hint`].
+pub fn write_with_capacity(value: Value, capacity: usize) -> Result<Vec<u8>, EncoderError> {
+    let mut encoded_cbor = Vec::with_capacity(capacity);
+    write(value, &mut encoded_cbor)?;
+    Ok(encoded_cbor)
+}
+
+/// Convert a [`Value`] to serialized CBOR data, consuming it along the way and returning a new
+/// vector pre-allocated from [`Value::encoded_len_hint`].  Use [`write`] instead to append to an
+/// existing buffer, e.g. to reuse it.  Maximum level of nesting supported is 127; more deeply
+/// nested structures will fail with [`EncoderError::TooMuchNesting`].
+pub fn encode_to_vec(value: Value) -> Result<Vec<u8>, EncoderError> {
+    let capacity = value.encoded_len_hint();
+    write_with_capacity(value, capacity)
+}
+
+/// Convert a [`Value`] to serialized CBOR data, consuming it along the way and returning it in an
+/// allocation of exactly the encoded size, for storing it long-term.  The buffer is pre-allocated
+/// from [`Value::encoded_len_hint`], so that it is only reallocated when floats need more than
+/// half precision.
+pub fn write_to_boxed(value: Value) -> Result<Box<[u8]>, EncoderError> {
+    let capacity = value.encoded_len_hint();
+    Ok(write_with_capacity(value, capacity)?.into_boxed_slice())
+}
+
+/// Convert a [`Value`] to canonical CBOR data and wrap it in a byte string, for signed structures
+/// whose payload is a `bstr` holding a canonically encoded map.  Map entries are sorted whatever
+/// their order in the value.  This is the same as [`Value::into_embedded_cbor`]; use
+/// [`Value::tagged_embedded_cbor`] to also tag the byte string as encoded CBOR (tag 24).
+pub fn canonical_wrapped(value: Value) -> Result<Value, EncoderError> {
+    value.into_embedded_cbor()
+}
+
+/// Convert a [`Value`] to serialized CBOR data, consuming it along the way and streaming it into
+/// the provided sink, returning the number of bytes written. Maximum level of nesting supported is
+/// 127; more deeply nested structures will fail with [`EncoderError::TooMuchNesting`].
+///
+/// Only map keys are buffered, as canonical ordering requires sorting them by their encoding.
+#[cfg(feature = "std")]
+pub fn write_to<W: std::io::Write>(value: Value, sink: &mut W) -> Result<usize, WriteError> {
+    write_nested_to(value, sink, Some(MAX_NESTING_DEPTH))
+}
+
+/// Convert a [`Value`] to serialized CBOR data, consuming it along the way and streaming it into
+/// the provided sink, returning the number of bytes written.  If `max_nest` is `Some(max)`, then
+/// nested structures are only supported up to the given limit (returning
+/// [`EncoderError::TooMuchNesting`] if the limit is hit).
+#[cfg(feature = "std")]
+pub fn write_nested_to<W: std::io::Write>(
+    value: Value,
+    sink: &mut W,
+    max_nest: Option<i8>,
+) -> Result<usize, WriteError> {
+    let mut output = IoOutput(sink);
+    let mut writer = Writer::new(&mut output);
+    let result = writer.encode_cbor(Node::Value(&value), max_nest);
+    dismantle(value);
+    result?;
+    Ok(writer.bytes_written)
+}
+
+/// Hash the serialized CBOR data of a [`Value`] with the digest `D`, consuming the value along the
+/// way and feeding the encoding to the hasher as it is produced instead of buffering it.  The hash
+/// is that of the data [`write`] would produce.  Maximum level of nesting supported is 127; more
+/// deeply nested structures will fail with [`EncoderError::TooMuchNesting`].
+#[cfg(feature = "digest")]
+pub fn encode_and_hash<D: digest::Digest>(value: Value) -> Result<digest::Output<D>, EncoderError> {
+    let mut output = DigestOutput(D::new());
+    let mut writer = Writer::new(&mut output);
+    let result = writer.encode_cbor(Node::Value(&value), Some(MAX_NESTING_DEPTH));
+    dismantle(value);
+    result?;
+    Ok(output.0.finalize())
+}
+
+#[cfg(feature = "digest")]
+impl Value {
+    /// Hash the canonical encoding of the [`Value`] with the digest `D` for content addressing,
+    /// without consuming the value.  The hash is the same as that of [`encode_and_hash`], and fails
+    /// likewise.
+    ///
+    /// The canonical encoding is the core deterministic encoding of RFC 8949 §4.2.1: integers,
+    /// lengths and tags use their shortest form, items have definite lengths, map entries are
+    /// sorted by the bytewise order of their encoded keys, and floats use the shortest form that
+    /// preserves their value, with NaN as `0xF97E00`.  It is part of the stable interface of this
+    /// crate and does not change within a major version (or minor version before 1.0), so that
+    /// hashes stay valid across patch releases.
+    pub fn cid_hash<D: digest::Digest>(&self) -> Result<digest::Output<D>, EncoderError> {
+        let mut output = DigestOutput(D::new());
+        let mut writer = Writer::new(&mut output);
+        writer.encode_cbor(Node::Value(self), Some(MAX_NESTING_DEPTH))?;
+        Ok(output.0.finalize())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use alloc::vec;
+/// Start writing an array of `len` elements to the provided vector, to push them one at a time
+/// without building the whole array first.  Pushing more than `len` elements, or finishing after
+/// fewer, fails with [`EncoderError::LengthMismatch`].
+pub fn array_writer(len: u64, encoded_cbor: &mut Vec<u8>) -> ArrayWriter<'_> {
+    put_header(encoded_cbor, MajorType::Array, Some(len));
+    ArrayWriter {
+        encoded_cbor,
+        remaining_len: Some(len),
+    }
+}
 
-    use super::*;
-    use crate::{
-        cbor_array, cbor_array_vec, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null,
-        cbor_tagged, cbor_text, cbor_true, cbor_undefined,
+/// Start writing an indefinite-length array to the provided vector, to push any number of
+/// elements one at a time.  Note that indefinite-length items are not canonical.
+pub fn indefinite_array_writer(encoded_cbor: &mut Vec<u8>) -> ArrayWriter<'_> {
+    put_header(encoded_cbor, MajorType::Array, None);
+    ArrayWriter {
+        encoded_cbor,
+        remaining_len: None,
+    }
+}
+
+/// Start writing a map of `len` entries to the provided vector, to push them one at a time without
+/// building the whole map first.  As entries are not buffered, they must be pushed in canonical
+/// order, i.e. sorted by the bytewise order of their encoded keys.  Pushing more than `len`
+/// entries, or finishing after fewer, fails with [`EncoderError::LengthMismatch`].
+pub fn map_writer(len: u64, encoded_cbor: &mut Vec<u8>) -> MapWriter<'_> {
+    put_header(encoded_cbor, MajorType::Map, Some(len));
+    MapWriter {
+        encoded_cbor,
+        remaining_len: Some(len),
+        last_key: None,
+    }
+}
+
+/// Start writing an indefinite-length map to the provided vector, to push any number of entries
+/// one at a time in canonical order.  Note that indefinite-length items are not canonical.
+pub fn indefinite_map_writer(encoded_cbor: &mut Vec<u8>) -> MapWriter<'_> {
+    put_header(encoded_cbor, MajorType::Map, None);
+    MapWriter {
+        encoded_cbor,
+        remaining_len: None,
+        last_key: None,
+    }
+}
+
+/// Append an indefinite-length byte string made of the given chunks to the provided vector, for
+/// streaming data whose total length is not known up front.  Each chunk is written as a
+/// definite-length byte string as it comes, followed by the break byte.  Readers decoding
+/// non-strictly reassemble the chunks into a single byte string.  Note that indefinite-length
+/// items are not canonical.
+pub fn write_bytes_chunks<I: IntoIterator<Item = Vec<u8>>>(chunks: I, encoded_cbor: &mut Vec<u8>) {
+    put_header(encoded_cbor, MajorType::ByteString, None);
+    for chunk in chunks {
+        put_header(
+            encoded_cbor,
+            MajorType::ByteString,
+            Some(chunk.len() as u64),
+        );
+        encoded_cbor.extend_from_slice(&chunk);
+    }
+    encoded_cbor.push(Constants::BREAK);
+}
+
+/// Append the head of an item of the given major type, of indefinite length if `len` is `None`.
+fn put_header(encoded_cbor: &mut Vec<u8>, major_type: MajorType, len: Option<u64>) {
+    let mut writer = Writer::new(encoded_cbor);
+    let result = match len {
+        Some(len) => writer.start_item(major_type.to_label(), len),
+        None => writer.start_indefinite_item(major_type.to_label()),
     };
+    // Appending to a vector without an output limit cannot fail.
+    result.unwrap();
+}
 
-    fn write_return(value: Value) -> Option<Vec<u8>> {
-        let mut encoded_cbor = Vec::new();
-        if write(value, &mut encoded_cbor).is_ok() {
-            Some(encoded_cbor)
-        } else {
-            None
+/// Count one more item pushed, failing if a definite-length container is already full.
+fn count_item(remaining_len: &mut Option<u64>) -> Result<(), EncoderError> {
+    match remaining_len {
+        Some(0) => Err(EncoderError::LengthMismatch),
+        Some(len) => {
+            *len -= 1;
+            Ok(())
         }
+        None => Ok(()),
     }
+}
 
-    #[test]
-    fn test_write_unsigned() {
-        let cases = vec![
-            (0, vec![0x00]),
-            (1, vec![0x01]),
-            (10, vec![0x0A]),
-            (23, vec![0x17]),
-            (24, vec![0x18, 0x18]),
-            (25, vec![0x18, 0x19]),
-            (100, vec![0x18, 0x64]),
-            (1000, vec![0x19, 0x03, 0xE8]),
-            (1000000, vec![0x1A, 0x00, 0x0F, 0x42, 0x40]),
-            (0xFFFFFFFF, vec![0x1A, 0xFF, 0xFF, 0xFF, 0xFF]),
-            (
-                0x100000000,
-                vec![0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
-            ),
-            (
-                core::i64::MAX,
-                vec![0x1B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
-            ),
-        ];
-        for (unsigned, correct_cbor) in cases {
-            assert_eq!(write_return(cbor_int!(unsigned)), Some(correct_cbor));
+/// Finish a container, failing if a definite-length container is not full yet.
+fn finish_items(
+    encoded_cbor: &mut Vec<u8>,
+    remaining_len: Option<u64>,
+) -> Result<(), EncoderError> {
+    match remaining_len {
+        Some(0) => Ok(()),
+        Some(_) => Err(EncoderError::LengthMismatch),
+        None => {
+            encoded_cbor.push(Constants::BREAK);
+            Ok(())
         }
     }
+}
 
-    #[test]
-    fn test_write_negative() {
-        let cases = vec![
-            (-1, vec![0x20]),
-            (-10, vec![0x29]),
-            (-23, vec![0x36]),
-            (-24, vec![0x37]),
-            (-25, vec![0x38, 0x18]),
-            (-100, vec![0x38, 0x63]),
-            (-1000, vec![0x39, 0x03, 0xE7]),
-            (-4294967296, vec![0x3A, 0xFF, 0xFF, 0xFF, 0xFF]),
-            (
-                -4294967297,
-                vec![0x3B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
-            ),
-            (
-                core::i64::MIN,
-                vec![0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
-            ),
-        ];
-        for (negative, correct_cbor) in cases {
-            assert_eq!(write_return(cbor_int!(negative)), Some(correct_cbor));
-        }
+/// Handle for writing the elements of an array one at a time, created by [`array_writer`] or
+/// [`indefinite_array_writer`].  The array is only complete once [`ArrayWriter::finish`] succeeds;
+/// after any error, the output is not valid CBOR.
+pub struct ArrayWriter<'a> {
+    encoded_cbor: &'a mut Vec<u8>,
+    /// Number of elements still expected, or `None` for an indefinite-length array.
+    remaining_len: Option<u64>,
+}
+
+impl ArrayWriter<'_> {
+    /// Append the next element.  Maximum level of nesting supported within the array is 127, as
+    /// for [`write`].
+    pub fn push(&mut self, value: Value) -> Result<(), EncoderError> {
+        count_item(&mut self.remaining_len)?;
+        write_nested(value, self.encoded_cbor, Some(MAX_NESTING_DEPTH - 1))
     }
 
-    #[test]
-    fn test_write_byte_string() {
-        let cases = vec![
-            (vec![], vec![0x40]),
-            (
-                vec![0x01, 0x02, 0x03, 0x04],
-                vec![0x44, 0x01, 0x02, 0x03, 0x04],
-            ),
-        ];
-        for (byte_string, correct_cbor) in cases {
-            assert_eq!(write_return(cbor_bytes!(byte_string)), Some(correct_cbor));
-        }
+    /// Finish the array, terminating it if it has an indefinite length.
+    pub fn finish(self) -> Result<(), EncoderError> {
+        finish_items(self.encoded_cbor, self.remaining_len)
     }
+}
 
-    #[test]
-    fn test_write_text_string() {
-        let unicode_3byte = vec![0xE6, 0xB0, 0xB4];
-        let cases = vec![
-            ("", vec![0x60]),
-            ("a", vec![0x61, 0x61]),
-            ("IETF", vec![0x64, 0x49, 0x45, 0x54, 0x46]),
-            ("\"\\", vec![0x62, 0x22, 0x5C]),
-            ("ü", vec![0x62, 0xC3, 0xBC]),
-            (
-                core::str::from_utf8(&unicode_3byte).unwrap(),
-                vec![0x63, 0xE6, 0xB0, 0xB4],
-            ),
-            ("𐅑", vec![0x64, 0xF0, 0x90, 0x85, 0x91]),
-        ];
-        for (text_string, correct_cbor) in cases {
-            assert_eq!(write_return(cbor_text!(text_string)), Some(correct_cbor));
+/// Handle for writing the entries of a map one at a time, created by [`map_writer`] or
+/// [`indefinite_map_writer`].  The map is only complete once [`MapWriter::finish`] succeeds; after
+/// any error, the output is not valid CBOR.
+pub struct MapWriter<'a> {
+    encoded_cbor: &'a mut Vec<u8>,
+    /// Number of entries still expected, or `None` for an indefinite-length map.
+    remaining_len: Option<u64>,
+    /// Encoding of the key of the last entry, which the next key must follow.
+    last_key: Option<Vec<u8>>,
+}
+
+impl MapWriter<'_> {
+    /// Append the next entry, failing with [`EncoderError::DuplicateMapKey`] if its key is that of
+    /// the previous entry, or with [`EncoderError::OutOfOrderKey`] if it sorts before it.  Maximum
+    /// level of nesting supported within the map is 127, as for [`write`].
+    pub fn push(&mut self, key: Value, value: Value) -> Result<(), EncoderError> {
+        let mut encoded_key = Vec::new();
+        write_nested(key, &mut encoded_key, Some(MAX_NESTING_DEPTH - 1))?;
+        if let Some(last_key) = &self.last_key {
+            match encoded_key.cmp(last_key) {
+                core::cmp::Ordering::Less => return Err(EncoderError::OutOfOrderKey),
+                core::cmp::Ordering::Equal => {
+                    return Err(EncoderError::DuplicateMapKey { key: encoded_key })
+                }
+                core::cmp::Ordering::Greater => {}
+            }
         }
+        count_item(&mut self.remaining_len)?;
+        self.encoded_cbor.extend_from_slice(&encoded_key);
+        self.last_key = Some(encoded_key);
+        write_nested(value, self.encoded_cbor, Some(MAX_NESTING_DEPTH - 1))
     }
 
-    #[test]
-    fn test_write_array() {
-        let value_vec: Vec<_> = (1..26).collect();
-        let expected_cbor = vec![
-            0x98, 0x19, // array of 25 elements
-            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
-            0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x18, 0x18, 0x19,
-        ];
-        assert_eq!(
-            write_return(cbor_array_vec!(value_vec)),
-            Some(expected_cbor)
-        );
+    /// Finish the map, terminating it if it has an indefinite length.
+    pub fn finish(self) -> Result<(), EncoderError> {
+        finish_items(self.encoded_cbor, self.remaining_len)
     }
+}
 
-    #[test]
-    fn test_write_map() {
-        let value_map = cbor_map! {
-            0 => "a",
-            23 => "b",
-            24 => "c",
-            core::u8::MAX as i64 => "d",
-            256 => "e",
-            core::u16::MAX as i64 => "f",
-            65536 => "g",
-            core::u32::MAX as i64 => "h",
-            4294967296_i64 => "i",
-            core::i64::MAX => "j",
-            -1 => "k",
-            -24 => "l",
-            -25 => "m",
-            -256 => "n",
-            -257 => "o",
-            -65537 => "p",
-            -4294967296_i64 => "q",
-            -4294967297_i64 => "r",
-            core::i64::MIN => "s",
+/// Types that serialize directly to CBOR data, without building a [`Value`] first.
+///
+/// The output is identical to that of [`write`] for the [`Value`] the type converts into, with
+/// maps sorted by their encoded keys.  It is implemented for integers, booleans, strings,
+/// [`Value`], and slices, vectors and maps of implementing types.  `u8` is deliberately left out
+/// so that `Vec<u8>` and `[u8]` are byte strings, as for [`Value::from`].
+///
+/// Unlike the `Encode` trait targeted by the derive macros of the `oasis-cbor` crate, this does
+/// not consume the data.
+pub trait DirectEncode {
+    /// Serialize the data, appending to the provided vector.  Maximum level of nesting supported
+    /// is 127; more deeply nested structures will fail with [`EncoderError::TooMuchNesting`].
+    fn encode(&self, encoded_cbor: &mut Vec<u8>) -> Result<(), EncoderError> {
+        self.encode_nested(encoded_cbor, MAX_NESTING_DEPTH)
+    }
+
+    /// Serialize the data, appending to the provided vector, with nested items only supported up
+    /// to the given level of nesting as for [`write_nested`].
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError>;
+}
+
+impl<T: DirectEncode + ?Sized> DirectEncode for &T {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        (**self).encode_nested(encoded_cbor, remaining_depth)
+    }
+}
+
+macro_rules! impl_direct_encode_for_uint {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl DirectEncode for $t {
+                fn encode_nested(
+                    &self,
+                    encoded_cbor: &mut Vec<u8>,
+                    remaining_depth: i8,
+                ) -> Result<(), EncoderError> {
+                    check_depth(remaining_depth)?;
+                    put_header(encoded_cbor, MajorType::Unsigned, Some(*self as u64));
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_direct_encode_for_int {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl DirectEncode for $t {
+                fn encode_nested(
+                    &self,
+                    encoded_cbor: &mut Vec<u8>,
+                    remaining_depth: i8,
+                ) -> Result<(), EncoderError> {
+                    check_depth(remaining_depth)?;
+                    match u64::try_from(*self) {
+                        Ok(unsigned) => {
+                            put_header(encoded_cbor, MajorType::Unsigned, Some(unsigned))
+                        }
+                        Err(_) => put_header(
+                            encoded_cbor,
+                            MajorType::Negative,
+                            Some(!(*self as i64) as u64),
+                        ),
+                    }
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_direct_encode_for_uint!(u16, u32, u64);
+impl_direct_encode_for_int!(i8, i16, i32, i64);
+
+impl DirectEncode for bool {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        check_depth(remaining_depth)?;
+        let simple_value = if *self {
+            SimpleValue::TrueValue
+        } else {
+            SimpleValue::FalseValue
+        };
+        put_header(
+            encoded_cbor,
+            MajorType::SimpleFloat,
+            Some(simple_value.to_integer().into()),
+        );
+        Ok(())
+    }
+}
+
+impl DirectEncode for str {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        check_depth(remaining_depth)?;
+        put_header(encoded_cbor, MajorType::TextString, Some(self.len() as u64));
+        encoded_cbor.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+}
+
+impl DirectEncode for alloc::string::String {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        self.as_str().encode_nested(encoded_cbor, remaining_depth)
+    }
+}
+
+impl DirectEncode for [u8] {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        check_depth(remaining_depth)?;
+        put_header(encoded_cbor, MajorType::ByteString, Some(self.len() as u64));
+        encoded_cbor.extend_from_slice(self);
+        Ok(())
+    }
+}
+
+impl DirectEncode for Vec<u8> {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        self.as_slice().encode_nested(encoded_cbor, remaining_depth)
+    }
+}
+
+impl<T: DirectEncode> DirectEncode for [T] {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        check_depth(remaining_depth)?;
+        put_header(encoded_cbor, MajorType::Array, Some(self.len() as u64));
+        for el in self {
+            el.encode_nested(encoded_cbor, remaining_depth - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: DirectEncode> DirectEncode for Vec<T> {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        self.as_slice().encode_nested(encoded_cbor, remaining_depth)
+    }
+}
+
+impl<K: DirectEncode, V: DirectEncode> DirectEncode for alloc::collections::BTreeMap<K, V> {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        encode_map_entries(self.iter(), self.len(), encoded_cbor, remaining_depth)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: DirectEncode, V: DirectEncode, S> DirectEncode for std::collections::HashMap<K, V, S> {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        encode_map_entries(self.iter(), self.len(), encoded_cbor, remaining_depth)
+    }
+}
+
+impl DirectEncode for Value {
+    fn encode_nested(
+        &self,
+        encoded_cbor: &mut Vec<u8>,
+        remaining_depth: i8,
+    ) -> Result<(), EncoderError> {
+        write_nested_ref(self, encoded_cbor, Some(remaining_depth))
+    }
+}
+
+/// Fail with [`EncoderError::TooMuchNesting`] for an item nested deeper than allowed, as the writer
+/// does.
+fn check_depth(remaining_depth: i8) -> Result<(), EncoderError> {
+    if remaining_depth < 0 {
+        return Err(EncoderError::TooMuchNesting);
+    }
+    Ok(())
+}
+
+/// Serialize the entries of a map, sorted by their encoded keys.
+fn encode_map_entries<'a, K: DirectEncode + 'a, V: DirectEncode + 'a>(
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    len: usize,
+    encoded_cbor: &mut Vec<u8>,
+    remaining_depth: i8,
+) -> Result<(), EncoderError> {
+    check_depth(remaining_depth)?;
+    let mut encoded_entries = Vec::with_capacity(len);
+    for (k, v) in entries {
+        let mut encoded_key = Vec::new();
+        k.encode_nested(&mut encoded_key, remaining_depth - 1)?;
+        encoded_entries.push((encoded_key, v));
+    }
+    encoded_entries.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some(w) = encoded_entries.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(EncoderError::DuplicateMapKey {
+            key: w[0].0.clone(),
+        });
+    }
+    put_header(encoded_cbor, MajorType::Map, Some(len as u64));
+    for (encoded_key, v) in encoded_entries {
+        encoded_cbor.extend_from_slice(&encoded_key);
+        v.encode_nested(encoded_cbor, remaining_depth - 1)?;
+    }
+    Ok(())
+}
+
+/// Options controlling how values are serialized.
+///
+/// The default options produce canonical CBOR.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriterOptions {
+    /// Emit arrays, maps, byte strings and text strings as indefinite-length items terminated by a
+    /// break byte instead of prefixing them with their length.
+    pub indefinite_length: bool,
+    /// Maximum length of each chunk of an indefinite-length byte or text string. Text strings are
+    /// only split at character boundaries. If `None`, strings are emitted as a single chunk.
+    pub max_chunk_len: Option<usize>,
+    /// Emit map entries in their original order instead of sorting them by their encoded keys.
+    pub preserve_map_order: bool,
+    /// Accept duplicate map keys instead of failing with [`EncoderError::DuplicateMapKey`]. This is
+    /// only honored when `preserve_map_order` is set, as canonical maps never contain duplicates.
+    pub allow_duplicate_keys: bool,
+    /// Order in which map entries are sorted by their encoded keys, unless `preserve_map_order` is
+    /// set.
+    pub ordering: MapOrdering,
+    /// Trust that map entries are already sorted by their encoded keys as dictated by `ordering`,
+    /// without duplicates, and emit them directly instead of sorting them. This is checked only
+    /// in debug builds. Maps built from a `BTreeMap<Value, Value>` (with canonicalized keys) or
+    /// by [`Value::canonicalize`] are sorted for the default ordering.
+    pub presorted_maps: bool,
+    /// Emit null as undefined. As `None` is encoded as null by convention, this serves peers that
+    /// expect undefined for absent values; readers treat both alike.
+    pub write_undefined_for_none: bool,
+    /// Apply Unicode Normalization Form C to text strings, so that canonically equivalent strings
+    /// are written alike and collide as map keys, failing with [`EncoderError::DuplicateMapKey`].
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_text: bool,
+    /// Maximum number of bytes to write, failing with [`EncoderError::OutputTooLarge`] as soon as
+    /// the encoding would exceed it, for bounding the memory used by values of untrusted size.
+    /// Buffered map keys count towards the limit.  If `None`, the output is unbounded.
+    pub max_output_len: Option<usize>,
+    /// Encode every NaN as the half-precision quiet NaN `0xF97E00`, so that NaNs differing only in
+    /// their sign or payload are written (and hashed) alike, as deterministic encoding requires.
+    /// Otherwise NaNs keep their sign and payload, in the shortest form preserving them.  Both
+    /// zeros keep their sign either way, as CBOR tells them apart.  Set by default.
+    pub canonical_nan: bool,
+    /// Width of the argument of unsigned and negative integers.  Any width but the default
+    /// [`IntWidth::Minimal`] produces non-canonical CBOR, for counterparts expecting fixed-width
+    /// integers.  Lengths and tag numbers are unaffected.
+    pub int_width: IntWidth,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            indefinite_length: false,
+            max_chunk_len: None,
+            preserve_map_order: false,
+            allow_duplicate_keys: false,
+            ordering: MapOrdering::default(),
+            presorted_maps: false,
+            write_undefined_for_none: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalize_text: false,
+            max_output_len: None,
+            canonical_nan: true,
+            int_width: IntWidth::default(),
+        }
+    }
+}
+
+/// Rule for sorting map entries by their encoded keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MapOrdering {
+    /// Bytewise lexicographic order of the encoded keys, as in RFC 8949 §4.2.1 and CTAP2.
+    #[default]
+    BytewiseLexicographic,
+    /// Shorter encoded keys first, then bytewise lexicographic order, as in the canonical CBOR of
+    /// RFC 7049 §3.9 (see also RFC 8949 §4.2.3).
+    LengthFirst,
+}
+
+/// Width of the encoded argument of integers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IntWidth {
+    /// Shortest form holding the integer, as canonical CBOR requires.
+    #[default]
+    Minimal,
+    /// Eight-byte form, whatever the magnitude of the integer.
+    Fixed64,
+}
+
+/// Destination for serialized CBOR data.
+trait Output {
+    type Error: From<EncoderError>;
+
+    /// Append the given bytes to the output.
+    fn put(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl Output for Vec<u8> {
+    type Error = EncoderError;
+
+    fn put(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Adapter that forwards serialized CBOR data to an [`std::io::Write`] sink.
+#[cfg(feature = "std")]
+struct IoOutput<'a, W>(&'a mut W);
+
+#[cfg(feature = "std")]
+impl<'a, W: std::io::Write> Output for IoOutput<'a, W> {
+    type Error = WriteError;
+
+    fn put(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write_all(data).map_err(WriteError::Io)
+    }
+}
+
+/// Adapter that feeds serialized CBOR data to a hasher.
+#[cfg(feature = "digest")]
+struct DigestOutput<D>(D);
+
+#[cfg(feature = "digest")]
+impl<D: digest::Digest> Output for DigestOutput<D> {
+    type Error = EncoderError;
+
+    fn put(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.update(data);
+        Ok(())
+    }
+}
+
+/// Value being encoded, borrowed from either a [`Value`] or a [`ValueRef`].
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Value(&'a Value),
+    Ref(&'a ValueRef<'a>),
+}
+
+/// Contents of a [`Node`], with nested values left as nodes.
+enum Item<'a> {
+    Unsigned(u64),
+    Negative(i128),
+    ByteString(&'a [u8]),
+    TextString(&'a str),
+    Array(Nodes<'a>),
+    Map(Entries<'a>),
+    Tag(u64, Node<'a>),
+    Simple(&'a SimpleValue),
+    Float(f64),
+    PreEncoded(&'a [u8]),
+}
+
+/// Elements of an array being encoded.
+#[derive(Clone, Copy)]
+enum Nodes<'a> {
+    Values(&'a [Value]),
+    Refs(&'a [ValueRef<'a>]),
+}
+
+/// Entries of a map being encoded.
+#[derive(Clone, Copy)]
+enum Entries<'a> {
+    Values(&'a [(Value, Value)]),
+    Refs(&'a [(ValueRef<'a>, ValueRef<'a>)]),
+}
+
+impl<'a> Node<'a> {
+    fn item(self) -> Item<'a> {
+        match self {
+            Node::Value(value) => match value {
+                Value::Unsigned(unsigned) => Item::Unsigned(*unsigned),
+                Value::Negative(negative) => Item::Negative(*negative),
+                Value::ByteString(byte_string) => Item::ByteString(byte_string),
+                Value::TextString(text_string) => Item::TextString(text_string),
+                Value::Array(array) => Item::Array(Nodes::Values(array)),
+                Value::Map(map) => Item::Map(Entries::Values(map)),
+                Value::Tag(tag, inner_value) => Item::Tag(*tag, Node::Value(inner_value)),
+                Value::Simple(simple_value) => Item::Simple(simple_value),
+                Value::Float(float) => Item::Float(*float),
+            },
+            Node::Ref(value) => match value {
+                ValueRef::Unsigned(unsigned) => Item::Unsigned(*unsigned),
+                ValueRef::Negative(negative) => Item::Negative(*negative),
+                ValueRef::ByteString(byte_string) => Item::ByteString(byte_string),
+                ValueRef::TextString(text_string) => Item::TextString(text_string),
+                ValueRef::Array(array) => Item::Array(Nodes::Refs(array)),
+                ValueRef::Map(map) => Item::Map(Entries::Refs(map)),
+                ValueRef::Tag(tag, inner_value) => Item::Tag(*tag, Node::Ref(inner_value)),
+                ValueRef::Simple(simple_value) => Item::Simple(simple_value),
+                ValueRef::Float(float) => Item::Float(*float),
+                ValueRef::Value(value) => Node::Value(value).item(),
+                ValueRef::PreEncoded(encoded_cbor) => Item::PreEncoded(encoded_cbor),
+            },
+        }
+    }
+}
+
+impl<'a> Nodes<'a> {
+    fn len(self) -> usize {
+        match self {
+            Nodes::Values(values) => values.len(),
+            Nodes::Refs(values) => values.len(),
+        }
+    }
+
+    fn get(self, index: usize) -> Node<'a> {
+        match self {
+            Nodes::Values(values) => Node::Value(&values[index]),
+            Nodes::Refs(values) => Node::Ref(&values[index]),
+        }
+    }
+}
+
+impl<'a> Entries<'a> {
+    fn len(self) -> usize {
+        match self {
+            Entries::Values(entries) => entries.len(),
+            Entries::Refs(entries) => entries.len(),
+        }
+    }
+
+    fn get(self, index: usize) -> (Node<'a>, Node<'a>) {
+        match self {
+            Entries::Values(entries) => (
+                Node::Value(&entries[index].0),
+                Node::Value(&entries[index].1),
+            ),
+            Entries::Refs(entries) => (Node::Ref(&entries[index].0), Node::Ref(&entries[index].1)),
+        }
+    }
+
+    fn iter(self) -> impl DoubleEndedIterator<Item = (Node<'a>, Node<'a>)> {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+}
+
+/// Pending step of encoding a value.
+enum Work<'a> {
+    /// Encode the value with the given remaining depth.
+    Encode(Node<'a>, Option<i8>),
+    /// Append already encoded data.
+    Put(Vec<u8>),
+    /// Terminate an indefinite-length item.
+    Break,
+    /// Start encoding a map key into its own buffer.
+    StartKey,
+    /// Finish encoding a map key, collecting its buffer.
+    EndKey,
+    /// Sort the collected keys of a map and schedule its entries.
+    FinishMap(Vec<Node<'a>>, Option<i8>),
+}
+
+struct Writer<'a, O> {
+    output: &'a mut O,
+    options: WriterOptions,
+    bytes_written: usize,
+    /// Length of the encoded map keys that are buffered but not written yet.
+    pending_key_len: usize,
+    /// Buffers of the map keys currently being encoded, innermost last. While any is present,
+    /// data goes to it rather than to the output.
+    key_buffers: Vec<Vec<u8>>,
+    /// Emptied buffers of map keys already written, reused for further keys.
+    spare_buffers: Vec<Vec<u8>>,
+}
+
+impl<'a, O: Output> Writer<'a, O> {
+    pub fn new(output: &mut O) -> Writer<O> {
+        Writer::with_options(output, WriterOptions::default())
+    }
+
+    pub fn with_options(output: &mut O, options: WriterOptions) -> Writer<O> {
+        Writer {
+            output,
+            options,
+            bytes_written: 0,
+            pending_key_len: 0,
+            key_buffers: Vec::new(),
+            spare_buffers: Vec::new(),
+        }
+    }
+
+    fn put(&mut self, data: &[u8]) -> Result<(), O::Error> {
+        if let Some(max_output_len) = self.options.max_output_len {
+            if self.bytes_written + self.pending_key_len + data.len() > max_output_len {
+                return Err(EncoderError::OutputTooLarge.into());
+            }
+        }
+        if let Some(key_buffer) = self.key_buffers.last_mut() {
+            key_buffer.extend_from_slice(data);
+            self.pending_key_len += data.len();
+            return Ok(());
+        }
+        self.output.put(data)?;
+        self.bytes_written += data.len();
+        Ok(())
+    }
+
+    /// Encode a value, appending it to the output.  Nested values are processed through an
+    /// explicit work list rather than by recursion, so that arbitrarily deep nesting cannot
+    /// overflow the stack.
+    fn encode_cbor(&mut self, node: Node, remaining_depth: Option<i8>) -> Result<(), O::Error> {
+        self.key_buffers.clear();
+        self.pending_key_len = 0;
+        let mut work = vec![Work::Encode(node, remaining_depth)];
+        // Encoded keys of the maps currently being sorted, innermost last.
+        let mut encoded_keys: Vec<Vec<Vec<u8>>> = Vec::new();
+        while let Some(item) = work.pop() {
+            match item {
+                Work::Encode(node, remaining_depth) => {
+                    self.encode_item(node, remaining_depth, &mut work, &mut encoded_keys)?
+                }
+                Work::Put(mut data) => {
+                    self.pending_key_len -= data.len();
+                    self.put(&data)?;
+                    data.clear();
+                    self.spare_buffers.push(data);
+                }
+                Work::Break => self.put(&[Constants::BREAK])?,
+                Work::StartKey => {
+                    let key_buffer = self.spare_buffers.pop().unwrap_or_default();
+                    self.key_buffers.push(key_buffer);
+                }
+                Work::EndKey => {
+                    let encoded_key = self.key_buffers.pop().unwrap();
+                    encoded_keys.last_mut().unwrap().push(encoded_key);
+                }
+                Work::FinishMap(values, remaining_depth) => {
+                    let map = encoded_keys
+                        .pop()
+                        .unwrap()
+                        .into_iter()
+                        .zip(values)
+                        .collect();
+                    let map = self.order_map_entries(map)?;
+                    if self.options.indefinite_length {
+                        self.start_indefinite_item(MajorType::Map.to_label())?;
+                        work.push(Work::Break);
+                    } else {
+                        self.start_item(MajorType::Map.to_label(), map.len() as u64)?;
+                    }
+                    for (encoded_key, v) in map.into_iter().rev() {
+                        work.push(Work::Encode(v, remaining_depth.map(|d| d - 1)));
+                        work.push(Work::Put(encoded_key));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a single item, scheduling any nested values on the work list.
+    fn encode_item<'b>(
+        &mut self,
+        node: Node<'b>,
+        remaining_depth: Option<i8>,
+        work: &mut Vec<Work<'b>>,
+        encoded_keys: &mut Vec<Vec<Vec<u8>>>,
+    ) -> Result<(), O::Error> {
+        if remaining_depth.map_or(false, |d| d < 0) {
+            return Err(EncoderError::TooMuchNesting.into());
+        }
+        match node.item() {
+            Item::Unsigned(unsigned) => {
+                self.encode_integer(MajorType::Unsigned.to_label(), unsigned)?
+            }
+            Item::Negative(negative) => {
+                debug_assert!(
+                    (-(1i128 << 64)..0).contains(&negative),
+                    "negative integer out of range -2^64..=-1"
+                );
+                self.encode_integer(MajorType::Negative.to_label(), -(negative + 1) as u64)?
+            }
+            Item::ByteString(byte_string) if self.options.indefinite_length => {
+                let type_label = MajorType::ByteString.to_label();
+                self.start_indefinite_item(type_label)?;
+                let chunk_len = self.options.max_chunk_len.unwrap_or(usize::MAX).max(1);
+                for chunk in byte_string.chunks(chunk_len) {
+                    self.start_item(type_label, chunk.len() as u64)?;
+                    self.put(chunk)?;
+                }
+                self.put(&[Constants::BREAK])?;
+            }
+            Item::ByteString(byte_string) => {
+                self.start_item(MajorType::ByteString.to_label(), byte_string.len() as u64)?;
+                self.put(byte_string)?;
+            }
+            #[cfg(feature = "unicode-normalization")]
+            Item::TextString(text_string)
+                if self.options.normalize_text && !unicode_normalization::is_nfc(text_string) =>
+            {
+                use unicode_normalization::UnicodeNormalization;
+                let normalized: alloc::string::String = text_string.nfc().collect();
+                self.encode_text(&normalized)?
+            }
+            Item::TextString(text_string) => self.encode_text(text_string)?,
+            Item::Array(array) => {
+                let type_label = MajorType::Array.to_label();
+                if self.options.indefinite_length {
+                    self.start_indefinite_item(type_label)?;
+                    work.push(Work::Break);
+                } else {
+                    self.start_item(type_label, array.len() as u64)?;
+                }
+                for i in (0..array.len()).rev() {
+                    work.push(Work::Encode(array.get(i), remaining_depth.map(|d| d - 1)));
+                }
+            }
+            Item::Map(map)
+                if self.options.presorted_maps
+                    && !self.options.preserve_map_order
+                    && remaining_depth.map_or(true, |d| d > 0) =>
+            {
+                debug_assert!(
+                    self.is_presorted(map),
+                    "map entries are not sorted by their encoded keys"
+                );
+                self.schedule_sorted_map(map.iter().collect(), remaining_depth, work)?;
+            }
+            Item::Map(map)
+                if !self.options.preserve_map_order
+                    && remaining_depth.map_or(true, |d| d > 0)
+                    && integer_keys(map) =>
+            {
+                // Integer keys of a single major type sort by their value, no need to encode them.
+                let map = order_integer_map_entries(map.iter().collect())
+                    .map_err(|key| self.duplicate_key_error(key))?;
+                self.schedule_sorted_map(map, remaining_depth, work)?;
+            }
+            Item::Map(map) => {
+                // Canonical ordering requires sorting by encoded keys, so encode them first.
+                let (keys, values): (Vec<_>, Vec<_>) = map.iter().unzip();
+                encoded_keys.push(Vec::with_capacity(keys.len()));
+                work.push(Work::FinishMap(values, remaining_depth));
+                for k in keys.into_iter().rev() {
+                    work.push(Work::EndKey);
+                    work.push(Work::Encode(k, remaining_depth.map(|d| d - 1)));
+                    work.push(Work::StartKey);
+                }
+            }
+            Item::Tag(tag, inner_value) => {
+                self.start_item(MajorType::Tag.to_label(), tag)?;
+                work.push(Work::Encode(inner_value, remaining_depth.map(|d| d - 1)));
+            }
+            Item::Simple(simple_value) => {
+                let simple_value = match simple_value {
+                    SimpleValue::NullValue if self.options.write_undefined_for_none => {
+                        SimpleValue::Undefined
+                    }
+                    _ => simple_value.clone(),
+                };
+                if let SimpleValue::Unassigned(20..=31) = simple_value {
+                    return Err(EncoderError::InvalidSimpleValue.into());
+                }
+                self.start_item(
+                    MajorType::SimpleFloat.to_label(),
+                    simple_value.to_integer().into(),
+                )?
+            }
+            Item::Float(float) => self.encode_float(float)?,
+            Item::PreEncoded(encoded_cbor) => {
+                debug_assert!(
+                    is_single_item(encoded_cbor),
+                    "pre-encoded data is not a single well-formed data item"
+                );
+                debug_assert!(
+                    self.key_buffers.is_empty() || crate::reader::is_canonical(encoded_cbor),
+                    "pre-encoded data within a map key is not canonical"
+                );
+                self.put(encoded_cbor)?
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a text string, in chunks if the options ask for indefinite-length items.
+    fn encode_text(&mut self, text_string: &str) -> Result<(), O::Error> {
+        let type_label = MajorType::TextString.to_label();
+        if !self.options.indefinite_length {
+            self.start_item(type_label, text_string.len() as u64)?;
+            return self.put(text_string.as_bytes());
+        }
+        self.start_indefinite_item(type_label)?;
+        let chunk_len = self.options.max_chunk_len.unwrap_or(usize::MAX).max(1);
+        let mut remaining = text_string;
+        while !remaining.is_empty() {
+            // Split at the last character boundary within the chunk length, but always
+            // make progress even if a single character is longer than the chunk length.
+            let mut split = chunk_len.min(remaining.len());
+            while !remaining.is_char_boundary(split) {
+                split -= 1;
+            }
+            if split == 0 {
+                split = remaining.chars().next().map_or(0, char::len_utf8);
+            }
+            let (chunk, rest) = remaining.split_at(split);
+            self.start_item(type_label, chunk.len() as u64)?;
+            self.put(chunk.as_bytes())?;
+            remaining = rest;
+        }
+        self.put(&[Constants::BREAK])
+    }
+
+    /// Start a map whose entries are already in order, scheduling them on the work list.
+    fn schedule_sorted_map<'b>(
+        &mut self,
+        map: Vec<(Node<'b>, Node<'b>)>,
+        remaining_depth: Option<i8>,
+        work: &mut Vec<Work<'b>>,
+    ) -> Result<(), O::Error> {
+        if self.options.indefinite_length {
+            self.start_indefinite_item(MajorType::Map.to_label())?;
+            work.push(Work::Break);
+        } else {
+            self.start_item(MajorType::Map.to_label(), map.len() as u64)?;
+        }
+        for (k, v) in map.into_iter().rev() {
+            work.push(Work::Encode(v, remaining_depth.map(|d| d - 1)));
+            work.push(Work::Encode(k, remaining_depth.map(|d| d - 1)));
+        }
+        Ok(())
+    }
+
+    /// Return whether map entries are strictly increasing by their encoded keys, as dictated by the
+    /// options.  Keys that fail to encode are left for the actual encoding to report.
+    fn is_presorted(&self, map: Entries) -> bool {
+        let mut encoded_keys = Vec::with_capacity(map.len());
+        for (k, _) in map.iter() {
+            let mut encoded_key = Vec::new();
+            let mut key_writer = Writer::with_options(&mut encoded_key, self.options.clone());
+            if key_writer.encode_cbor(k, None).is_err() {
+                return true;
+            }
+            encoded_keys.push(encoded_key);
+        }
+        encoded_keys
+            .windows(2)
+            .all(|w| self.compare_encoded_keys(&w[0], &w[1]) == core::cmp::Ordering::Less)
+    }
+
+    /// Compare encoded map keys as dictated by the options.
+    fn compare_encoded_keys(&self, a: &[u8], b: &[u8]) -> core::cmp::Ordering {
+        match self.options.ordering {
+            MapOrdering::BytewiseLexicographic => a.cmp(b),
+            MapOrdering::LengthFirst => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+        }
+    }
+
+    /// Order map entries (with already encoded keys) as dictated by the options, checking for
+    /// duplicate keys.
+    fn order_map_entries<'b>(
+        &self,
+        mut map: Vec<(Vec<u8>, Node<'b>)>,
+    ) -> Result<Vec<(Vec<u8>, Node<'b>)>, EncoderError> {
+        if self.options.preserve_map_order {
+            if !self.options.allow_duplicate_keys {
+                let mut keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+                keys.sort();
+                if let Some(w) = keys.windows(2).find(|w| w[0] == w[1]) {
+                    return Err(EncoderError::DuplicateMapKey { key: w[0].clone() });
+                }
+            }
+            return Ok(map);
+        }
+
+        map.sort_by(|a, b| self.compare_encoded_keys(&a.0, &b.0));
+        if let Some(w) = map.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(EncoderError::DuplicateMapKey {
+                key: w[0].0.clone(),
+            });
+        }
+        Ok(map)
+    }
+
+    /// Return the error for a map key occurring more than once, carrying its encoding under the
+    /// options.
+    fn duplicate_key_error(&self, key: Node) -> EncoderError {
+        let options = WriterOptions {
+            max_output_len: None,
+            ..self.options.clone()
+        };
+        let mut encoded_key = Vec::new();
+        match Writer::with_options(&mut encoded_key, options).encode_cbor(key, None) {
+            Ok(()) => EncoderError::DuplicateMapKey { key: encoded_key },
+            Err(e) => e,
+        }
+    }
+
+    /// Encode a float using the shortest representation that preserves its value.
+    fn encode_float(&mut self, float: f64) -> Result<(), O::Error> {
+        let (additional_info, bits) = if float.is_nan() && !self.options.canonical_nan {
+            shortest_nan_bits(float)
+        } else {
+            shortest_float_bits(float)
+        };
+        self.put(&[(7 << Constants::MAJOR_TYPE_BIT_SHIFT) | additional_info])?;
+        self.put(&bits.to_be_bytes()[8 - float_len(additional_info)..])
+    }
+
+    /// Encode the head of an integer, in the width the options ask for.
+    fn encode_integer(&mut self, type_label: u8, argument: u64) -> Result<(), O::Error> {
+        match self.options.int_width {
+            IntWidth::Minimal => self.start_item(type_label, argument),
+            IntWidth::Fixed64 => {
+                self.put(&[(type_label << Constants::MAJOR_TYPE_BIT_SHIFT)
+                    | Constants::ADDITIONAL_INFORMATION_8_BYTES])?;
+                self.put(&argument.to_be_bytes())
+            }
+        }
+    }
+
+    fn start_indefinite_item(&mut self, type_label: u8) -> Result<(), O::Error> {
+        self.put(&[(type_label << Constants::MAJOR_TYPE_BIT_SHIFT)
+            | Constants::ADDITIONAL_INFORMATION_INDEFINITE_LENGTH])
+    }
+
+    fn start_item(&mut self, type_label: u8, size: u64) -> Result<(), O::Error> {
+        let (mut first_byte, shift) = match size {
+            0..=23 => (size as u8, 0),
+            24..=0xFF => (Constants::ADDITIONAL_INFORMATION_1_BYTE, 1),
+            0x100..=0xFFFF => (Constants::ADDITIONAL_INFORMATION_2_BYTES, 2),
+            0x10000..=0xFFFF_FFFF => (Constants::ADDITIONAL_INFORMATION_4_BYTES, 4),
+            _ => (Constants::ADDITIONAL_INFORMATION_8_BYTES, 8),
+        };
+        first_byte |= type_label << Constants::MAJOR_TYPE_BIT_SHIFT;
+        self.put(&[first_byte])?;
+        self.put(&size.to_be_bytes()[8 - shift..])
+    }
+}
+
+/// Drop a consumed value piece by piece, as the recursive drop of deeply nested values would
+/// overflow the stack.
+fn dismantle(value: Value) {
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(array) => pending.extend(array),
+            Value::Map(map) => {
+                for (k, v) in map {
+                    pending.push(k);
+                    pending.push(v);
+                }
+            }
+            Value::Tag(_, inner_value) => pending.push(*inner_value),
+            _ => (),
+        }
+    }
+}
+
+/// Return whether serialized data holds exactly one well-formed data item.
+fn is_single_item(encoded_cbor: &[u8]) -> bool {
+    let mut scanner = Scanner::new(encoded_cbor);
+    scanner.skip_item().is_ok() && scanner.is_empty()
+}
+
+/// Return whether all keys of a map are unsigned integers, or all are negative integers.
+fn integer_keys(map: Entries) -> bool {
+    map.iter()
+        .all(|(k, _)| matches!(k.item(), Item::Unsigned(_)))
+        || map
+            .iter()
+            .all(|(k, _)| matches!(k.item(), Item::Negative(_)))
+}
+
+/// Sort map entries with integer keys of a single major type in canonical order, failing with a
+/// key occurring more than once.  For such keys, both the bytewise and the length-first order of
+/// the encodings are the order of the encoded argument, which is the value for unsigned integers
+/// and `-1 - n` for negative ones.
+fn order_integer_map_entries<'a>(
+    mut map: Vec<(Node<'a>, Node<'a>)>,
+) -> Result<Vec<(Node<'a>, Node<'a>)>, Node<'a>> {
+    let argument = |k: &Node| match k.item() {
+        Item::Unsigned(unsigned) => unsigned as i128,
+        Item::Negative(negative) => -(negative + 1),
+        _ => unreachable!(),
+    };
+    map.sort_by_key(|(k, _)| argument(k));
+    if let Some(w) = map
+        .windows(2)
+        .find(|w| argument(&w[0].0) == argument(&w[1].0))
+    {
+        return Err(w[0].0);
+    }
+    Ok(map)
+}
+
+/// Return the additional information and big-endian payload of the shortest encoding of a float
+/// that preserves its value exactly, trying half, single and double precision in turn.  A
+/// narrower form is only chosen if widening it back yields the same bits, so that both zeros keep
+/// their sign and no rounding occurs.  All NaNs map to the canonical half-precision quiet NaN.
+pub fn shortest_float_encoding(float: f64) -> (u8, Vec<u8>) {
+    let (additional_info, bits) = shortest_float_bits(float);
+    let len = float_len(additional_info);
+    (additional_info, bits.to_be_bytes()[8 - len..].to_vec())
+}
+
+/// Return the length of the payload of a float with the given additional information.
+fn float_len(additional_info: u8) -> usize {
+    match additional_info {
+        Constants::ADDITIONAL_INFORMATION_2_BYTES => 2,
+        Constants::ADDITIONAL_INFORMATION_4_BYTES => 4,
+        _ => 8,
+    }
+}
+
+/// Return the additional information and payload of the shortest encoding of a NaN that keeps its
+/// sign and payload, narrowing it only if the dropped low bits of the payload are all zero.
+fn shortest_nan_bits(float: f64) -> (u8, u64) {
+    let bits = float.to_bits();
+    let sign = bits >> 63;
+    let mantissa = bits & 0x000F_FFFF_FFFF_FFFF;
+    if mantissa & ((1 << 42) - 1) == 0 {
+        (
+            Constants::ADDITIONAL_INFORMATION_2_BYTES,
+            (sign << 15) | 0x7C00 | (mantissa >> 42),
+        )
+    } else if mantissa & ((1 << 29) - 1) == 0 {
+        (
+            Constants::ADDITIONAL_INFORMATION_4_BYTES,
+            (sign << 31) | 0x7F80_0000 | (mantissa >> 29),
+        )
+    } else {
+        (Constants::ADDITIONAL_INFORMATION_8_BYTES, bits)
+    }
+}
+
+/// Return the additional information and payload of the shortest encoding of a float, as in
+/// [`shortest_float_encoding`] but without allocating.  Comparing the results orders floats like
+/// their encodings.
+pub(crate) fn shortest_float_bits(float: f64) -> (u8, u64) {
+    if float.is_nan() {
+        (Constants::ADDITIONAL_INFORMATION_2_BYTES, 0x7E00)
+    } else if let Some(half) = f64_to_f16(float) {
+        (Constants::ADDITIONAL_INFORMATION_2_BYTES, half as u64)
+    } else if ((float as f32) as f64).to_bits() == float.to_bits() {
+        (
+            Constants::ADDITIONAL_INFORMATION_4_BYTES,
+            (float as f32).to_bits() as u64,
+        )
+    } else {
+        (Constants::ADDITIONAL_INFORMATION_8_BYTES, float.to_bits())
+    }
+}
+
+/// Convert a non-NaN float to the bits of an IEEE 754 half-precision float, if it can be
+/// represented exactly.
+fn f64_to_f16(float: f64) -> Option<u16> {
+    let bits = float.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let mantissa = bits & 0x000F_FFFF_FFFF_FFFF;
+
+    match exponent {
+        // Zero (subnormal doubles are too small for half precision).
+        0 if mantissa == 0 => Some(sign),
+        0 => None,
+        // Infinity.
+        0x7FF if mantissa == 0 => Some(sign | 0x7C00),
+        0x7FF => None,
+        _ => {
+            let exponent = exponent - 1023;
+            if (-14..=15).contains(&exponent) {
+                // Normal half: the low 42 bits of the mantissa are dropped.
+                if mantissa & ((1 << 42) - 1) != 0 {
+                    return None;
+                }
+                Some(sign | (((exponent + 15) as u16) << 10) | (mantissa >> 42) as u16)
+            } else if (-24..-14).contains(&exponent) {
+                // Subnormal half: the implicit leading bit becomes part of the mantissa.
+                let significand = mantissa | (1 << 52);
+                let shift = 28 - exponent;
+                if significand & ((1 << shift) - 1) != 0 {
+                    return None;
+                }
+                Some(sign | (significand >> shift) as u16)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{boxed::Box, vec};
+
+    use super::*;
+    use crate::{
+        cbor_array, cbor_array_vec, cbor_bytes, cbor_false, cbor_int, cbor_map, cbor_null,
+        cbor_simple, cbor_tagged, cbor_text, cbor_true, cbor_undefined,
+    };
+
+    fn write_unbounded(value: Value) -> Vec<u8> {
+        let mut encoded_cbor = Vec::new();
+        write_nested(value, &mut encoded_cbor, None).unwrap();
+        encoded_cbor
+    }
+
+    fn write_return(value: Value) -> Option<Vec<u8>> {
+        let mut encoded_cbor = Vec::new();
+        if write(value, &mut encoded_cbor).is_ok() {
+            Some(encoded_cbor)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_write_unsigned() {
+        let cases = vec![
+            (0, vec![0x00]),
+            (1, vec![0x01]),
+            (10, vec![0x0A]),
+            (23, vec![0x17]),
+            (24, vec![0x18, 0x18]),
+            (25, vec![0x18, 0x19]),
+            (100, vec![0x18, 0x64]),
+            (1000, vec![0x19, 0x03, 0xE8]),
+            (1000000, vec![0x1A, 0x00, 0x0F, 0x42, 0x40]),
+            (0xFFFFFFFF, vec![0x1A, 0xFF, 0xFF, 0xFF, 0xFF]),
+            (
+                0x100000000,
+                vec![0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+            ),
+            (
+                core::i64::MAX,
+                vec![0x1B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+        ];
+        for (unsigned, correct_cbor) in cases {
+            assert_eq!(write_return(cbor_int!(unsigned)), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_negative() {
+        let cases = vec![
+            (-1, vec![0x20]),
+            (-10, vec![0x29]),
+            (-23, vec![0x36]),
+            (-24, vec![0x37]),
+            (-25, vec![0x38, 0x18]),
+            (-100, vec![0x38, 0x63]),
+            (-1000, vec![0x39, 0x03, 0xE7]),
+            (-4294967296, vec![0x3A, 0xFF, 0xFF, 0xFF, 0xFF]),
+            (
+                -4294967297,
+                vec![0x3B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+            ),
+            (
+                core::i64::MIN,
+                vec![0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+        ];
+        for (negative, correct_cbor) in cases {
+            assert_eq!(write_return(cbor_int!(negative)), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_negative_extremes() {
+        assert_eq!(
+            write_return(Value::negative(i64::MIN)),
+            Some(vec![0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])
+        );
+        assert_eq!(
+            write_return(Value::Negative(-(1i128 << 64))),
+            Some(vec![0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_write_negative_out_of_range() {
+        write_return(Value::Negative(0));
+    }
+
+    #[test]
+    fn test_write_byte_string() {
+        let cases = vec![
+            (vec![], vec![0x40]),
+            (
+                vec![0x01, 0x02, 0x03, 0x04],
+                vec![0x44, 0x01, 0x02, 0x03, 0x04],
+            ),
+        ];
+        for (byte_string, correct_cbor) in cases {
+            assert_eq!(write_return(cbor_bytes!(byte_string)), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_text_string() {
+        let unicode_3byte = vec![0xE6, 0xB0, 0xB4];
+        let cases = vec![
+            ("", vec![0x60]),
+            ("a", vec![0x61, 0x61]),
+            ("IETF", vec![0x64, 0x49, 0x45, 0x54, 0x46]),
+            ("\"\\", vec![0x62, 0x22, 0x5C]),
+            ("ü", vec![0x62, 0xC3, 0xBC]),
+            (
+                core::str::from_utf8(&unicode_3byte).unwrap(),
+                vec![0x63, 0xE6, 0xB0, 0xB4],
+            ),
+            ("𐅑", vec![0x64, 0xF0, 0x90, 0x85, 0x91]),
+        ];
+        for (text_string, correct_cbor) in cases {
+            assert_eq!(write_return(cbor_text!(text_string)), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_array() {
+        let value_vec: Vec<_> = (1..26).collect();
+        let expected_cbor = vec![
+            0x98, 0x19, // array of 25 elements
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+            0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x18, 0x18, 0x19,
+        ];
+        assert_eq!(
+            write_return(cbor_array_vec!(value_vec)),
+            Some(expected_cbor)
+        );
+    }
+
+    #[test]
+    fn test_write_map() {
+        let value_map = cbor_map! {
+            0 => "a",
+            23 => "b",
+            24 => "c",
+            core::u8::MAX as i64 => "d",
+            256 => "e",
+            core::u16::MAX as i64 => "f",
+            65536 => "g",
+            core::u32::MAX as i64 => "h",
+            4294967296_i64 => "i",
+            core::i64::MAX => "j",
+            -1 => "k",
+            -24 => "l",
+            -25 => "m",
+            -256 => "n",
+            -257 => "o",
+            -65537 => "p",
+            -4294967296_i64 => "q",
+            -4294967297_i64 => "r",
+            core::i64::MIN => "s",
             b"a" => 2,
             b"bar" => 3,
             b"foo" => 4,
@@ -276,194 +1728,1670 @@ mod test {
             "aa" => "AA",
         };
         let expected_cbor = vec![
-            0xb8, 0x19, // map of 25 pairs:
-            0x00, // key 0
-            0x61, 0x61, // value "a"
-            0x17, // key 23
-            0x61, 0x62, // value "b"
-            0x18, 0x18, // key 24
-            0x61, 0x63, // value "c"
-            0x18, 0xFF, // key 255
-            0x61, 0x64, // value "d"
-            0x19, 0x01, 0x00, // key 256
-            0x61, 0x65, // value "e"
-            0x19, 0xFF, 0xFF, // key 65535
-            0x61, 0x66, // value "f"
-            0x1A, 0x00, 0x01, 0x00, 0x00, // key 65536
-            0x61, 0x67, // value "g"
-            0x1A, 0xFF, 0xFF, 0xFF, 0xFF, // key 4294967295
-            0x61, 0x68, // value "h"
-            // key 4294967296
-            0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x61, 0x69, //  value "i"
-            // key INT64_MAX
-            0x1b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x61, 0x6a, //  value "j"
-            0x20, // key -1
-            0x61, 0x6b, // value "k"
-            0x37, // key -24
-            0x61, 0x6c, // value "l"
-            0x38, 0x18, // key -25
-            0x61, 0x6d, // value "m"
-            0x38, 0xFF, // key -256
-            0x61, 0x6e, // value "n"
-            0x39, 0x01, 0x00, // key -257
-            0x61, 0x6f, // value "o"
-            0x3A, 0x00, 0x01, 0x00, 0x00, // key -65537
-            0x61, 0x70, // value "p"
-            0x3A, 0xFF, 0xFF, 0xFF, 0xFF, // key -4294967296
-            0x61, 0x71, // value "q"
-            // key -4294967297
-            0x3B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x61, 0x72, //  value "r"
-            // key INT64_MIN
-            0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x61, 0x73, //  value "s"
-            0x41, b'a', // byte string "a"
-            0x02, 0x43, b'b', b'a', b'r', // byte string "bar"
-            0x03, 0x43, b'f', b'o', b'o', // byte string "foo"
-            0x04, 0x60, // key ""
-            0x61, 0x2e, // value "."
-            0x61, 0x65, // key "e"
-            0x61, 0x45, // value "E"
-            0x62, 0x61, 0x61, // key "aa"
-            0x62, 0x41, 0x41, // value "AA"
+            0xb8, 0x19, // map of 25 pairs:
+            0x00, // key 0
+            0x61, 0x61, // value "a"
+            0x17, // key 23
+            0x61, 0x62, // value "b"
+            0x18, 0x18, // key 24
+            0x61, 0x63, // value "c"
+            0x18, 0xFF, // key 255
+            0x61, 0x64, // value "d"
+            0x19, 0x01, 0x00, // key 256
+            0x61, 0x65, // value "e"
+            0x19, 0xFF, 0xFF, // key 65535
+            0x61, 0x66, // value "f"
+            0x1A, 0x00, 0x01, 0x00, 0x00, // key 65536
+            0x61, 0x67, // value "g"
+            0x1A, 0xFF, 0xFF, 0xFF, 0xFF, // key 4294967295
+            0x61, 0x68, // value "h"
+            // key 4294967296
+            0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x61, 0x69, //  value "i"
+            // key INT64_MAX
+            0x1b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x61, 0x6a, //  value "j"
+            0x20, // key -1
+            0x61, 0x6b, // value "k"
+            0x37, // key -24
+            0x61, 0x6c, // value "l"
+            0x38, 0x18, // key -25
+            0x61, 0x6d, // value "m"
+            0x38, 0xFF, // key -256
+            0x61, 0x6e, // value "n"
+            0x39, 0x01, 0x00, // key -257
+            0x61, 0x6f, // value "o"
+            0x3A, 0x00, 0x01, 0x00, 0x00, // key -65537
+            0x61, 0x70, // value "p"
+            0x3A, 0xFF, 0xFF, 0xFF, 0xFF, // key -4294967296
+            0x61, 0x71, // value "q"
+            // key -4294967297
+            0x3B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x61, 0x72, //  value "r"
+            // key INT64_MIN
+            0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x61, 0x73, //  value "s"
+            0x41, b'a', // byte string "a"
+            0x02, 0x43, b'b', b'a', b'r', // byte string "bar"
+            0x03, 0x43, b'f', b'o', b'o', // byte string "foo"
+            0x04, 0x60, // key ""
+            0x61, 0x2e, // value "."
+            0x61, 0x65, // key "e"
+            0x61, 0x45, // value "E"
+            0x62, 0x61, 0x61, // key "aa"
+            0x62, 0x41, 0x41, // value "AA"
+        ];
+        assert_eq!(write_return(value_map), Some(expected_cbor));
+    }
+
+    #[test]
+    fn test_write_map_sorted() {
+        let sorted_map = cbor_map! {
+            0 => "a",
+            1 => "b",
+            -1 => "c",
+            -2 => "d",
+            b"a" => "e",
+            b"b" => "f",
+            "" => "g",
+            "c" => "h",
+        };
+        let unsorted_map = cbor_map! {
+            1 => "b",
+            -2 => "d",
+            b"b" => "f",
+            "c" => "h",
+            "" => "g",
+            b"a" => "e",
+            -1 => "c",
+            0 => "a",
+        };
+        assert_eq!(write_return(sorted_map), write_return(unsorted_map));
+    }
+
+    #[test]
+    fn test_write_map_duplicates() {
+        let duplicate0 = cbor_map! {
+            0 => "a",
+            -1 => "c",
+            b"a" => "e",
+            "c" => "g",
+            0 => "b",
+        };
+        assert_eq!(write_return(duplicate0), None);
+        let duplicate1 = cbor_map! {
+            0 => "a",
+            -1 => "c",
+            b"a" => "e",
+            "c" => "g",
+            -1 => "d",
+        };
+        assert_eq!(write_return(duplicate1), None);
+        let duplicate2 = cbor_map! {
+            0 => "a",
+            -1 => "c",
+            b"a" => "e",
+            "c" => "g",
+            b"a" => "f",
+        };
+        assert_eq!(write_return(duplicate2), None);
+        let duplicate3 = cbor_map! {
+            0 => "a",
+            -1 => "c",
+            b"a" => "e",
+            "c" => "g",
+            "c" => "h",
+        };
+        assert_eq!(write_return(duplicate3), None);
+    }
+
+    #[test]
+    fn test_write_empty_containers() {
+        let cases = vec![
+            (cbor_array![], vec![0x80]),
+            (cbor_map! {}, vec![0xa0]),
+            (cbor_bytes!(vec![]), vec![0x40]),
+            (cbor_text!(""), vec![0x60]),
+            (cbor_array![cbor_map! {}], vec![0x81, 0xa0]),
+            (cbor_map! {"" => cbor_array![]}, vec![0xa1, 0x60, 0x80]),
+            (
+                cbor_map! {cbor_bytes!(vec![]) => cbor_map! {}, "" => cbor_text!("")},
+                vec![0xa2, 0x40, 0xa0, 0x60, 0x60],
+            ),
+            (
+                cbor_array![
+                    cbor_array![],
+                    cbor_bytes!(vec![]),
+                    cbor_array![cbor_map! {}]
+                ],
+                vec![0x83, 0x80, 0x40, 0x81, 0xa0],
+            ),
+            (cbor_tagged!(1, cbor_array![]), vec![0xc1, 0x80]),
+        ];
+        for (value, correct_cbor) in cases {
+            assert_eq!(write_return(value.clone()), Some(correct_cbor.clone()));
+            assert_eq!(crate::reader::read(&correct_cbor), Ok(value));
+        }
+
+        // Empty containers do not nest anything, so they fit at the deepest level.
+        for value in [cbor_array![], cbor_map! {}] {
+            let mut encoded_cbor = Vec::new();
+            assert_eq!(write_nested(value, &mut encoded_cbor, Some(0)), Ok(()));
+            assert_eq!(encoded_cbor.len(), 1);
+        }
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_nested(cbor_array![cbor_map! {}], &mut encoded_cbor, Some(0)),
+            Err(EncoderError::TooMuchNesting)
+        );
+        assert_eq!(
+            crate::reader::read_nested(&[0xa0], Some(0)),
+            Ok(cbor_map! {})
+        );
+    }
+
+    #[test]
+    fn test_write_map_with_container_keys() {
+        let value_map = cbor_map! {
+            cbor_map! {1 => 2} => 0,
+            cbor_array![1, 2] => 1,
+            cbor_map! {} => 2,
+            cbor_array![1] => 3,
+            cbor_array![] => 4,
+            "a" => 5,
+        };
+        let expected_cbor = vec![
+            0xa6, // map of 6 entries
+            0x61, 0x61, 0x05, // "a"
+            0x80, 0x04, // []
+            0x81, 0x01, 0x03, // [1]
+            0x82, 0x01, 0x02, 0x01, // [1, 2]
+            0xa0, 0x02, // {}
+            0xa1, 0x01, 0x02, 0x00, // {1: 2}
+        ];
+        assert_eq!(write_return(value_map), Some(expected_cbor));
+
+        let duplicate_maps = vec![
+            (
+                cbor_map! {
+                    cbor_array![1, cbor_map! {2 => 3}] => 0,
+                    cbor_array![1, cbor_map! {2 => 3}] => 1,
+                },
+                vec![0x82, 0x01, 0xa1, 0x02, 0x03],
+            ),
+            // Keys that only differ in the order of their own map entries encode alike.
+            (
+                cbor_map! {
+                    cbor_map! {1 => 1, 2 => 2} => 0,
+                    cbor_map! {2 => 2, 1 => 1} => 1,
+                },
+                vec![0xa2, 0x01, 0x01, 0x02, 0x02],
+            ),
+        ];
+        for (map, key) in duplicate_maps {
+            let mut encoded_cbor = Vec::new();
+            assert_eq!(
+                write(map, &mut encoded_cbor),
+                Err(EncoderError::DuplicateMapKey { key })
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_map_key_nesting() {
+        // Keys and values are nested one level below their map alike.
+        let nested = |levels: usize| {
+            let mut value = cbor_int!(0);
+            for _ in 0..levels {
+                value = cbor_array![value];
+            }
+            value
+        };
+        for (depth, ok) in [(1, true), (2, false)] {
+            let maps = vec![
+                cbor_map! {nested(depth) => 0},
+                cbor_map! {0 => nested(depth)},
+                cbor_map! {nested(depth) => 0, 1 => 0},
+            ];
+            for map in maps {
+                let mut encoded_cbor = Vec::new();
+                let result = write_nested(map, &mut encoded_cbor, Some(2));
+                assert_eq!(result.is_ok(), ok);
+                if !ok {
+                    assert_eq!(result, Err(EncoderError::TooMuchNesting));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_map_with_integer_keys() {
+        // Interleave the keys so that they need sorting.
+        let keys: Vec<i64> = (0..1000).map(|i| (i * 7919) % 1000).collect();
+        for sign in [1, -1] {
+            let entries: Vec<_> = keys
+                .iter()
+                .map(|k| (Value::integer(sign * k - (sign < 0) as i64), cbor_int!(*k)))
+                .collect();
+            // Sort by encoded keys, like the general path does.
+            let mut encoded_entries: Vec<_> = entries
+                .iter()
+                .map(|(k, v)| (write_return(k.clone()).unwrap(), v.clone()))
+                .collect();
+            encoded_entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut expected_cbor = vec![0xb9, 0x03, 0xe8];
+            for (encoded_key, v) in encoded_entries {
+                expected_cbor.extend(encoded_key);
+                expected_cbor.extend(write_return(v).unwrap());
+            }
+            assert_eq!(write_return(Value::Map(entries)), Some(expected_cbor));
+        }
+
+        let cases = vec![
+            (
+                cbor_map! {24 => 0, 1 => 1, 1000 => 2, 0 => 3},
+                vec![
+                    0xa4, 0x00, 0x03, 0x01, 0x01, 0x18, 0x18, 0x00, 0x19, 0x03, 0xe8, 0x02,
+                ],
+            ),
+            (
+                cbor_map! {-1 => 0, -1000 => 1, -25 => 2, -2 => 3},
+                vec![
+                    0xa4, 0x20, 0x00, 0x21, 0x03, 0x38, 0x18, 0x02, 0x39, 0x03, 0xe7, 0x01,
+                ],
+            ),
+        ];
+        for (map, correct_cbor) in cases {
+            assert_eq!(write_return(map), Some(correct_cbor));
+        }
+
+        assert_eq!(write_return(cbor_map! {1 => 0, 2 => 1, 1 => 2}), None);
+        assert_eq!(write_return(cbor_map! {-1 => 0, -1 => 1}), None);
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_nested(cbor_map! {1 => 0}, &mut encoded_cbor, Some(0)),
+            Err(EncoderError::TooMuchNesting)
+        );
+    }
+
+    #[test]
+    fn test_write_presorted_maps() {
+        let options = WriterOptions {
+            presorted_maps: true,
+            ..Default::default()
+        };
+        let mut map = alloc::collections::BTreeMap::new();
+        for (k, v) in [
+            (cbor_text!("bb"), cbor_int!(0)),
+            (cbor_int!(-1), cbor_map! {"x" => 2, "y" => 1}),
+            (cbor_text!("a"), cbor_int!(2)),
+            (cbor_int!(1000), cbor_int!(3)),
+            (cbor_array![1], cbor_int!(4)),
+        ] {
+            map.insert(k, v);
+        }
+        let value = Value::from(map);
+        let mut encoded_cbor = Vec::new();
+        write_with_options(value.clone(), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(Some(encoded_cbor), write_return(value));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_write_presorted_maps_unsorted() {
+        let options = WriterOptions {
+            presorted_maps: true,
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        let _ = write_with_options(
+            cbor_map! {"b" => 1, "a" => 2},
+            &mut encoded_cbor,
+            None,
+            &options,
+        );
+    }
+
+    #[test]
+    fn test_write_map_ordering() {
+        let map = cbor_map! {
+            24 => "a",
+            -1 => "b",
+            "z" => "c",
+        };
+        let bytewise_cbor = vec![
+            0xa3, // map of 3 pairs
+            0x18, 0x18, 0x61, 0x61, // 24 => "a"
+            0x20, 0x61, 0x62, // -1 => "b"
+            0x61, 0x7a, 0x61, 0x63, // "z" => "c"
+        ];
+        let length_first_cbor = vec![
+            0xa3, // map of 3 pairs
+            0x20, 0x61, 0x62, // -1 => "b"
+            0x18, 0x18, 0x61, 0x61, // 24 => "a"
+            0x61, 0x7a, 0x61, 0x63, // "z" => "c"
+        ];
+        assert_eq!(write_return(map.clone()), Some(bytewise_cbor.clone()));
+
+        for (ordering, expected_cbor) in [
+            (MapOrdering::BytewiseLexicographic, bytewise_cbor),
+            (MapOrdering::LengthFirst, length_first_cbor),
+        ] {
+            let options = WriterOptions {
+                ordering,
+                ..Default::default()
+            };
+            let mut encoded_cbor = Vec::new();
+            write_with_options(map.clone(), &mut encoded_cbor, None, &options).unwrap();
+            assert_eq!(encoded_cbor, expected_cbor);
+        }
+
+        let options = WriterOptions {
+            ordering: MapOrdering::LengthFirst,
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_with_options(
+                cbor_map! {1 => 1, 1 => 2},
+                &mut encoded_cbor,
+                None,
+                &options
+            ),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x01] })
+        );
+    }
+
+    #[test]
+    fn test_write_map_preserving_order() {
+        let options = WriterOptions {
+            preserve_map_order: true,
+            ..Default::default()
+        };
+        let unsorted_map = cbor_map! {
+            "b" => 2,
+            1 => "a",
+            "a" => cbor_map! {"d" => 4, "c" => 3},
+        };
+        let expected_cbor = vec![
+            0xa3, // map of 3 pairs
+            0x61, 0x62, 0x02, // "b" => 2
+            0x01, 0x61, 0x61, // 1 => "a"
+            0x61, 0x61, // "a"
+            0xa2, // map of 2 pairs
+            0x61, 0x64, 0x04, // "d" => 4
+            0x61, 0x63, 0x03, // "c" => 3
+        ];
+        let mut encoded_cbor = Vec::new();
+        write_with_options(unsorted_map.clone(), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(encoded_cbor, expected_cbor);
+        assert_ne!(write_return(unsorted_map), Some(expected_cbor));
+
+        let duplicate = cbor_map! {"b" => 1, "a" => 2, "b" => 3};
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_with_options(duplicate.clone(), &mut encoded_cbor, None, &options),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0x62],
+            })
+        );
+
+        let options = WriterOptions {
+            preserve_map_order: true,
+            allow_duplicate_keys: true,
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        write_with_options(duplicate.clone(), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(
+            encoded_cbor,
+            vec![0xa3, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02, 0x61, 0x62, 0x03]
+        );
+
+        // Duplicates are always rejected in canonical maps.
+        let options = WriterOptions {
+            allow_duplicate_keys: true,
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_with_options(duplicate, &mut encoded_cbor, None, &options),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0x62],
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_map_with_array() {
+        let value_map = cbor_map! {
+            "a" => 1,
+            "b" => cbor_array![2, 3],
+        };
+        let expected_cbor = vec![
+            0xa2, // map of 2 pairs
+            0x61, 0x61, // "a"
+            0x01, 0x61, 0x62, // "b"
+            0x82, // array with 2 elements
+            0x02, 0x03,
+        ];
+        assert_eq!(write_return(value_map), Some(expected_cbor));
+    }
+
+    #[test]
+    fn test_write_nested_map() {
+        let value_map = cbor_map! {
+            "a" => 1,
+            "b" => cbor_map! {
+                "c" => 2,
+                "d" => 3,
+            },
+        };
+        let expected_cbor = vec![
+            0xa2, // map of 2 pairs
+            0x61, 0x61, // "a"
+            0x01, 0x61, 0x62, // "b"
+            0xa2, // map of 2 pairs
+            0x61, 0x63, // "c"
+            0x02, 0x61, 0x64, // "d"
+            0x03,
+        ];
+        assert_eq!(write_return(value_map), Some(expected_cbor));
+    }
+
+    #[test]
+    fn test_write_tagged() {
+        let cases = vec![
+            (cbor_tagged!(6, cbor_int!(0x42)), vec![0xc6, 0x18, 0x42]),
+            (cbor_tagged!(1, cbor_true!()), vec![0xc1, 0xf5]),
+            (
+                cbor_tagged!(
+                    1000,
+                    cbor_map! {
+                        "a" => 1,
+                        "b" => cbor_array![2, 3],
+                    }
+                ),
+                vec![
+                    0xd9, 0x03, 0xe8, 0xa2, // map of 2 pairs
+                    0x61, 0x61, // "a"
+                    0x01, 0x61, 0x62, // "b"
+                    0x82, // array with 2 elements
+                    0x02, 0x03,
+                ],
+            ),
+        ];
+        for (value, correct_cbor) in cases {
+            assert_eq!(write_return(value), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_bignum() {
+        let cases = vec![
+            (
+                Value::from_u128(u64::MAX as u128 + 1),
+                vec![
+                    0xC2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                Value::from_u128(u128::MAX),
+                vec![
+                    0xC2, 0x50, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                    0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                ],
+            ),
+            (
+                Value::from_i128(-(u64::MAX as i128) - 2),
+                vec![
+                    0xC3, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                Value::from_i128(-(u64::MAX as i128) - 1),
+                vec![0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+        ];
+        for (value, correct_cbor) in cases {
+            assert_eq!(write_return(value), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_simple() {
+        let cases = vec![
+            (cbor_false!(), vec![0xF4]),
+            (cbor_true!(), vec![0xF5]),
+            (cbor_null!(), vec![0xF6]),
+            (cbor_undefined!(), vec![0xF7]),
+            (cbor_simple!(0), vec![0xE0]),
+            (cbor_simple!(16), vec![0xF0]),
+            (cbor_simple!(32), vec![0xF8, 0x20]),
+            (cbor_simple!(255), vec![0xF8, 0xFF]),
+        ];
+        for (value, correct_cbor) in cases {
+            assert_eq!(write_return(value), Some(correct_cbor));
+        }
+    }
+
+    #[test]
+    fn test_write_invalid_simple() {
+        // Reserved values, and named values that are not constructed as such.
+        for int in 20..=31 {
+            let value = cbor_array![Value::Simple(SimpleValue::Unassigned(int))];
+            assert_eq!(
+                write(value, &mut Vec::new()),
+                Err(EncoderError::InvalidSimpleValue)
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_undefined_for_none() {
+        let options = WriterOptions {
+            write_undefined_for_none: true,
+            ..WriterOptions::default()
+        };
+        let value = cbor_array![cbor_null!(), cbor_undefined!(), cbor_false!()];
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_with_options(value, &mut encoded_cbor, None, &options),
+            Ok(4)
+        );
+        assert_eq!(encoded_cbor, vec![0x83, 0xF7, 0xF7, 0xF4]);
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_write_normalize_text() {
+        let options = WriterOptions {
+            normalize_text: true,
+            ..WriterOptions::default()
+        };
+        let write_normalized = |value: Value| {
+            let mut encoded_cbor = Vec::new();
+            write_with_options(value, &mut encoded_cbor, None, &options).map(|_| encoded_cbor)
+        };
+        // "é" as a single code point, and as "e" followed by a combining acute accent.
+        let composed = "\u{e9}";
+        let decomposed = "e\u{301}";
+        assert_eq!(
+            write_normalized(cbor_text!(decomposed)),
+            Ok(vec![0x62, 0xC3, 0xA9])
+        );
+        assert_eq!(
+            write_normalized(cbor_array![composed, decomposed, "a"]),
+            Ok(vec![0x83, 0x62, 0xC3, 0xA9, 0x62, 0xC3, 0xA9, 0x61, 0x61])
+        );
+        assert_eq!(
+            write_normalized(cbor_map! {composed => 1, decomposed => 2}),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x62, 0xc3, 0xa9],
+            })
+        );
+        assert_eq!(
+            write_return(cbor_text!(decomposed)),
+            Some(vec![0x63, 0x65, 0xCC, 0x81])
+        );
+
+        let options = WriterOptions {
+            indefinite_length: true,
+            max_chunk_len: Some(1),
+            ..options
+        };
+        let mut encoded_cbor = Vec::new();
+        write_with_options(cbor_text!(decomposed), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(encoded_cbor, vec![0x7F, 0x62, 0xC3, 0xA9, 0xFF]);
+    }
+
+    #[test]
+    fn test_write_float() {
+        let cases = vec![
+            (0.0, vec![0xF9, 0x00, 0x00]),
+            (-0.0, vec![0xF9, 0x80, 0x00]),
+            (1.0, vec![0xF9, 0x3C, 0x00]),
+            (1.5, vec![0xF9, 0x3E, 0x00]),
+            (65504.0, vec![0xF9, 0x7B, 0xFF]),
+            (5.960464477539063e-8, vec![0xF9, 0x00, 0x01]),
+            (0.00006103515625, vec![0xF9, 0x04, 0x00]),
+            (-4.0, vec![0xF9, 0xC4, 0x00]),
+            (f64::INFINITY, vec![0xF9, 0x7C, 0x00]),
+            (f64::NEG_INFINITY, vec![0xF9, 0xFC, 0x00]),
+            (f64::NAN, vec![0xF9, 0x7E, 0x00]),
+            (100000.0, vec![0xFA, 0x47, 0xC3, 0x50, 0x00]),
+            (3.4028234663852886e38, vec![0xFA, 0x7F, 0x7F, 0xFF, 0xFF]),
+            (
+                1.1,
+                vec![0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A],
+            ),
+            (
+                1.0e300,
+                vec![0xFB, 0x7E, 0x37, 0xE4, 0x3C, 0x88, 0x00, 0x75, 0x9C],
+            ),
+            (
+                -4.1,
+                vec![0xFB, 0xC0, 0x10, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66],
+            ),
+        ];
+        for (float, correct_cbor) in cases {
+            assert_eq!(write_return(Value::Float(float)), Some(correct_cbor));
+        }
+    }
+
+    fn direct_encode<T: DirectEncode + ?Sized>(data: &T) -> Result<Vec<u8>, EncoderError> {
+        let mut encoded_cbor = Vec::new();
+        data.encode(&mut encoded_cbor)?;
+        Ok(encoded_cbor)
+    }
+
+    #[test]
+    fn test_direct_encode() {
+        use alloc::{collections::BTreeMap, string::String};
+
+        let ints = vec![0u64, 23, 24, 255, 256, 65536, u64::MAX];
+        assert_eq!(
+            direct_encode(&ints).ok(),
+            write_return(cbor_array_vec!(ints))
+        );
+        let ints = vec![i64::MIN, -257, -25, -24, -1, 0, 1, i64::MAX];
+        assert_eq!(
+            direct_encode(&ints).ok(),
+            write_return(cbor_array_vec!(ints))
+        );
+        assert_eq!(direct_encode(&(-1i8)).ok(), write_return(cbor_int!(-1)));
+        assert_eq!(
+            direct_encode(&vec![true, false]).ok(),
+            write_return(cbor_array![true, false])
+        );
+        assert_eq!(direct_encode("a").ok(), write_return(cbor_text!("a")));
+        assert_eq!(
+            direct_encode(&String::from("abc")).ok(),
+            write_return(cbor_text!("abc"))
+        );
+        assert_eq!(
+            direct_encode(&vec![0x01u8, 0x02]).ok(),
+            write_return(cbor_bytes!(vec![0x01, 0x02]))
+        );
+        assert_eq!(
+            direct_encode(&[0x01u8][..]).ok(),
+            write_return(cbor_bytes!(vec![0x01]))
+        );
+        assert_eq!(
+            direct_encode(&vec![vec!["a"], vec![], vec!["b", "c"]]).ok(),
+            write_return(cbor_array![
+                cbor_array!["a"],
+                cbor_array![],
+                cbor_array!["b", "c"]
+            ])
+        );
+
+        // Maps are sorted by their encoded keys rather than by the order of the keys.
+        let mut map = BTreeMap::new();
+        map.insert(String::from("aa"), vec![1u32]);
+        map.insert(String::from("b"), vec![]);
+        map.insert(String::from("a"), vec![2, 3]);
+        assert_eq!(
+            direct_encode(&map).ok(),
+            write_return(Value::from(
+                map.iter()
+                    .map(|(k, v)| (k.as_str(), cbor_array_vec!(v.clone())))
+                    .collect::<BTreeMap<_, _>>()
+            ))
+        );
+        let map: BTreeMap<i32, &str> = vec![(-1, "a"), (0, "b"), (-24, "c"), (1000, "d")]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            direct_encode(&map).ok(),
+            write_return(Value::from(map.clone()))
+        );
+
+        // Values are written as by `write`, counting towards the nesting limit.
+        let value = cbor_map! {"b" => cbor_tagged!(1, cbor_int!(2)), "a" => cbor_null!()};
+        assert_eq!(
+            direct_encode(&vec![value.clone()]).ok(),
+            write_return(cbor_array![value])
+        );
+        let mut nested = cbor_int!(0);
+        for _ in 0..126 {
+            nested = cbor_array![nested];
+        }
+        assert_eq!(
+            direct_encode(&vec![nested.clone()]).ok(),
+            write_return(cbor_array![nested.clone()])
+        );
+        assert_eq!(
+            direct_encode(&vec![vec![nested]]),
+            Err(EncoderError::TooMuchNesting)
+        );
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            vec![vec![1u32]].encode_nested(&mut encoded_cbor, 1),
+            Err(EncoderError::TooMuchNesting)
+        );
+        assert_eq!(
+            vec![Vec::<u32>::new()].encode_nested(&mut Vec::new(), 1),
+            Ok(())
+        );
+        // Scalars and empty containers count towards the limit as for `write_nested`.
+        assert_eq!(
+            1u32.encode_nested(&mut Vec::new(), -1),
+            write_nested(cbor_int!(1), &mut Vec::new(), Some(-1))
+        );
+        assert_eq!(
+            "a".encode_nested(&mut Vec::new(), -1),
+            Err(EncoderError::TooMuchNesting)
+        );
+        assert_eq!(
+            vec![vec![Vec::<u32>::new()]].encode_nested(&mut Vec::new(), 1),
+            write_nested(
+                cbor_array![cbor_array![cbor_array![]]],
+                &mut Vec::new(),
+                Some(1)
+            )
+        );
+        assert_eq!(
+            BTreeMap::<u32, u32>::new().encode_nested(&mut Vec::new(), -1),
+            Err(EncoderError::TooMuchNesting)
+        );
+
+        let map: BTreeMap<Value, u32> = vec![
+            (cbor_map! {1 => 1, 2 => 2}, 0),
+            (cbor_map! {2 => 2, 1 => 1}, 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            direct_encode(&map),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0xa2, 0x01, 0x01, 0x02, 0x02]
+            })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_direct_encode_hash_map() {
+        let map: std::collections::HashMap<u64, bool> =
+            (0..100).map(|i| (i * 7, i % 2 == 0)).collect();
+        assert_eq!(
+            direct_encode(&map).ok(),
+            write_return(Value::from(map.clone()))
+        );
+    }
+
+    #[test]
+    fn test_write_duplicate_key() {
+        let mut entries = (0..100)
+            .map(|i| (cbor_text!(alloc::format!("field{}", i)), cbor_int!(i)))
+            .collect::<Vec<_>>();
+        entries.push((cbor_text!("field42"), cbor_int!(0)));
+        let key = match write(Value::Map(entries), &mut Vec::new()) {
+            Err(EncoderError::DuplicateMapKey { key }) => key,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(crate::reader::read(&key), Ok(cbor_text!("field42")));
+
+        // Integer keys, which are sorted without encoding them first, are reported encoded under
+        // the options.
+        let options = WriterOptions {
+            int_width: IntWidth::Fixed64,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_with_options(
+                cbor_map! {3 => 0, 1 => 1, 3 => 2},
+                &mut Vec::new(),
+                None,
+                &options
+            ),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x1B, 0, 0, 0, 0, 0, 0, 0, 0x03]
+            })
+        );
+        assert_eq!(
+            write(cbor_map! {-3 => 0, -3 => 1}, &mut Vec::new()),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x22] })
+        );
+    }
+
+    #[test]
+    fn test_write_int_width() {
+        let options = WriterOptions {
+            int_width: IntWidth::Fixed64,
+            ..Default::default()
+        };
+        let cases = vec![
+            (cbor_int!(1), vec![0x1B, 0, 0, 0, 0, 0, 0, 0, 0x01]),
+            (cbor_int!(-1), vec![0x3B, 0, 0, 0, 0, 0, 0, 0, 0x00]),
+            (
+                Value::Unsigned(u64::MAX),
+                vec![0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+            // Lengths and tag numbers keep their shortest form.
+            (
+                cbor_tagged!(1, cbor_array![2]),
+                vec![0xC1, 0x81, 0x1B, 0, 0, 0, 0, 0, 0, 0, 0x02],
+            ),
+            // Map keys are sorted by their fixed-width encoding.
+            (
+                cbor_map! {256 => "b", 1 => "a"},
+                vec![
+                    0xA2, 0x1B, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x61, 0x61, 0x1B, 0, 0, 0, 0, 0, 0,
+                    0x01, 0x00, 0x61, 0x62,
+                ],
+            ),
+        ];
+        for (value, expected_cbor) in cases {
+            let mut encoded_cbor = Vec::new();
+            assert_eq!(
+                write_with_options(value, &mut encoded_cbor, None, &options),
+                Ok(expected_cbor.len())
+            );
+            assert_eq!(encoded_cbor, expected_cbor);
+        }
+
+        // The fixed-width form is not canonical, but decodes non-strictly.
+        let mut encoded_cbor = Vec::new();
+        write_with_options(cbor_int!(1), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(encoded_cbor.len(), 9);
+        assert_eq!(
+            crate::reader::read(&encoded_cbor),
+            Err(crate::reader::DecoderError::NonMinimalCborEncoding)
+        );
+        assert_eq!(
+            crate::reader::read_non_strict_with_options(&encoded_cbor, None, &Default::default()),
+            Ok(cbor_int!(1))
+        );
+    }
+
+    #[test]
+    fn test_write_nan() {
+        let nans = vec![
+            f64::NAN,
+            -f64::NAN,
+            // Signaling NaN, and NaNs with payloads only fitting single and double precision.
+            f64::from_bits(0x7FF0_0000_0000_0001),
+            f64::from_bits(0x7FF8_0000_2000_0000),
+            f64::from_bits(0xFFF4_0000_0000_0000),
+            f64::from_bits(0x7FFF_FFFF_FFFF_FFFF),
+        ];
+        for &nan in &nans {
+            assert_eq!(
+                write_return(Value::Float(nan)),
+                Some(vec![0xF9, 0x7E, 0x00])
+            );
+        }
+
+        let options = WriterOptions {
+            canonical_nan: false,
+            ..Default::default()
+        };
+        let expected_cbor = vec![
+            vec![0xF9, 0x7E, 0x00],
+            vec![0xF9, 0xFE, 0x00],
+            vec![0xFB, 0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+            vec![0xFA, 0x7F, 0xC0, 0x00, 0x01],
+            vec![0xF9, 0xFD, 0x00],
+            vec![0xFB, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        ];
+        for (nan, expected_cbor) in nans.into_iter().zip(expected_cbor) {
+            let mut encoded_cbor = Vec::new();
+            write_with_options(Value::Float(nan), &mut encoded_cbor, None, &options).unwrap();
+            assert_eq!(encoded_cbor, expected_cbor, "{:016x}", nan.to_bits());
+        }
+
+        // Zeros keep their sign either way.
+        for options in [WriterOptions::default(), options].iter() {
+            let mut encoded_cbor = Vec::new();
+            write_with_options(
+                cbor_array![Value::Float(0.0), Value::Float(-0.0)],
+                &mut encoded_cbor,
+                None,
+                options,
+            )
+            .unwrap();
+            assert_eq!(encoded_cbor, vec![0x82, 0xF9, 0x00, 0x00, 0xF9, 0x80, 0x00]);
+        }
+    }
+
+    #[test]
+    fn test_shortest_float_encoding() {
+        let cases = vec![
+            (0.0, 25, vec![0x00, 0x00]),
+            (-0.0, 25, vec![0x80, 0x00]),
+            // Largest half.
+            (65504.0, 25, vec![0x7B, 0xFF]),
+            (-65504.0, 25, vec![0xFB, 0xFF]),
+            // Would round to infinity in half precision.
+            (65520.0, 26, vec![0x47, 0x7F, 0xF0, 0x00]),
+            // Would round to 65504 in half precision.
+            (65505.0, 26, vec![0x47, 0x7F, 0xE1, 0x00]),
+            // Smallest normal and largest subnormal halves.
+            (6.103515625e-5, 25, vec![0x04, 0x00]),
+            (6.097555160522461e-5, 25, vec![0x03, 0xFF]),
+            // Smallest subnormal half, and half of it.
+            (5.960464477539063e-8, 25, vec![0x00, 0x01]),
+            (2.9802322387695312e-8, 26, vec![0x33, 0x00, 0x00, 0x00]),
+            // Needs one more mantissa bit than a half provides.
+            (1.0009765625, 25, vec![0x3C, 0x01]),
+            (1.00048828125, 26, vec![0x3F, 0x80, 0x10, 0x00]),
+            // Smallest subnormal single.
+            (1.401298464324817e-45, 26, vec![0x00, 0x00, 0x00, 0x01]),
+            (f32::MAX as f64, 26, vec![0x7F, 0x7F, 0xFF, 0xFF]),
+            (
+                f64::MIN_POSITIVE,
+                27,
+                vec![0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ),
+            (
+                1.0 + f64::EPSILON,
+                27,
+                vec![0x3F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ),
+            (f64::INFINITY, 25, vec![0x7C, 0x00]),
+            (-f64::NAN, 25, vec![0x7E, 0x00]),
+        ];
+        for (float, additional_info, bytes) in cases {
+            assert_eq!(
+                shortest_float_encoding(float),
+                (additional_info, bytes),
+                "{:e}",
+                float
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_with_capacity() {
+        let value = cbor_array_vec!((0..10000).collect::<Vec<_>>());
+        let capacity = value.encoded_len_hint();
+        let expected_cbor = write_return(value.clone()).unwrap();
+        let encoded_cbor = write_with_capacity(value, capacity).unwrap();
+        assert_eq!(encoded_cbor, expected_cbor);
+        assert_eq!(encoded_cbor.capacity(), capacity);
+
+        let duplicate = cbor_map! {0 => "a", 0 => "b"};
+        assert_eq!(
+            write_with_capacity(duplicate, 0),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+    }
+
+    #[test]
+    fn test_encode_to_vec() {
+        let value = cbor_map! {
+            "a" => cbor_array_vec!((0..1000).collect::<Vec<_>>()),
+            2 => cbor_bytes!(vec![0x01; 100]),
+        };
+        let capacity = value.encoded_len_hint();
+        let expected_cbor = write_return(value.clone()).unwrap();
+        let encoded_cbor = encode_to_vec(value).unwrap();
+        assert_eq!(encoded_cbor, expected_cbor);
+        assert_eq!(encoded_cbor.capacity(), capacity);
+
+        assert_eq!(
+            encode_to_vec(cbor_map! {0 => "a", 0 => "b"}),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+        let mut nested = cbor_int!(0);
+        for _ in 0..128 {
+            nested = cbor_array![nested];
+        }
+        assert_eq!(encode_to_vec(nested), Err(EncoderError::TooMuchNesting));
+    }
+
+    #[test]
+    fn test_canonical_wrapped() {
+        let unsorted = Value::Map(vec![
+            (cbor_text!("b"), cbor_int!(1)),
+            (cbor_int!(2), cbor_array![cbor_int!(3)]),
+            (cbor_text!("a"), cbor_int!(4)),
+        ]);
+        let expected_cbor = write_return(unsorted.clone()).unwrap();
+        assert!(crate::reader::is_canonical(&expected_cbor));
+        assert_eq!(
+            canonical_wrapped(unsorted),
+            Ok(Value::ByteString(expected_cbor))
+        );
+        assert_eq!(
+            canonical_wrapped(cbor_map! {0 => 0, 0 => 1}),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+    }
+
+    #[test]
+    fn test_write_to_boxed() {
+        let values = vec![
+            cbor_array_vec!((0..10000).collect::<Vec<_>>()),
+            cbor_map! {"a" => Value::Float(1.1), 2 => cbor_bytes!(vec![0x01; 100])},
+            cbor_null!(),
+        ];
+        for value in values {
+            let expected_cbor = write_return(value.clone()).unwrap();
+            assert_eq!(
+                write_to_boxed(value).unwrap(),
+                expected_cbor.into_boxed_slice()
+            );
+        }
+        assert_eq!(
+            write_to_boxed(cbor_map! {0 => "a", 0 => "b"}),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+    }
+
+    #[test]
+    fn test_write_framed() {
+        let mut encoded_cbor = vec![0xff];
+        assert_eq!(write_framed(cbor_array![1, "a"], &mut encoded_cbor), Ok(()));
+        assert_eq!(
+            encoded_cbor,
+            vec![0xff, 0x00, 0x00, 0x00, 0x04, 0x82, 0x01, 0x61, 0x61]
+        );
+
+        let value = cbor_bytes!(vec![0x00; 300]);
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(write_framed(value.clone(), &mut encoded_cbor), Ok(()));
+        assert_eq!(&encoded_cbor[..4], &[0x00, 0x00, 0x01, 0x2f]);
+        assert_eq!(encoded_cbor[4..], write_return(value).unwrap()[..]);
+
+        let mut encoded_cbor = vec![0xff];
+        assert_eq!(
+            write_framed(cbor_map! {0 => 1, 0 => 2}, &mut encoded_cbor),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+        assert_eq!(encoded_cbor, vec![0xff]);
+    }
+
+    #[test]
+    fn test_encoder() {
+        let mut encoder = Encoder::new();
+        let cases = vec![
+            cbor_int!(1),
+            cbor_map! {"bb" => 1, "a" => cbor_map! {"d" => 2, "c" => 3}},
+            cbor_array![cbor_map! {cbor_array![2] => 1, cbor_array![1] => 2}],
+        ];
+        for value in cases {
+            let mut encoded_cbor = vec![0xff];
+            let expected_cbor = write_return(value.clone()).unwrap();
+            assert_eq!(
+                encoder.encode(&value, &mut encoded_cbor),
+                Ok(expected_cbor.len())
+            );
+            assert_eq!(encoded_cbor[1..], expected_cbor[..]);
+        }
+        // The buffers of the encoded keys are kept for further calls.
+        assert!(!encoder.spare_buffers.is_empty());
+
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            encoder.encode(&cbor_map! {0 => 1, 0 => 2}, &mut encoded_cbor),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+
+        let mut encoder = Encoder::with_options(
+            Some(0),
+            WriterOptions {
+                indefinite_length: true,
+                ..Default::default()
+            },
+        );
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(encoder.encode(&cbor_text!("a"), &mut encoded_cbor), Ok(4));
+        assert_eq!(encoded_cbor, vec![0x7f, 0x61, 0x61, 0xff]);
+        assert_eq!(
+            encoder.encode(&cbor_array![1], &mut encoded_cbor),
+            Err(EncoderError::TooMuchNesting)
+        );
+    }
+
+    #[test]
+    fn test_write_self_describing() {
+        let mut encoded_cbor = vec![0xff];
+        assert_eq!(
+            write_self_describing(cbor_array![1, "a"], &mut encoded_cbor),
+            Ok(())
+        );
+        assert_eq!(
+            encoded_cbor,
+            vec![0xff, 0xd9, 0xd9, 0xf7, 0x82, 0x01, 0x61, 0x61]
+        );
+        assert_eq!(
+            encoded_cbor[1..],
+            write_return(cbor_array![1, "a"].self_describing()).unwrap()[..]
+        );
+
+        let mut encoded_cbor = vec![0xff];
+        assert_eq!(
+            write_self_describing(cbor_map! {0 => 1, 0 => 2}, &mut encoded_cbor),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+        assert_eq!(encoded_cbor, vec![0xff]);
+    }
+
+    #[test]
+    fn test_array_writer() {
+        let mut encoded_cbor = Vec::new();
+        let mut array = array_writer(30, &mut encoded_cbor);
+        for i in 0..30 {
+            array.push(cbor_int!(i)).unwrap();
+        }
+        assert_eq!(array.finish(), Ok(()));
+        let expected_cbor = write_return(cbor_array_vec!((0..30).collect::<Vec<_>>())).unwrap();
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        let mut encoded_cbor = Vec::new();
+        let mut array = indefinite_array_writer(&mut encoded_cbor);
+        array.push(cbor_int!(1)).unwrap();
+        array.push(cbor_map! {"b" => 2, "a" => 3}).unwrap();
+        assert_eq!(array.finish(), Ok(()));
+        assert_eq!(
+            encoded_cbor,
+            vec![0x9F, 0x01, 0xA2, 0x61, 0x61, 0x03, 0x61, 0x62, 0x02, 0xFF]
+        );
+
+        let mut encoded_cbor = Vec::new();
+        let array = indefinite_array_writer(&mut encoded_cbor);
+        assert_eq!(array.finish(), Ok(()));
+        assert_eq!(encoded_cbor, vec![0x9F, 0xFF]);
+
+        // The number of elements must match the length.
+        let mut encoded_cbor = Vec::new();
+        let mut array = array_writer(1, &mut encoded_cbor);
+        array.push(cbor_int!(1)).unwrap();
+        assert_eq!(array.push(cbor_int!(2)), Err(EncoderError::LengthMismatch));
+        assert_eq!(array.finish(), Ok(()));
+        assert_eq!(encoded_cbor, vec![0x81, 0x01]);
+        let mut encoded_cbor = Vec::new();
+        let mut array = array_writer(2, &mut encoded_cbor);
+        array.push(cbor_int!(1)).unwrap();
+        assert_eq!(array.finish(), Err(EncoderError::LengthMismatch));
+
+        let mut nested = cbor_int!(0);
+        for _ in 0..MAX_NESTING_DEPTH {
+            nested = cbor_array![nested];
+        }
+        let mut encoded_cbor = Vec::new();
+        let mut array = array_writer(1, &mut encoded_cbor);
+        assert_eq!(array.push(nested), Err(EncoderError::TooMuchNesting));
+    }
+
+    #[test]
+    fn test_write_bytes_chunks() {
+        let mut encoded_cbor = vec![0x82];
+        write_bytes_chunks(vec![vec![0x01, 0x02], vec![0x03]], &mut encoded_cbor);
+        write_bytes_chunks(vec![], &mut encoded_cbor);
+        assert_eq!(
+            encoded_cbor,
+            vec![0x82, 0x5F, 0x42, 0x01, 0x02, 0x41, 0x03, 0xFF, 0x5F, 0xFF]
+        );
+        assert_eq!(
+            crate::reader::read_nested_non_strict(&encoded_cbor, None),
+            Ok(cbor_array![
+                cbor_bytes!(vec![0x01, 0x02, 0x03]),
+                cbor_bytes!(vec![])
+            ])
+        );
+
+        let chunks = (0..3u8).map(|i| vec![i; 100]);
+        let mut encoded_cbor = Vec::new();
+        write_bytes_chunks(chunks.clone(), &mut encoded_cbor);
+        assert_eq!(
+            crate::reader::read_nested_non_strict(&encoded_cbor, None),
+            Ok(cbor_bytes!(chunks.flatten().collect::<Vec<_>>()))
+        );
+    }
+
+    #[test]
+    fn test_map_writer() {
+        let mut encoded_cbor = Vec::new();
+        let mut map = map_writer(3, &mut encoded_cbor);
+        map.push(cbor_int!(1), cbor_text!("a")).unwrap();
+        map.push(cbor_text!("a"), cbor_array![2]).unwrap();
+        map.push(cbor_text!("bb"), cbor_null!()).unwrap();
+        assert_eq!(map.finish(), Ok(()));
+        let expected_cbor =
+            write_return(cbor_map! {"bb" => cbor_null!(), "a" => cbor_array![2], 1 => "a"});
+        assert_eq!(Some(encoded_cbor), expected_cbor);
+
+        let mut encoded_cbor = Vec::new();
+        let mut map = indefinite_map_writer(&mut encoded_cbor);
+        map.push(cbor_int!(1), cbor_int!(2)).unwrap();
+        assert_eq!(map.finish(), Ok(()));
+        assert_eq!(encoded_cbor, vec![0xBF, 0x01, 0x02, 0xFF]);
+
+        // Keys must be pushed in canonical order, without duplicates.
+        let mut encoded_cbor = Vec::new();
+        let mut map = map_writer(2, &mut encoded_cbor);
+        map.push(cbor_text!("a"), cbor_int!(1)).unwrap();
+        assert_eq!(
+            map.push(cbor_int!(1), cbor_int!(2)),
+            Err(EncoderError::OutOfOrderKey)
+        );
+        assert_eq!(
+            map.push(cbor_text!("a"), cbor_int!(2)),
+            Err(EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0x61],
+            })
+        );
+        map.push(cbor_text!("b"), cbor_int!(2)).unwrap();
+        assert_eq!(
+            map.push(cbor_text!("c"), cbor_int!(3)),
+            Err(EncoderError::LengthMismatch)
+        );
+        assert_eq!(map.finish(), Ok(()));
+        assert_eq!(encoded_cbor, vec![0xA2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x02]);
+
+        let mut encoded_cbor = Vec::new();
+        let map = map_writer(1, &mut encoded_cbor);
+        assert_eq!(map.finish(), Err(EncoderError::LengthMismatch));
+    }
+
+    #[test]
+    fn test_write_sequence() {
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(write_sequence(vec![], &mut encoded_cbor), Ok(()));
+        assert!(encoded_cbor.is_empty());
+
+        let items = vec![
+            cbor_int!(1),
+            cbor_array![2, "a"],
+            cbor_map! {},
+            cbor_null!(),
         ];
-        assert_eq!(write_return(value_map), Some(expected_cbor));
+        assert_eq!(write_sequence(items, &mut encoded_cbor), Ok(()));
+        assert_eq!(encoded_cbor, vec![0x01, 0x82, 0x02, 0x61, 0x61, 0xa0, 0xf6]);
+
+        let items = vec![cbor_int!(1), cbor_map! {0 => 1, 0 => 2}];
+        assert_eq!(
+            write_sequence(items, &mut Vec::new()),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
     }
 
     #[test]
-    fn test_write_map_sorted() {
-        let sorted_map = cbor_map! {
-            0 => "a",
-            1 => "b",
-            -1 => "c",
-            -2 => "d",
-            b"a" => "e",
-            b"b" => "f",
-            "" => "g",
-            "c" => "h",
-        };
-        let unsorted_map = cbor_map! {
-            1 => "b",
-            -2 => "d",
-            b"b" => "f",
-            "c" => "h",
-            "" => "g",
-            b"a" => "e",
-            -1 => "c",
-            0 => "a",
+    fn test_write_pre_encoded() {
+        let cached = write_return(cbor_map! {"x" => cbor_array![1, 2]}).unwrap();
+        let value_ref = ValueRef::Map(vec![
+            (ValueRef::TextString("b"), ValueRef::PreEncoded(&cached)),
+            (
+                ValueRef::PreEncoded(&[0x61, 0x61]),
+                ValueRef::Array(vec![ValueRef::PreEncoded(&cached), ValueRef::Unsigned(3)]),
+            ),
+        ]);
+        let expected_cbor = write_return(cbor_map! {
+            "b" => cbor_map! {"x" => cbor_array![1, 2]},
+            "a" => cbor_array![cbor_map! {"x" => cbor_array![1, 2]}, 3],
+        })
+        .unwrap();
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(write_value_ref(&value_ref, &mut encoded_cbor), Ok(()));
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        // Pre-encoded keys take part in duplicate detection through their encoding.
+        let duplicate = ValueRef::Map(vec![
+            (ValueRef::PreEncoded(&[0x00]), "a".into()),
+            (ValueRef::Unsigned(0), "b".into()),
+        ]);
+        assert_eq!(
+            write_value_ref(&duplicate, &mut Vec::new()),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+
+        // Pre-encoded data is not subject to the nesting limit nor to the options.
+        let options = WriterOptions {
+            indefinite_length: true,
+            ..Default::default()
         };
-        assert_eq!(write_return(sorted_map), write_return(unsorted_map));
+        let nested = [0x81, 0x81, 0x80];
+        let mut encoded_cbor = Vec::new();
+        let mut writer = Writer::with_options(&mut encoded_cbor, options);
+        assert!(writer
+            .encode_cbor(Node::Ref(&ValueRef::PreEncoded(&nested)), Some(0))
+            .is_ok());
+        assert_eq!(encoded_cbor, nested);
     }
 
+    #[cfg(debug_assertions)]
     #[test]
-    fn test_write_map_duplicates() {
-        let duplicate0 = cbor_map! {
-            0 => "a",
-            -1 => "c",
-            b"a" => "e",
-            "c" => "g",
-            0 => "b",
-        };
-        assert_eq!(write_return(duplicate0), None);
-        let duplicate1 = cbor_map! {
-            0 => "a",
-            -1 => "c",
-            b"a" => "e",
-            "c" => "g",
-            -1 => "d",
-        };
-        assert_eq!(write_return(duplicate1), None);
-        let duplicate2 = cbor_map! {
-            0 => "a",
-            -1 => "c",
-            b"a" => "e",
-            "c" => "g",
-            b"a" => "f",
-        };
-        assert_eq!(write_return(duplicate2), None);
-        let duplicate3 = cbor_map! {
-            0 => "a",
-            -1 => "c",
-            b"a" => "e",
-            "c" => "g",
-            "c" => "h",
+    #[should_panic]
+    fn test_write_pre_encoded_malformed() {
+        let _ = write_value_ref(&ValueRef::PreEncoded(&[0x82, 0x01]), &mut Vec::new());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_write_pre_encoded_non_canonical_key() {
+        let value_ref = ValueRef::Map(vec![(ValueRef::PreEncoded(&[0x18, 0x01]), "a".into())]);
+        let _ = write_value_ref(&value_ref, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_write_ref() {
+        let blob = vec![0x5a; 300];
+        let value = cbor_map! {
+            "b" => cbor_bytes!(blob.clone()),
+            2 => cbor_array![cbor_text!("é"), cbor_null!(), cbor_tagged!(7, cbor_int!(-24))],
+            "a" => cbor_map! {cbor_array![] => cbor_true!()},
+            -1 => Value::Float(1.5),
         };
-        assert_eq!(write_return(duplicate3), None);
+        let expected_cbor = write_return(value.clone()).unwrap();
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(write_ref(&value, &mut encoded_cbor), Ok(()));
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        let inner_map = cbor_map! {cbor_array![] => cbor_true!()};
+        let value_ref = ValueRef::Map(vec![
+            (ValueRef::TextString("b"), ValueRef::ByteString(&blob)),
+            (
+                ValueRef::Unsigned(2),
+                ValueRef::Array(vec![
+                    ValueRef::TextString("é"),
+                    ValueRef::Simple(SimpleValue::NullValue),
+                    ValueRef::Tag(7, Box::new(ValueRef::Negative(-24))),
+                ]),
+            ),
+            ("a".into(), (&inner_map).into()),
+            (ValueRef::Negative(-1), ValueRef::Float(1.5)),
+        ]);
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(write_value_ref(&value_ref, &mut encoded_cbor), Ok(()));
+        assert_eq!(encoded_cbor, expected_cbor);
+
+        let duplicate = ValueRef::Map(vec![
+            (ValueRef::Unsigned(0), "a".into()),
+            (ValueRef::Value(&Value::Unsigned(0)), "b".into()),
+        ]);
+        assert_eq!(
+            write_value_ref(&duplicate, &mut Vec::new()),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x00] })
+        );
+        let nested = cbor_array![cbor_array![]];
+        assert_eq!(
+            write_nested_ref(&nested, &mut Vec::new(), Some(0)),
+            Err(EncoderError::TooMuchNesting)
+        );
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_write_map_with_array() {
-        let value_map = cbor_map! {
+    fn test_write_to() {
+        let value = cbor_map! {
+            "b" => cbor_array![2, 3],
             "a" => 1,
+            1000 => cbor_tagged!(1, Value::Float(1.5)),
+        };
+        let mut expected_cbor = Vec::new();
+        write(value.clone(), &mut expected_cbor).unwrap();
+
+        let mut sink = std::io::Cursor::new(Vec::new());
+        assert_eq!(write_to(value, &mut sink).unwrap(), expected_cbor.len());
+        assert_eq!(sink.into_inner(), expected_cbor);
+
+        let mut sink = Vec::new();
+        let nested = cbor_array![cbor_array![1]];
+        assert!(matches!(
+            write_nested_to(nested, &mut sink, Some(0)),
+            Err(WriteError::Encoder(EncoderError::TooMuchNesting))
+        ));
+
+        let mut sink = [0u8; 2];
+        assert!(matches!(
+            write_to(cbor_text!("IETF"), &mut &mut sink[..]),
+            Err(WriteError::Io(_))
+        ));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_encode_and_hash() {
+        use sha2::{Digest, Sha256};
+
+        let value = cbor_map! {
             "b" => cbor_array![2, 3],
+            "a" => cbor_bytes!(vec![0x01; 100]),
+            1 => cbor_tagged!(1, Value::Float(1.5)),
         };
-        let expected_cbor = vec![
-            0xa2, // map of 2 pairs
-            0x61, 0x61, // "a"
-            0x01, 0x61, 0x62, // "b"
-            0x82, // array with 2 elements
-            0x02, 0x03,
-        ];
-        assert_eq!(write_return(value_map), Some(expected_cbor));
+        let mut encoded_cbor = Vec::new();
+        write(value.clone(), &mut encoded_cbor).unwrap();
+        assert_eq!(
+            encode_and_hash::<Sha256>(value),
+            Ok(Sha256::digest(&encoded_cbor))
+        );
+
+        let mut nested = cbor_int!(1);
+        for _ in 0..128 {
+            nested = cbor_array![nested];
+        }
+        assert_eq!(
+            encode_and_hash::<Sha256>(nested),
+            Err(EncoderError::TooMuchNesting)
+        );
+        assert_eq!(
+            encode_and_hash::<Sha256>(cbor_map! {1 => 1, 1 => 2}),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x01] })
+        );
     }
 
+    #[cfg(feature = "digest")]
     #[test]
-    fn test_write_nested_map() {
-        let value_map = cbor_map! {
+    fn test_cid_hash() {
+        use sha2::Sha256;
+
+        // Golden vector: the canonical encoding, and thus the hash, must never change.
+        let value = cbor_map! {
+            "b" => cbor_array![Value::Float(1.5)],
             "a" => 1,
-            "b" => cbor_map! {
-                "c" => 2,
-                "d" => 3,
-            },
+            1 => "A",
         };
-        let expected_cbor = vec![
-            0xa2, // map of 2 pairs
-            0x61, 0x61, // "a"
-            0x01, 0x61, 0x62, // "b"
-            0xa2, // map of 2 pairs
-            0x61, 0x63, // "c"
-            0x02, 0x61, 0x64, // "d"
-            0x03,
+        assert_eq!(
+            write_return(value.clone()).unwrap(),
+            vec![0xA3, 0x01, 0x61, 0x41, 0x61, 0x61, 0x01, 0x61, 0x62, 0x81, 0xF9, 0x3E, 0x00]
+        );
+        let expected_hash = [
+            0x98, 0x9d, 0x35, 0xdb, 0x6b, 0x5a, 0xe3, 0x52, 0xe5, 0x62, 0x4d, 0x7a, 0x28, 0x82,
+            0xed, 0xd9, 0x61, 0x4e, 0x35, 0x8f, 0x49, 0xba, 0x7f, 0x46, 0x1f, 0xe2, 0x3c, 0xaa,
+            0xf8, 0x10, 0xd3, 0x6f,
         ];
-        assert_eq!(write_return(value_map), Some(expected_cbor));
+        assert_eq!(value.cid_hash::<Sha256>().unwrap()[..], expected_hash);
+        assert_eq!(
+            value.cid_hash::<Sha256>(),
+            encode_and_hash::<Sha256>(value.clone())
+        );
+
+        assert_eq!(
+            cbor_map! {1 => 1, 1 => 2}.cid_hash::<Sha256>(),
+            Err(EncoderError::DuplicateMapKey { key: vec![0x01] })
+        );
     }
 
     #[test]
-    fn test_write_tagged() {
+    fn test_encoder_error_display() {
+        use alloc::string::ToString;
+        assert_eq!(
+            EncoderError::TooMuchNesting.to_string(),
+            "maximum nesting depth exceeded"
+        );
+        assert_eq!(
+            EncoderError::DuplicateMapKey {
+                key: vec![0x61, 0xff]
+            }
+            .to_string(),
+            "duplicate map key h'61ff'"
+        );
+    }
+
+    #[test]
+    fn test_write_indefinite_length() {
+        let options = WriterOptions {
+            indefinite_length: true,
+            max_chunk_len: Some(2),
+            ..Default::default()
+        };
         let cases = vec![
-            (cbor_tagged!(6, cbor_int!(0x42)), vec![0xc6, 0x18, 0x42]),
-            (cbor_tagged!(1, cbor_true!()), vec![0xc1, 0xf5]),
+            (cbor_array![], vec![0x9F, 0xFF]),
             (
-                cbor_tagged!(
-                    1000,
-                    cbor_map! {
-                        "a" => 1,
-                        "b" => cbor_array![2, 3],
-                    }
-                ),
+                cbor_array![1, cbor_array![2, 3]],
+                vec![0x9F, 0x01, 0x9F, 0x02, 0x03, 0xFF, 0xFF],
+            ),
+            (
+                cbor_map! {"a" => 1, "b" => 2},
                 vec![
-                    0xd9, 0x03, 0xe8, 0xa2, // map of 2 pairs
-                    0x61, 0x61, // "a"
-                    0x01, 0x61, 0x62, // "b"
-                    0x82, // array with 2 elements
-                    0x02, 0x03,
+                    0xBF, 0x7F, 0x61, 0x61, 0xFF, 0x01, 0x7F, 0x61, 0x62, 0xFF, 0x02, 0xFF,
                 ],
             ),
+            (cbor_bytes!(vec![]), vec![0x5F, 0xFF]),
+            (
+                cbor_bytes!(vec![0x01, 0x02, 0x03]),
+                vec![0x5F, 0x42, 0x01, 0x02, 0x41, 0x03, 0xFF],
+            ),
+            (cbor_text!(""), vec![0x7F, 0xFF]),
+            (
+                cbor_text!("aüb"),
+                vec![0x7F, 0x61, b'a', 0x62, 0xC3, 0xBC, 0x61, b'b', 0xFF],
+            ),
+            (cbor_text!("水"), vec![0x7F, 0x63, 0xE6, 0xB0, 0xB4, 0xFF]),
+            (cbor_int!(24), vec![0x18, 0x18]),
         ];
         for (value, correct_cbor) in cases {
-            assert_eq!(write_return(value), Some(correct_cbor));
+            let mut encoded_cbor = Vec::new();
+            write_with_options(value.clone(), &mut encoded_cbor, None, &options).unwrap();
+            assert_eq!(encoded_cbor, correct_cbor);
+
+            // Both forms decode to the same value.
+            let definite_cbor = write_return(value.clone()).unwrap();
+            assert_eq!(crate::reader::read(&definite_cbor), Ok(value.clone()));
+            assert_eq!(
+                crate::reader::read_nested_non_strict(&encoded_cbor, None),
+                Ok(value)
+            );
         }
+
+        let mut encoded_cbor = Vec::new();
+        let options = WriterOptions {
+            indefinite_length: true,
+            ..Default::default()
+        };
+        write_with_options(cbor_text!("IETF"), &mut encoded_cbor, None, &options).unwrap();
+        assert_eq!(encoded_cbor, vec![0x7F, 0x64, 0x49, 0x45, 0x54, 0x46, 0xFF]);
     }
 
     #[test]
-    fn test_write_simple() {
+    fn test_write_max_output_len() {
+        let value = cbor_array_vec!((0..1000).collect::<Vec<_>>());
+        let len = write_return(value.clone()).unwrap().len();
+        let options = WriterOptions {
+            max_output_len: Some(len),
+            ..Default::default()
+        };
+        assert_eq!(
+            write_with_options(value.clone(), &mut Vec::new(), None, &options),
+            Ok(len)
+        );
+        let options = WriterOptions {
+            max_output_len: Some(len - 1),
+            ..Default::default()
+        };
+        assert_eq!(
+            write_with_options(value, &mut Vec::new(), None, &options),
+            Err(EncoderError::OutputTooLarge)
+        );
+
+        // The limit is checked as data is appended, not only once encoded.
+        let options = WriterOptions {
+            max_output_len: Some(100),
+            ..Default::default()
+        };
+        let mut encoded_cbor = Vec::new();
+        let value = cbor_array_vec!((0..1_000_000).collect::<Vec<_>>());
+        assert_eq!(
+            write_with_options(value, &mut encoded_cbor, None, &options),
+            Err(EncoderError::OutputTooLarge)
+        );
+        assert!(encoded_cbor.len() <= 100);
+
+        // Keys are counted while buffered for sorting.
+        let mut encoded_cbor = Vec::new();
+        let value = cbor_map! {cbor_bytes!(vec![0x00; 1000]) => 1};
+        assert_eq!(
+            write_with_options(value, &mut encoded_cbor, None, &options),
+            Err(EncoderError::OutputTooLarge)
+        );
+        assert!(encoded_cbor.len() <= 100);
+
+        let value = cbor_map! {"b" => cbor_map! {"d" => 1, "c" => 2}, "a" => 3};
+        let len = write_return(value.clone()).unwrap().len();
+        let options = WriterOptions {
+            max_output_len: Some(len),
+            ..Default::default()
+        };
+        assert_eq!(
+            write_with_options(value, &mut Vec::new(), None, &options),
+            Ok(len)
+        );
+    }
+
+    #[test]
+    fn test_write_bytes_written() {
+        let mut encoded_cbor = vec![0x01, 0x02];
+        let options = WriterOptions::default();
         let cases = vec![
-            (cbor_false!(), vec![0xF4]),
-            (cbor_true!(), vec![0xF5]),
-            (cbor_null!(), vec![0xF6]),
-            (cbor_undefined!(), vec![0xF7]),
+            (cbor_int!(1), 1),
+            (cbor_int!(1000), 3),
+            (cbor_text!("IETF"), 5),
+            (cbor_map! {"b" => cbor_array![2, 3], "a" => 1}, 9),
+            (Value::Float(1.1), 9),
         ];
-        for (value, correct_cbor) in cases {
-            assert_eq!(write_return(value), Some(correct_cbor));
+        for (value, len) in cases {
+            let start = encoded_cbor.len();
+            assert_eq!(
+                write_with_options(value, &mut encoded_cbor, None, &options),
+                Ok(len)
+            );
+            assert_eq!(encoded_cbor.len() - start, len);
+        }
+
+        let options = WriterOptions {
+            indefinite_length: true,
+            ..Default::default()
+        };
+        let start = encoded_cbor.len();
+        assert_eq!(
+            write_with_options(cbor_array![1, 2], &mut encoded_cbor, None, &options),
+            Ok(4)
+        );
+        assert_eq!(encoded_cbor.len() - start, 4);
+    }
+
+    #[test]
+    fn test_write_deeply_nested_unbounded() {
+        let depth = 100_000;
+        let mut value = cbor_int!(0);
+        for _ in 0..depth {
+            value = cbor_array![value];
+        }
+        let encoded_cbor = write_unbounded(value);
+        assert_eq!(encoded_cbor.len(), depth + 1);
+        assert!(encoded_cbor[..depth].iter().all(|b| *b == 0x81));
+
+        // Keys nested inside keys are encoded into separate buffers.
+        let depth = 10_000;
+        let mut value = cbor_int!(0);
+        for _ in 0..depth {
+            value = cbor_map! {value => 1};
+        }
+        let encoded_cbor = write_unbounded(value);
+        assert_eq!(encoded_cbor.len(), 2 * depth + 1);
+        assert_eq!(encoded_cbor[..2], [0xa1, 0xa1]);
+
+        let mut value = cbor_int!(0);
+        for _ in 0..depth {
+            value = cbor_array![value];
         }
+        let mut encoded_cbor = Vec::new();
+        assert_eq!(
+            write_nested(value, &mut encoded_cbor, Some(i8::MAX)),
+            Err(EncoderError::TooMuchNesting)
+        );
     }
 
     #[test]
@@ -483,12 +3411,12 @@ mod test {
         for (value, level) in positive_cases {
             let mut buf = Vec::new();
             let mut writer = Writer::new(&mut buf);
-            assert!(writer.encode_cbor(value, Some(level)).is_ok());
+            assert!(writer.encode_cbor(Node::Value(&value), Some(level)).is_ok());
         }
         for (value, level) in negative_cases {
             let mut buf = Vec::new();
             let mut writer = Writer::new(&mut buf);
-            assert!(!writer.encode_cbor(value, Some(level)).is_ok());
+            assert!(!writer.encode_cbor(Node::Value(&value), Some(level)).is_ok());
         }
     }
 
@@ -504,10 +3432,10 @@ mod test {
 
         let mut buf = Vec::new();
         let mut writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_map.clone(), Some(2)).is_ok());
-        assert!(writer.encode_cbor(cbor_map.clone(), None).is_ok());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), Some(2)).is_ok());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), None).is_ok());
         writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_map, Some(1)).is_err());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), Some(1)).is_err());
     }
 
     #[test]
@@ -527,9 +3455,13 @@ mod test {
 
         let mut buf = Vec::new();
         let mut writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_array.clone(), Some(3)).is_ok());
+        assert!(writer
+            .encode_cbor(Node::Value(&cbor_array), Some(3))
+            .is_ok());
         writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_array, Some(2)).is_err());
+        assert!(writer
+            .encode_cbor(Node::Value(&cbor_array), Some(2))
+            .is_err());
     }
 
     #[test]
@@ -555,9 +3487,9 @@ mod test {
 
         let mut buf = Vec::new();
         let mut writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_map.clone(), Some(5)).is_ok());
-        assert!(writer.encode_cbor(cbor_map.clone(), None).is_ok());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), Some(5)).is_ok());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), None).is_ok());
         writer = Writer::new(&mut buf);
-        assert!(writer.encode_cbor(cbor_map, Some(4)).is_err());
+        assert!(writer.encode_cbor(Node::Value(&cbor_map), Some(4)).is_err());
     }
 }